@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::responses::{ModelCall, ResponsesClient};
+
+pub const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Returns one vector per input, in input order.
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    /// Like `embed`, also returning the billed calls made. Embedders that
+    /// don't call a model report none.
+    async fn embed_with_usage(&self, inputs: &[String]) -> Result<(Vec<Vec<f32>>, Vec<ModelCall>)> {
+        Ok((self.embed(inputs).await?, Vec::new()))
+    }
+}
+
+pub struct OpenAiEmbedder {
+    client: Arc<ResponsesClient>,
+    model: String,
+    batch_size: usize,
+}
+
+impl OpenAiEmbedder {
+    pub fn new(client: Arc<ResponsesClient>, model: impl Into<String>) -> Self {
+        Self {
+            client,
+            model: model.into(),
+            batch_size: 256,
+        }
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAiEmbedder {
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(self.embed_with_usage(inputs).await?.0)
+    }
+
+    async fn embed_with_usage(&self, inputs: &[String]) -> Result<(Vec<Vec<f32>>, Vec<ModelCall>)> {
+        let mut embeddings = Vec::with_capacity(inputs.len());
+        let mut calls = Vec::new();
+        for batch in inputs.chunks(self.batch_size) {
+            let (vectors, usage) = self
+                .client
+                .embeddings_with_usage(&self.model, batch)
+                .await?;
+            embeddings.extend(vectors);
+            calls.push(ModelCall::new(&self.model, usage));
+        }
+        Ok((embeddings, calls))
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
@@ -1,4 +1,5 @@
 pub mod agent;
+pub mod embeddings;
 pub mod oai;
 pub mod responses;
 pub mod schemas;
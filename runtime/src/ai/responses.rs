@@ -1,14 +1,79 @@
 use anyhow::Context;
 use reqwest::{Client, StatusCode};
-use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::{Value, json};
 use tokio::time::{Duration, sleep, timeout};
 use tracing::{debug, info, warn};
 
+/// Tokens billed for one response, as reported in its `usage` field.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    /// Includes `reasoning_tokens`.
+    pub output_tokens: u64,
+    pub reasoning_tokens: u64,
+}
+
+impl TokenUsage {
+    fn from_response(root: &Value) -> Self {
+        let tokens = |pointer: &str| {
+            root.pointer(&format!("/usage/{pointer}"))
+                .and_then(Value::as_u64)
+                .unwrap_or_default()
+        };
+        Self {
+            input_tokens: tokens("input_tokens"),
+            output_tokens: tokens("output_tokens"),
+            reasoning_tokens: tokens("output_tokens_details/reasoning_tokens"),
+        }
+    }
+}
+
+/// A model call and the tokens it was billed for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelCall {
+    pub model: String,
+    pub usage: TokenUsage,
+}
+
+impl ModelCall {
+    pub fn new(model: impl Into<String>, usage: TokenUsage) -> Self {
+        Self {
+            model: model.into(),
+            usage,
+        }
+    }
+
+    /// The call behind `err`, if it failed after the model had used tokens.
+    pub fn from_error(model: &str, err: &anyhow::Error) -> Option<Self> {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<FailedResponse>())
+            .map(|failed| Self::new(model, failed.usage))
+    }
+}
+
+/// A response that failed or was cancelled after it started generating, so
+/// its tokens are still billed.
+#[derive(Debug)]
+pub struct FailedResponse {
+    pub usage: TokenUsage,
+    message: String,
+}
+
+impl std::fmt::Display for FailedResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for FailedResponse {}
+
 pub struct ResponsesClient {
     http: Client,
     api_key: String,
     base: String,
+    reasoning_effort: String,
+    verbosity: Option<String>,
 }
 
 impl ResponsesClient {
@@ -22,9 +87,21 @@ impl ResponsesClient {
             http,
             api_key,
             base: base.unwrap_or_else(|| "https://api.openai.com".into()),
+            reasoning_effort: "high".into(),
+            verbosity: None,
         }
     }
 
+    pub fn with_reasoning_effort(mut self, effort: impl Into<String>) -> Self {
+        self.reasoning_effort = effort.into();
+        self
+    }
+
+    pub fn with_verbosity(mut self, verbosity: impl Into<String>) -> Self {
+        self.verbosity = Some(verbosity.into());
+        self
+    }
+
     fn extract_structured_output<T: DeserializeOwned>(root: &Value) -> Option<T> {
         if let Some(candidate) = root.get("output_parsed") {
             if let Some(parsed) = Self::parse_candidate::<T>(candidate) {
@@ -122,7 +199,11 @@ impl ResponsesClient {
                                     .or_else(|| payload.pointer("/last_error/message"))
                                     .and_then(Value::as_str)
                                     .unwrap_or("no detail provided");
-                                return Err(anyhow::anyhow!("OpenAI background responses | status={status} | detail={detail}, response_id={id}"));
+                                return Err(FailedResponse {
+                                    usage: TokenUsage::from_response(&payload),
+                                    message: format!("OpenAI background responses | status={status} | detail={detail}, response_id={id}"),
+                                }
+                                .into());
                             }
                             _ => debug!(response_id = id, "background job still running"),
                         }
@@ -165,6 +246,94 @@ impl ResponsesClient {
             .await
     }
 
+    pub async fn embeddings(
+        &self,
+        model: &str,
+        inputs: &[String],
+    ) -> anyhow::Result<Vec<Vec<f32>>> {
+        self.embeddings_with_usage(model, inputs)
+            .await
+            .map(|(embeddings, _)| embeddings)
+    }
+
+    /// Like `embeddings`, also returning the tokens the inputs used.
+    pub async fn embeddings_with_usage(
+        &self,
+        model: &str,
+        inputs: &[String],
+    ) -> anyhow::Result<(Vec<Vec<f32>>, TokenUsage)> {
+        if inputs.is_empty() {
+            return Ok((Vec::new(), TokenUsage::default()));
+        }
+
+        let body = json!({
+            "model": model,
+            "input": inputs,
+        });
+
+        let mut delay = Duration::from_millis(300);
+        for attempt in 0..5 {
+            let resp = self.post_json("/embeddings", &body).await?;
+            if resp.status().is_success() {
+                let v: Value = resp
+                    .json()
+                    .await
+                    .with_context(|| "Error from OpenAI embeddings api")?;
+                let mut data = v
+                    .get("data")
+                    .and_then(Value::as_array)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("embeddings response missing data"))?;
+                data.sort_by_key(|item| item.get("index").and_then(Value::as_u64).unwrap_or(0));
+                let embeddings = data
+                    .iter()
+                    .map(|item| {
+                        item.get("embedding")
+                            .and_then(Value::as_array)
+                            .map(|values| {
+                                values
+                                    .iter()
+                                    .filter_map(Value::as_f64)
+                                    .map(|v| v as f32)
+                                    .collect::<Vec<f32>>()
+                            })
+                            .ok_or_else(|| anyhow::anyhow!("embeddings response missing vector"))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                if embeddings.len() != inputs.len() {
+                    return Err(anyhow::anyhow!(
+                        "expected {} embeddings, got {}",
+                        inputs.len(),
+                        embeddings.len()
+                    ));
+                }
+                let usage = TokenUsage {
+                    input_tokens: v
+                        .pointer("/usage/prompt_tokens")
+                        .and_then(Value::as_u64)
+                        .unwrap_or_default(),
+                    ..TokenUsage::default()
+                };
+                return Ok((embeddings, usage));
+            }
+
+            if (matches!(resp.status(), StatusCode::TOO_MANY_REQUESTS)
+                || resp.status().is_server_error())
+                && attempt < 4
+            {
+                tokio::time::sleep(delay).await;
+                delay = Duration::from_millis((delay.as_millis() as f64 * 1.8) as u64)
+                    + Duration::from_millis(fastrand::u64(0..250));
+                continue;
+            }
+
+            let status = resp.status();
+            let err_txt = resp.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!("OpenAI error {}: {}", status, err_txt));
+        }
+        Err(anyhow::anyhow!("Retries exhausted"))
+    }
+
     pub async fn responses_structured<T: DeserializeOwned + Default>(
         &self,
         model: &str,
@@ -175,6 +344,32 @@ impl ResponsesClient {
         schema: Value,
         strict: bool,
     ) -> anyhow::Result<T> {
+        self.responses_structured_with_usage(
+            model,
+            system,
+            user,
+            chunk_id,
+            schema_name,
+            schema,
+            strict,
+        )
+        .await
+        .map(|(parsed, _)| parsed)
+    }
+
+    /// Like `responses_structured`, also returning the tokens the response
+    /// used.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn responses_structured_with_usage<T: DeserializeOwned + Default>(
+        &self,
+        model: &str,
+        system: &str,
+        user: &str,
+        chunk_id: Option<&str>,
+        schema_name: &str,
+        schema: Value,
+        strict: bool,
+    ) -> anyhow::Result<(T, TokenUsage)> {
         let response_format = json!({
             "type": "json_schema",
             "name": schema_name,
@@ -182,14 +377,19 @@ impl ResponsesClient {
             "schema": schema
         });
 
+        let mut text = json!({ "format": response_format });
+        if let Some(verbosity) = &self.verbosity {
+            text["verbosity"] = json!(verbosity);
+        }
+
         let body = json!({
             "model": model,
             "input": [
                 { "role": "system", "content": [{ "type": "input_text", "text": system }] },
                 { "role": "user",   "content": [{ "type": "input_text", "text": user }] }
             ],
-            "text": {"format": response_format},
-            "reasoning": {"effort":"high"},
+            "text": text,
+            "reasoning": {"effort": self.reasoning_effort},
             "service_tier": "flex",
             "background": true,
         });
@@ -206,18 +406,19 @@ impl ResponsesClient {
                     .poll_oai_response(v, "/responses")
                     .await
                     .with_context(|| "Error polling OpenAI responses api")?;
+                let usage = TokenUsage::from_response(&v);
                 if let Some(parsed) = Self::extract_structured_output(&v) {
                     if let Some(id) = chunk_id {
                         info!(chunk_id = %id, "Extracted entity relations for");
                     }
-                    return Ok(parsed);
+                    return Ok((parsed, usage));
                 }
                 let id = v
                     .get("id")
                     .and_then(Value::as_str)
                     .ok_or_else(|| anyhow::anyhow!("missing response id"))?;
                 warn!(response_id=%id, "Structured output not found in response");
-                return Ok(T::default());
+                return Ok((T::default(), usage));
             }
 
             if matches!(resp.status(), StatusCode::TOO_MANY_REQUESTS)
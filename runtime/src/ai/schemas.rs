@@ -1,84 +1,16 @@
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-
-pub const BASE_ENTITY_TYPES: [&str; 14] = [
-    "Gene",
-    "Protein",
-    "Compound",
-    "BiologicalProcess",
-    "MolecularFunction",
-    "CellularComponent",
-    "Pathway",
-    "Disease",
-    "Symptom",
-    "Intervention",
-    "Mechanism",
-    "CellType",
-    "Tissue",
-    "Organism",
-];
-
-pub const LONGEVITY_EXTENSION: [&str; 3] = ["AgingHallmark", "Biomarker", "LifespanModel"];
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-#[serde(rename_all = "PascalCase")]
-pub enum EntityType {
-    #[serde(rename = "Gene")]
-    Gene,
-    #[serde(rename = "Protein")]
-    Protein,
-    #[serde(rename = "Compound")]
-    Compound,
-    #[serde(rename = "BiologicalProcess")]
-    BiologicalProcess,
-    #[serde(rename = "MolecularFunction")]
-    MolecularFunction,
-    #[serde(rename = "CellularComponent")]
-    CellularComponent,
-    #[serde(rename = "Pathway")]
-    Pathway,
-    #[serde(rename = "Disease")]
-    Disease,
-    #[serde(rename = "Symptom")]
-    Symptom,
-    #[serde(rename = "Intervention")]
-    Intervention,
-    #[serde(rename = "Mechanism")]
-    Mechanism,
-    #[serde(rename = "CellType")]
-    CellType,
-    #[serde(rename = "Tissue")]
-    Tissue,
-    #[serde(rename = "Organism")]
-    Organism,
-}
-
-impl EntityType {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            Self::Gene => "Gene",
-            Self::Protein => "Protein",
-            Self::Compound => "Compound",
-            Self::BiologicalProcess => "BiologicalProcess",
-            Self::MolecularFunction => "MolecularFunction",
-            Self::CellularComponent => "CellularComponent",
-            Self::Pathway => "Pathway",
-            Self::Disease => "Disease",
-            Self::Symptom => "Symptom",
-            Self::Intervention => "Intervention",
-            Self::Mechanism => "Mechanism",
-            Self::CellType => "CellType",
-            Self::Tissue => "Tissue",
-            Self::Organism => "Organism",
-        }
-    }
-}
+use ts_rs::TS;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtractedEntity {
     pub entity_name: String,
-    pub entity_type: EntityType,
+    /// One of the ontology's entity types; see `entities_relationships_schema`.
+    pub entity_type: String,
     pub entity_description: String,
+    /// Verbatim quote from the chunk that supports the entity.
+    #[serde(default)]
+    pub evidence: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,6 +19,43 @@ pub struct ExtractedRelationship {
     pub target_entity: String,
     pub relationship_keywords: Vec<String>,
     pub relationship_description: String,
+    /// Verbatim quote from the chunk that supports the relationship.
+    #[serde(default)]
+    pub evidence: String,
+    /// How strongly the text supports the relationship, from 0 to 1.
+    #[serde(default)]
+    pub confidence: Option<f32>,
+    #[serde(default)]
+    pub modality: Modality,
+    /// Words in the text that hedge or negate the claim, e.g. "may".
+    #[serde(default)]
+    pub hedge_cues: Vec<String>,
+    /// One of the ontology's predicates, when it defines any.
+    #[serde(default)]
+    pub predicate: Option<String>,
+}
+
+/// Whether the text states a relationship, only suggests it or denies it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum Modality {
+    #[default]
+    Asserted,
+    /// Stated with hedging such as "may", "suggests" or "is associated with".
+    Hedged,
+    /// Stated as not holding, e.g. "does not" or "failed to".
+    Negated,
+}
+
+impl Modality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Asserted => "asserted",
+            Self::Hedged => "hedged",
+            Self::Negated => "negated",
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -95,6 +64,18 @@ pub struct EntitiesRelationships {
     pub relationships: Vec<ExtractedRelationship>,
 }
 
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct DescriptionSummary {
+    pub description: String,
+}
+
+/// Abstract-style summary and keywords of a whole document.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentAbstract {
+    pub summary: String,
+    pub keywords: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct CalendarEvent {
     pub name: String,
@@ -118,8 +99,19 @@ pub fn calendar_event_schema() -> serde_json::Value {
     })
 }
 
-pub fn entities_relationships_schema() -> serde_json::Value {
-    json!({
+/// Schema for entity and relationship extraction. `entity_types` are the
+/// allowed types and `type_guide` describes them to the model; relationships
+/// are only asked for a predicate when `predicates` is not empty.
+pub fn entities_relationships_schema(
+    entity_types: &[&str],
+    type_guide: &str,
+    predicates: &[&str],
+    predicate_guide: &str,
+) -> serde_json::Value {
+    let entity_type_description = format!(
+        "Categorize the entity using one of the following types:\n{type_guide}\nIf none of the types apply, do not add a new type and classify it as `Other`."
+    );
+    let mut schema = json!({
         "type": "object",
         "additionalProperties": false,
         "properties": {
@@ -135,15 +127,19 @@ pub fn entities_relationships_schema() -> serde_json::Value {
                         },
                         "entity_type": {
                             "type": "string",
-                            "enum": BASE_ENTITY_TYPES.iter().copied().collect::<Vec<_>>(),
-                            "description": "Categorize the entity using one of the following controlled vocabulary. If none of the provided entity types apply, do not add new entity type and classify it as `Other`"
+                            "enum": entity_types,
+                            "description": entity_type_description
                         },
                         "entity_description": {
                             "type": "string",
                             "description": "Provide a concise yet comprehensive description of the entity's attributes and activities, based *solely* on the information present in the input text."
+                        },
+                        "evidence": {
+                            "type": "string",
+                            "description": "The shortest passage of the input text, copied **verbatim**, that mentions the entity. Do not paraphrase or join separate passages."
                         }
                     },
-                    "required": ["entity_name", "entity_type", "entity_description"]
+                    "required": ["entity_name", "entity_type", "entity_description", "evidence"]
                 }
             },
             "relationships": {
@@ -170,12 +166,80 @@ pub fn entities_relationships_schema() -> serde_json::Value {
                         "relationship_description":  {
                             "type": "string",
                             "description": "A concise explanation of the nature of the relationship between the source and target entities, providing a clear rationale for their connection."
+                        },
+                        "evidence": {
+                            "type": "string",
+                            "description": "The sentence of the input text, copied **verbatim**, that states the relationship. Do not paraphrase or join separate passages."
+                        },
+                        "confidence": {
+                            "type": "number",
+                            "description": "How strongly the text supports the relationship, from 0 (barely implied) to 1 (stated directly with supporting results)."
+                        },
+                        "modality": {
+                            "type": "string",
+                            "enum": ["asserted", "hedged", "negated"],
+                            "description": "`asserted` when the text states the relationship, `hedged` when it only suggests it (\"may\", \"might\", \"suggests\", \"is associated with\", \"potentially\"), `negated` when it states the relationship does not hold (\"does not\", \"no effect\", \"failed to\")."
+                        },
+                        "hedge_cues": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "The words of the evidence that hedge or negate the relationship, copied verbatim. Empty for asserted relationships."
                         }
                     },
-                    "required": ["source_entity", "target_entity", "relationship_keywords", "relationship_description"]
+                    "required": ["source_entity", "target_entity", "relationship_keywords", "relationship_description", "evidence", "confidence", "modality", "hedge_cues"]
                 }
             }
         },
         "required": ["entities", "relationships"]
+    });
+    if !predicates.is_empty() {
+        let mut allowed: Vec<serde_json::Value> = predicates.iter().map(|p| json!(p)).collect();
+        allowed.push(serde_json::Value::Null);
+        let predicate_description = format!(
+            "The predicate that types the relationship, from the following vocabulary with the source and target entity types it allows:\n{predicate_guide}\nUse null when none of them fits."
+        );
+        let relationship = &mut schema["properties"]["relationships"]["items"];
+        relationship["properties"]["predicate"] = json!({
+            "type": ["string", "null"],
+            "enum": allowed,
+            "description": predicate_description
+        });
+        if let Some(required) = relationship["required"].as_array_mut() {
+            required.push(json!("predicate"));
+        }
+    }
+    schema
+}
+
+pub fn document_abstract_schema(max_keywords: usize) -> serde_json::Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "summary": {
+                "type": "string",
+                "description": "An abstract of the document in a few sentences: its subject, methods and main findings, using only what the text states."
+            },
+            "keywords": {
+                "type": "array",
+                "description": format!("At most {max_keywords} keywords or key phrases, most important first."),
+                "items": { "type": "string" }
+            }
+        },
+        "required": ["summary", "keywords"]
+    })
+}
+
+pub fn description_summary_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "description": {
+                "type": "string",
+                "description": "One consolidated description that keeps every distinct fact from the input descriptions and states contradictions explicitly."
+            }
+        },
+        "required": ["description"]
     })
 }
@@ -0,0 +1,662 @@
+use std::{net::IpAddr, path::Path, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use tracing::info;
+
+use crate::{
+    ai::embeddings::DEFAULT_EMBEDDING_MODEL,
+    pipeline::{
+        ChunkStrategy, CsvMapping, DescriptionConfig, DocumentSummaryConfig, DryRunConfig,
+        EntityNormalizer, GleaningConfig, HookConfig, HooksConfig, NearDuplicateConfig,
+        NormalizationConfig, Ontology, OntologyConfig, PipelineConfig, PricingConfig,
+        RelationRepairConfig, ResolutionConfig, UrlFetchConfig,
+        WatcherConfig as InputWatcherConfig, scheduler,
+    },
+};
+
+/// Prefix for environment overrides. `APP__LLM__MODEL=gpt-5` replaces
+/// `llm.model`; nested keys are separated by a double underscore.
+pub const ENV_PREFIX: &str = "APP__";
+
+/// Environment variable `storage.workspace` falls back to when unset.
+const WORKSPACE_ENV: &str = "WORKSPACE";
+
+const REASONING_EFFORTS: &[&str] = &["minimal", "low", "medium", "high"];
+const VERBOSITY_LEVELS: &[&str] = &["low", "medium", "high"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub working_dir: String,
+    #[serde(default)]
+    pub llm: LlmConfig,
+    #[serde(default)]
+    pub chunk_config: ChunkingConfig,
+    #[serde(default)]
+    pub scheduler: SchedulerSettings,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub ingestion: IngestionConfig,
+    #[serde(default)]
+    pub watcher: WatcherConfig,
+    #[serde(default)]
+    pub url_ingestion: UrlIngestionConfig,
+    #[serde(default)]
+    pub resolution: ResolutionConfig,
+    #[serde(default)]
+    pub descriptions: DescriptionConfig,
+    #[serde(default)]
+    pub relation_repair: RelationRepairConfig,
+    #[serde(default)]
+    pub ontology: OntologyConfig,
+    #[serde(default)]
+    pub gleaning: GleaningConfig,
+    #[serde(default)]
+    pub pricing: PricingConfig,
+    #[serde(default)]
+    pub dry_run: DryRunConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub document_summary: DocumentSummaryConfig,
+    #[serde(default)]
+    pub normalization: NormalizationConfig,
+    /// The ontology selected by `ontology` for the configured workspace.
+    #[serde(skip)]
+    pub loaded_ontology: Arc<Ontology>,
+    /// The dictionaries listed under `normalization`.
+    #[serde(skip)]
+    pub loaded_normalizer: Arc<EntityNormalizer>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmConfig {
+    pub model: String,
+    pub reasoning_effort: String,
+    pub verbosity: Option<String>,
+    /// Upper bound on chunks sent to the LLM at the same time.
+    pub max_concurrent_chunks: usize,
+    pub embedding_model: String,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            model: "gpt-5-mini".into(),
+            reasoning_effort: "high".into(),
+            verbosity: None,
+            max_concurrent_chunks: 10,
+            embedding_model: DEFAULT_EMBEDDING_MODEL.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChunkingConfig {
+    pub max_tokens: usize,
+    pub overlap: usize,
+    pub split_by_character: Option<String>,
+    pub split_by_character_only: bool,
+    pub strategy: ChunkStrategy,
+    pub semantic_threshold: f32,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens: 500,
+            overlap: 50,
+            split_by_character: None,
+            split_by_character_only: false,
+            strategy: ChunkStrategy::Token,
+            semantic_threshold: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerSettings {
+    pub queue_capacity: u32,
+    pub max_inflight: u8,
+    pub tick_interval_secs: u64,
+    pub chunk_max_retries: u8,
+    /// Number of extraction workers draining the chunk queue.
+    pub workers: usize,
+}
+
+impl Default for SchedulerSettings {
+    fn default() -> Self {
+        Self {
+            queue_capacity: 5,
+            max_inflight: 10,
+            tick_interval_secs: 10,
+            chunk_max_retries: 20,
+            workers: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Storage namespace inside `working_dir`. Falls back to the `WORKSPACE`
+    /// environment variable when unset.
+    pub workspace: Option<String>,
+}
+
+/// Optional polling of the input directory for files copied in directly.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatcherConfig {
+    pub enabled: bool,
+    pub poll_interval_secs: u64,
+    pub debounce_secs: u64,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            poll_interval_secs: 5,
+            debounce_secs: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrlIngestionConfig {
+    pub timeout_secs: u64,
+    pub max_bytes: usize,
+    pub allow_private_addresses: bool,
+}
+
+impl Default for UrlIngestionConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 30,
+            max_bytes: 20 * 1024 * 1024,
+            allow_private_addresses: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IngestionConfig {
+    #[serde(default)]
+    pub csv_mappings: Vec<CsvMapping>,
+    #[serde(default)]
+    pub near_duplicate: NearDuplicateConfig,
+}
+
+impl AppConfig {
+    /// Reads the YAML file at `path`, applies `APP__*` overrides from the
+    /// process environment and validates the result.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read config file at {}", path.display()))?;
+        let config = Self::from_yaml(&contents, std::env::vars())
+            .with_context(|| format!("Invalid config file at {}", path.display()))?;
+        info!(path = %path.display(), "Configuration loaded from disk");
+        Ok(config)
+    }
+
+    pub fn from_yaml(
+        contents: &str,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self> {
+        let mut root: Value = serde_yaml::from_str(contents).context("failed to parse YAML")?;
+        if root.is_null() {
+            root = Value::Mapping(Mapping::new());
+        }
+        let env: Vec<(String, String)> = env.into_iter().collect();
+        let fallback_workspace = env
+            .iter()
+            .find(|(key, _)| key == WORKSPACE_ENV)
+            .map(|(_, value)| value.clone());
+        apply_env_overrides(&mut root, env)?;
+
+        let mut config: AppConfig =
+            serde_yaml::from_value(root).context("failed to deserialize config")?;
+        if config.storage.workspace.is_none() {
+            config.storage.workspace = fallback_workspace;
+        }
+        config.storage.workspace = config
+            .storage
+            .workspace
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty());
+        config.loaded_ontology = Arc::new(
+            config
+                .ontology
+                .load(config.storage.workspace.as_deref())
+                .context("failed to load ontology")?,
+        );
+        config.loaded_normalizer = Arc::new(
+            EntityNormalizer::load(&config.normalization)
+                .context("failed to load normalization dictionaries")?,
+        );
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks every section and reports all problems at once.
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        if self.server.host.parse::<IpAddr>().is_err() && self.server.host != "localhost" {
+            errors.push(format!(
+                "server.host '{}' is not an IP address",
+                self.server.host
+            ));
+        }
+        if self.working_dir.trim().is_empty() {
+            errors.push("working_dir must not be empty".to_string());
+        }
+
+        if self.llm.model.trim().is_empty() {
+            errors.push("llm.model must not be empty".to_string());
+        }
+        if !REASONING_EFFORTS.contains(&self.llm.reasoning_effort.as_str()) {
+            errors.push(format!(
+                "llm.reasoning_effort '{}' must be one of {}",
+                self.llm.reasoning_effort,
+                REASONING_EFFORTS.join(", ")
+            ));
+        }
+        if let Some(verbosity) = &self.llm.verbosity
+            && !VERBOSITY_LEVELS.contains(&verbosity.as_str())
+        {
+            errors.push(format!(
+                "llm.verbosity '{verbosity}' must be one of {}",
+                VERBOSITY_LEVELS.join(", ")
+            ));
+        }
+        if self.llm.max_concurrent_chunks == 0 {
+            errors.push("llm.max_concurrent_chunks must be at least 1".to_string());
+        }
+
+        let chunking = &self.chunk_config;
+        if chunking.max_tokens == 0 {
+            errors.push("chunk_config.max_tokens must be at least 1".to_string());
+        }
+        if chunking.overlap >= chunking.max_tokens {
+            errors.push(format!(
+                "chunk_config.overlap ({}) must be smaller than chunk_config.max_tokens ({})",
+                chunking.overlap, chunking.max_tokens
+            ));
+        }
+        if chunking.split_by_character_only && chunking.split_by_character.is_none() {
+            errors.push(
+                "chunk_config.split_by_character_only requires chunk_config.split_by_character"
+                    .to_string(),
+            );
+        }
+        if !(-1.0..=1.0).contains(&chunking.semantic_threshold) {
+            errors.push(format!(
+                "chunk_config.semantic_threshold ({}) must be between -1 and 1",
+                chunking.semantic_threshold
+            ));
+        }
+
+        if self.scheduler.queue_capacity == 0 {
+            errors.push("scheduler.queue_capacity must be at least 1".to_string());
+        }
+        if self.scheduler.max_inflight == 0 {
+            errors.push("scheduler.max_inflight must be at least 1".to_string());
+        }
+        if self.scheduler.tick_interval_secs == 0 {
+            errors.push("scheduler.tick_interval_secs must be at least 1".to_string());
+        }
+        if self.scheduler.workers == 0 {
+            errors.push("scheduler.workers must be at least 1".to_string());
+        }
+
+        if self.watcher.enabled && self.watcher.poll_interval_secs == 0 {
+            errors.push("watcher.poll_interval_secs must be at least 1".to_string());
+        }
+
+        if self.url_ingestion.timeout_secs == 0 {
+            errors.push("url_ingestion.timeout_secs must be at least 1".to_string());
+        }
+        if self.url_ingestion.max_bytes == 0 {
+            errors.push("url_ingestion.max_bytes must be at least 1".to_string());
+        }
+
+        let resolution = &self.resolution;
+        if !(0.0..=1.0).contains(&resolution.review_threshold)
+            || !(0.0..=1.0).contains(&resolution.auto_merge_threshold)
+        {
+            errors.push("resolution thresholds must be between 0 and 1".to_string());
+        } else if resolution.review_threshold > resolution.auto_merge_threshold {
+            errors.push(format!(
+                "resolution.review_threshold ({}) must not exceed auto_merge_threshold ({})",
+                resolution.review_threshold, resolution.auto_merge_threshold
+            ));
+        }
+
+        if self.descriptions.token_budget == 0 {
+            errors.push("descriptions.token_budget must be at least 1".to_string());
+        }
+        if self.document_summary.max_input_tokens == 0 {
+            errors.push("document_summary.max_input_tokens must be at least 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.relation_repair.fuzzy_threshold) {
+            errors.push(format!(
+                "relation_repair.fuzzy_threshold ({}) must be between 0 and 1",
+                self.relation_repair.fuzzy_threshold
+            ));
+        }
+
+        for (model, pricing) in &self.pricing.models {
+            if pricing.input_per_million < 0.0 || pricing.output_per_million < 0.0 {
+                errors.push(format!(
+                    "pricing.models.{model} prices must not be negative"
+                ));
+            }
+        }
+
+        if self.dry_run.output_token_ratio < 0.0 {
+            errors.push(format!(
+                "dry_run.output_token_ratio ({}) must not be negative",
+                self.dry_run.output_token_ratio
+            ));
+        }
+
+        for dictionary in &self.normalization.dictionaries {
+            let known = self
+                .loaded_ontology
+                .type_names()
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&dictionary.entity_type));
+            if !known {
+                errors.push(format!(
+                    "normalization: dictionary '{}' is for entity type '{}', which is not in ontology '{}'",
+                    dictionary.path, dictionary.entity_type, self.loaded_ontology.name
+                ));
+            }
+        }
+
+        for hook in self.hooks.for_workspace(self.storage.workspace.as_deref()) {
+            if let HookConfig::EntityFilter(filter) = hook {
+                for entity_type in &filter.drop_types {
+                    let known = self
+                        .loaded_ontology
+                        .type_names()
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(entity_type));
+                    if !known {
+                        errors.push(format!(
+                            "hooks: entity_filter drops entity type '{entity_type}', which is not in ontology '{}'",
+                            self.loaded_ontology.name
+                        ));
+                    }
+                }
+            }
+        }
+
+        let near_duplicate = &self.ingestion.near_duplicate;
+        if !(0.0..=1.0).contains(&near_duplicate.threshold) {
+            errors.push(format!(
+                "ingestion.near_duplicate.threshold ({}) must be between 0 and 1",
+                near_duplicate.threshold
+            ));
+        }
+        if near_duplicate.shingle_size == 0 || near_duplicate.num_hashes == 0 {
+            errors.push(
+                "ingestion.near_duplicate.shingle_size and num_hashes must be at least 1"
+                    .to_string(),
+            );
+        }
+
+        for mapping in &self.ingestion.csv_mappings {
+            if let Err(err) = mapping.validate() {
+                errors.push(format!("ingestion.csv_mappings: {err}"));
+            }
+            let column_type = |column: &str| {
+                mapping
+                    .entities
+                    .iter()
+                    .find(|entity| entity.column == column)
+                    .map(|entity| entity.entity_type.as_str())
+            };
+            for relation in &mapping.relations {
+                let Some(predicate) = &relation.predicate else {
+                    continue;
+                };
+                if let Err(err) = self.loaded_ontology.resolve_predicate(
+                    predicate,
+                    column_type(&relation.source_column),
+                    column_type(&relation.target_column),
+                ) {
+                    errors.push(format!("ingestion.csv_mappings: '{}': {err}", mapping.name));
+                }
+            }
+            for entity in &mapping.entities {
+                if self.loaded_ontology.get(&entity.entity_type).is_none() {
+                    errors.push(format!(
+                        "ingestion.csv_mappings: '{}' uses entity type '{}', which is not in ontology '{}'",
+                        mapping.name, entity.entity_type, self.loaded_ontology.name
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "invalid configuration:\n  - {}",
+                errors.join("\n  - ")
+            ))
+        }
+    }
+
+    pub fn pipeline_config(&self) -> PipelineConfig {
+        PipelineConfig {
+            chunk_size: self.chunk_config.max_tokens,
+            chunk_overlap: self.chunk_config.overlap,
+            split_by_character: self.chunk_config.split_by_character.clone(),
+            split_by_character_only: self.chunk_config.split_by_character_only,
+            chunk_strategy: self.chunk_config.strategy,
+            semantic_threshold: self.chunk_config.semantic_threshold,
+            embedding_model: self.llm.embedding_model.clone(),
+            llm_model: self.llm.model.clone(),
+            max_concurrent_chunks: self.llm.max_concurrent_chunks,
+            csv_mappings: self.ingestion.csv_mappings.clone(),
+            near_duplicate: self.ingestion.near_duplicate.clone(),
+            resolution: self.resolution.clone(),
+            descriptions: self.descriptions.clone(),
+            relation_repair: self.relation_repair.clone(),
+            ontology: self.loaded_ontology.clone(),
+            gleaning: self.gleaning.clone(),
+            pricing: self.pricing.clone(),
+            dry_run: self.dry_run.clone(),
+            hooks: self.hooks.for_workspace(self.storage.workspace.as_deref()),
+            document_summary: self.document_summary.clone(),
+            normalizer: self.loaded_normalizer.clone(),
+        }
+    }
+
+    pub fn watcher_config(&self) -> InputWatcherConfig {
+        InputWatcherConfig {
+            poll_interval: Duration::from_secs(self.watcher.poll_interval_secs),
+            debounce: Duration::from_secs(self.watcher.debounce_secs),
+        }
+    }
+
+    pub fn url_fetch_config(&self) -> UrlFetchConfig {
+        UrlFetchConfig {
+            timeout: Duration::from_secs(self.url_ingestion.timeout_secs),
+            max_bytes: self.url_ingestion.max_bytes,
+            allow_private_addresses: self.url_ingestion.allow_private_addresses,
+        }
+    }
+
+    pub fn scheduler_config(&self) -> scheduler::SchedulerConfig {
+        scheduler::SchedulerConfig {
+            max_inflight: self.scheduler.max_inflight,
+            queue_capacity: self.scheduler.queue_capacity,
+            workers: self.scheduler.workers,
+            tick_interval: Duration::from_secs(self.scheduler.tick_interval_secs),
+            chunk_max_retries: self.scheduler.chunk_max_retries,
+        }
+    }
+}
+
+/// Writes every `APP__SECTION__KEY=value` pair into `root`. Values are parsed
+/// as YAML scalars, so numbers, booleans and `null` keep their type.
+fn apply_env_overrides(
+    root: &mut Value,
+    env: impl IntoIterator<Item = (String, String)>,
+) -> Result<()> {
+    for (key, raw) in env {
+        let Some(path) = key.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let segments: Vec<String> = path.split("__").map(str::to_lowercase).collect();
+        if segments.iter().any(String::is_empty) {
+            return Err(anyhow!("malformed config override '{key}'"));
+        }
+
+        let value = match serde_yaml::from_str::<Value>(&raw) {
+            Ok(value @ (Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_))) => {
+                value
+            }
+            _ => Value::String(raw.clone()),
+        };
+
+        let mut node = &mut *root;
+        for (idx, segment) in segments.iter().enumerate() {
+            let map = node
+                .as_mapping_mut()
+                .ok_or_else(|| anyhow!("config override '{key}' targets a non-mapping value"))?;
+            let segment = Value::String(segment.clone());
+            if idx + 1 == segments.len() {
+                map.insert(segment, value);
+                break;
+            }
+            node = map
+                .entry(segment)
+                .or_insert_with(|| Value::Mapping(Mapping::new()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+server:
+  host: 127.0.0.1
+  port: 42069
+llm:
+  max_concurrent_chunks: 10
+  model: gpt-5-mini
+  reasoning_effort: high
+chunk_config:
+  max_tokens: 500
+  overlap: 50
+working_dir: "data"
+"#;
+
+    fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn env_overrides_keep_scalar_types() {
+        let config = AppConfig::from_yaml(
+            BASE,
+            env(&[
+                ("APP__LLM__MODEL", "gpt-5"),
+                ("APP__CHUNK_CONFIG__MAX_TOKENS", "800"),
+                ("APP__CHUNK_CONFIG__STRATEGY", "sentence"),
+                ("APP__SCHEDULER__TICK_INTERVAL_SECS", "2"),
+                ("APP_CONFIG_PATH", "ignored.yaml"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.llm.model, "gpt-5");
+        assert_eq!(config.chunk_config.max_tokens, 800);
+        assert_eq!(config.chunk_config.strategy, ChunkStrategy::Sentence);
+        assert_eq!(
+            config.scheduler_config().tick_interval,
+            Duration::from_secs(2)
+        );
+    }
+
+    #[test]
+    fn workspace_env_is_a_fallback_for_the_config_value() {
+        let config = AppConfig::from_yaml(BASE, env(&[("WORKSPACE", " aging ")])).unwrap();
+        assert_eq!(config.storage.workspace.as_deref(), Some("aging"));
+
+        let config = AppConfig::from_yaml(
+            BASE,
+            env(&[
+                ("WORKSPACE", "aging"),
+                ("APP__STORAGE__WORKSPACE", "oncology"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.storage.workspace.as_deref(), Some("oncology"));
+    }
+
+    #[test]
+    fn validation_reports_every_problem() {
+        let err = AppConfig::from_yaml(
+            BASE,
+            env(&[
+                ("APP__CHUNK_CONFIG__OVERLAP", "600"),
+                ("APP__LLM__REASONING_EFFORT", "extreme"),
+            ]),
+        )
+        .unwrap_err();
+        let message = format!("{err:#}");
+
+        assert!(message.contains("chunk_config.overlap (600)"));
+        assert!(message.contains("llm.reasoning_effort 'extreme'"));
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let err =
+            AppConfig::from_yaml(BASE, env(&[("APP__SCHEDULER__TICK_INTERVAL", "2")])).unwrap_err();
+        assert!(format!("{err:#}").contains("tick_interval"));
+
+        let err = AppConfig::from_yaml(&format!("{BASE}messages:\n  greeting: hi\n"), env(&[]))
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("messages"));
+    }
+
+    #[test]
+    fn scheduler_workers_are_configured_separately() {
+        let config = AppConfig::from_yaml(BASE, env(&[])).unwrap();
+        assert_eq!(config.scheduler_config().workers, 20);
+
+        let config = AppConfig::from_yaml(BASE, env(&[("APP__SCHEDULER__WORKERS", "4")])).unwrap();
+        assert_eq!(config.scheduler_config().workers, 4);
+    }
+}
@@ -21,12 +21,14 @@ use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 
 mod ai;
+mod config;
 mod pipeline;
 mod routes;
 mod storage;
 use ai::responses::ResponsesClient;
+use config::AppConfig;
 use pipeline::{
-    AppStorages, DocumentManager, Pipeline,
+    AppStorages, CostEstimator, DocumentManager, InputWatcher, Pipeline, UrlFetcher,
     scheduler::{JobDispatch, JobResult, Scheduler},
 };
 use storage::{
@@ -38,12 +40,6 @@ use storage::{
 const DEFAULT_CONFIG_PATH: &str = "config/app.yaml";
 pub(crate) const SUPPORTED_EXTENSIONS: &[&str] = &[".txt", ".md", ".json", ".csv"];
 
-#[derive(Debug, Clone, Deserialize)]
-struct AppConfig {
-    server: ServerConfig,
-    working_dir: String,
-}
-
 #[derive(Clone)]
 pub(crate) struct AppState {
     config: Arc<AppConfig>,
@@ -52,22 +48,123 @@ pub(crate) struct AppState {
     storages_status: StoragesStatus,
     ai_client: Arc<ResponsesClient>,
     scheduler: Arc<Scheduler>,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-struct ServerConfig {
-    host: String,
-    port: u16,
+    url_fetcher: Arc<UrlFetcher>,
+    estimator: Arc<CostEstimator>,
 }
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("dry-run") {
+        if let Err(err) = dry_run(&args[1..]).await {
+            eprintln!("Dry run failed: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
     if let Err(err) = run().await {
         error!(error = %err, "Backend crashed");
         eprintln!("Backend crashed: {err}");
     }
 }
 
+/// `runtime dry-run [--json] <paths...>`: chunks the given files, and the
+/// supported files under the given directories, and prints the chunks,
+/// tokens and estimated extraction cost of each without ingesting anything.
+async fn dry_run(args: &[String]) -> Result<()> {
+    let json = args.iter().any(|arg| arg == "--json");
+    let paths: Vec<PathBuf> = args
+        .iter()
+        .filter(|arg| !arg.starts_with("--"))
+        .map(PathBuf::from)
+        .collect();
+    if paths.is_empty() {
+        anyhow::bail!("usage: runtime dry-run [--json] <file or directory>...");
+    }
+
+    let config = AppConfig::load(&config_path())
+        .await
+        .context("Failed to load application configuration")?;
+    let estimator = CostEstimator::new(config.pipeline_config(), SUPPORTED_EXTENSIONS)?;
+
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            collect_supported_files(&estimator, &path, &mut files).await?;
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    let mut report = estimator.report();
+    for file in files {
+        let bytes = fs::read(&file)
+            .await
+            .with_context(|| format!("failed to read {}", file.display()))?;
+        report.push(estimator.estimate(&file.to_string_lossy(), bytes).await);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    let cost = |cost: Option<f64>| cost.map_or("-".to_string(), |cost| format!("${cost:.4}"));
+    println!(
+        "{:<48} {:>7} {:>10} {:>6} {:>12} {:>12} {:>10}",
+        "file", "chunks", "tokens", "calls", "input", "output", "cost"
+    );
+    for file in &report.files {
+        if let Some(err) = &file.error {
+            println!("{:<48} error: {err}", file.file_name);
+            continue;
+        }
+        println!(
+            "{:<48} {:>7} {:>10} {:>6} {:>12} {:>12} {:>10}",
+            file.file_name,
+            file.chunks,
+            file.tokens,
+            file.llm_calls,
+            file.estimated_input_tokens,
+            file.estimated_output_tokens,
+            cost(file.estimated_cost_usd)
+        );
+    }
+    println!(
+        "{:<48} {:>7} {:>10} {:>6} {:>12} {:>12} {:>10}",
+        format!("total ({} files, {})", report.files.len(), report.model),
+        report.chunks,
+        report.tokens,
+        report.llm_calls,
+        report.estimated_input_tokens,
+        report.estimated_output_tokens,
+        cost(Some(report.estimated_cost_usd))
+    );
+    Ok(())
+}
+
+async fn collect_supported_files(
+    estimator: &CostEstimator,
+    dir: &std::path::Path,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("failed to read directory {}", dir.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                dirs.push(path);
+            } else if estimator.is_supported(&path.to_string_lossy()) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
 async fn run() -> Result<()> {
     init_tracing();
     if let Err(err) = dotenv() {
@@ -80,14 +177,11 @@ async fn run() -> Result<()> {
     let work_rx = Arc::new(Mutex::new(work_rx));
     let job_result_rx = Arc::new(Mutex::new(job_result_rx));
 
-    let config = load_config()
+    let config = AppConfig::load(&config_path())
         .await
         .context("Failed to load application configuration")?;
     let working_dir = PathBuf::from(&config.working_dir);
-    let workspace = env::var("WORKSPACE")
-        .ok()
-        .map(|value| value.trim().to_string())
-        .filter(|value| !value.is_empty());
+    let workspace = config.storage.workspace.clone();
 
     let full_docs = Arc::new(JsonKvStorage::new(JsonKvStorageConfig {
         working_dir: working_dir.clone(),
@@ -119,6 +213,36 @@ async fn run() -> Result<()> {
         workspace: workspace.clone(),
     }));
 
+    let llm_usage = Arc::new(JsonKvStorage::new(JsonKvStorageConfig {
+        working_dir: working_dir.clone(),
+        namespace: "llm_usage".into(),
+        workspace: workspace.clone(),
+    }));
+
+    let doc_fingerprints = Arc::new(JsonKvStorage::new(JsonKvStorageConfig {
+        working_dir: working_dir.clone(),
+        namespace: "doc_fingerprints".into(),
+        workspace: workspace.clone(),
+    }));
+
+    let canonical_entities = Arc::new(JsonKvStorage::new(JsonKvStorageConfig {
+        working_dir: working_dir.clone(),
+        namespace: "canonical_entities".into(),
+        workspace: workspace.clone(),
+    }));
+
+    let entity_embeddings = Arc::new(JsonKvStorage::new(JsonKvStorageConfig {
+        working_dir: working_dir.clone(),
+        namespace: "entity_embeddings".into(),
+        workspace: workspace.clone(),
+    }));
+
+    let entity_merges = Arc::new(JsonKvStorage::new(JsonKvStorageConfig {
+        working_dir: working_dir.clone(),
+        namespace: "entity_merges".into(),
+        workspace: workspace.clone(),
+    }));
+
     let doc_status_storage = Arc::new(JsonDocStatusStorage::new(JsonDocStatusConfig {
         working_dir: working_dir.clone(),
         namespace: "doc_status".into(),
@@ -131,6 +255,11 @@ async fn run() -> Result<()> {
     storage_manager.register_kv(full_entities.clone());
     storage_manager.register_kv(full_relations.clone());
     storage_manager.register_kv(llm_response_cache.clone());
+    storage_manager.register_kv(llm_usage.clone());
+    storage_manager.register_kv(doc_fingerprints.clone());
+    storage_manager.register_kv(canonical_entities.clone());
+    storage_manager.register_kv(entity_embeddings.clone());
+    storage_manager.register_kv(entity_merges.clone());
     storage_manager.register_doc_status(doc_status_storage.clone());
     storage_manager.initialize_all().await?;
 
@@ -140,6 +269,11 @@ async fn run() -> Result<()> {
         full_entities,
         full_relations,
         llm_response_cache,
+        llm_usage,
+        doc_fingerprints,
+        canonical_entities,
+        entity_embeddings,
+        entity_merges,
         doc_status: doc_status_storage.clone(),
     });
 
@@ -150,18 +284,23 @@ async fn run() -> Result<()> {
     )
     .await?;
 
-    let ai_client = Arc::new(ResponsesClient::new(api_key, None));
+    let mut ai_client =
+        ResponsesClient::new(api_key, None).with_reasoning_effort(&config.llm.reasoning_effort);
+    if let Some(verbosity) = &config.llm.verbosity {
+        ai_client = ai_client.with_verbosity(verbosity);
+    }
+    let ai_client = Arc::new(ai_client);
 
     let pipeline = Arc::new(Pipeline::new(
         storages.clone(),
         document_manager,
         ai_client.clone(),
+        config.pipeline_config(),
     ));
 
     // scheduler
     let scheduler = Arc::new(Scheduler::new(
-        10,
-        5,
+        config.scheduler_config(),
         work_tx,
         job_result_rx,
         pipeline.clone(),
@@ -178,6 +317,10 @@ async fn run() -> Result<()> {
         }
     });
 
+    if config.watcher.enabled {
+        InputWatcher::new(pipeline.clone(), scheduler.clone(), config.watcher_config()).spawn();
+    }
+
     let state = Arc::new(AppState {
         config: Arc::new(config.clone()),
         storages,
@@ -185,6 +328,11 @@ async fn run() -> Result<()> {
         storages_status: storage_manager.status(),
         ai_client,
         scheduler,
+        url_fetcher: Arc::new(UrlFetcher::new(config.url_fetch_config())),
+        estimator: Arc::new(CostEstimator::new(
+            config.pipeline_config(),
+            SUPPORTED_EXTENSIONS,
+        )?),
     });
 
     let addr_string = format!("{}:{}", config.server.host, config.server.port);
@@ -203,6 +351,8 @@ async fn run() -> Result<()> {
         .route("/health", get(health))
         .merge(routes::document_routes())
         .merge(routes::graph_routes())
+        .merge(routes::entity_routes())
+        .merge(routes::usage_routes())
         .merge(routes::download_routes())
         .with_state(state)
         .layer(cors);
@@ -233,17 +383,6 @@ fn init_tracing() {
         .init();
 }
 
-async fn load_config() -> Result<AppConfig> {
-    let path = config_path();
-    let contents = fs::read_to_string(&path)
-        .await
-        .with_context(|| format!("Failed to read config file at {}", path.display()))?;
-    let config: AppConfig = serde_yaml::from_str(&contents)
-        .with_context(|| format!("Failed to parse config file at {}", path.display()))?;
-    info!(path = %path.display(), "Configuration loaded from disk");
-    Ok(config)
-}
-
 fn config_path() -> PathBuf {
     env::var("APP_CONFIG_PATH")
         .map(PathBuf::from)
@@ -254,7 +393,7 @@ async fn handler(State(state): State<Arc<AppState>>) -> Result<String, StatusCod
     let _docs = state
         .storages
         .doc_status
-        .docs_paginated(None, 1, 10, "updated_at", "desc")
+        .docs_paginated(None, None, 1, 10, "updated_at", "desc")
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     Ok("Connected".to_owned())
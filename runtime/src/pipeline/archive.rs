@@ -0,0 +1,316 @@
+use std::{
+    io::{Cursor, Read},
+    path::{Component, Path},
+};
+
+use anyhow::{Context, Result, anyhow};
+use flate2::read::GzDecoder;
+
+/// Guards applied while unpacking an uploaded archive. Exceeding any of the
+/// archive-wide limits aborts the whole upload; per-entry problems only
+/// reject that entry.
+#[derive(Debug, Clone)]
+pub struct ArchiveLimits {
+    pub max_entries: usize,
+    pub max_entry_bytes: u64,
+    pub max_total_bytes: u64,
+    /// Largest allowed uncompressed/compressed size ratio for a zip entry.
+    pub max_compression_ratio: u64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 5_000,
+            max_entry_bytes: 64 * 1024 * 1024,
+            max_total_bytes: 1024 * 1024 * 1024,
+            max_compression_ratio: 200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveKind {
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let lower = file_name.to_ascii_lowercase();
+        if lower.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// One file found in an archive. `contents` is `Err` with the reason when
+/// the entry was rejected.
+#[derive(Debug)]
+pub struct ArchiveEntry {
+    /// Path as recorded in the archive.
+    pub path: String,
+    /// Final path component, used as the document file name.
+    pub file_name: Option<String>,
+    pub contents: std::result::Result<Vec<u8>, String>,
+}
+
+/// Unpacks every regular file in `bytes` into memory. Directories, links and
+/// hidden files are skipped.
+pub fn unpack_archive(
+    kind: ArchiveKind,
+    bytes: &[u8],
+    limits: &ArchiveLimits,
+) -> Result<Vec<ArchiveEntry>> {
+    match kind {
+        ArchiveKind::Zip => unpack_zip(bytes, limits),
+        ArchiveKind::TarGz => unpack_tar_gz(bytes, limits),
+    }
+}
+
+fn unpack_zip(bytes: &[u8], limits: &ArchiveLimits) -> Result<Vec<ArchiveEntry>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).context("invalid zip archive")?;
+    if archive.len() > limits.max_entries {
+        return Err(anyhow!(
+            "archive has {} entries, the limit is {}",
+            archive.len(),
+            limits.max_entries
+        ));
+    }
+
+    let mut budget = ByteBudget::new(limits.max_total_bytes);
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let mut file = archive
+            .by_index(index)
+            .with_context(|| format!("failed to read zip entry {index}"))?;
+        let is_symlink = file
+            .unix_mode()
+            .is_some_and(|mode| mode & 0o170000 == 0o120000);
+        if file.is_dir() || is_symlink {
+            continue;
+        }
+
+        let path = file.name().to_string();
+        let Some(file_name) = entry_file_name(&path) else {
+            entries.push(rejected(path, None, "path escapes the archive root"));
+            continue;
+        };
+        if file_name.starts_with('.') {
+            continue;
+        }
+        if file.size() > limits.max_entry_bytes {
+            entries.push(rejected(path, Some(file_name), "file is too large"));
+            continue;
+        }
+        if file.size() > file.compressed_size().max(1) * limits.max_compression_ratio {
+            return Err(anyhow!(
+                "entry '{path}' exceeds the compression ratio limit; refusing archive"
+            ));
+        }
+
+        let contents = read_limited(&mut file, limits.max_entry_bytes)
+            .with_context(|| format!("failed to decompress '{path}'"))?;
+        match contents {
+            Some(contents) => {
+                budget.spend(contents.len() as u64)?;
+                entries.push(ArchiveEntry {
+                    path,
+                    file_name: Some(file_name),
+                    contents: Ok(contents),
+                });
+            }
+            None => entries.push(rejected(path, Some(file_name), "file is too large")),
+        }
+    }
+    Ok(entries)
+}
+
+fn unpack_tar_gz(bytes: &[u8], limits: &ArchiveLimits) -> Result<Vec<ArchiveEntry>> {
+    let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+    let mut budget = ByteBudget::new(limits.max_total_bytes);
+    let mut entries = Vec::new();
+    let mut seen = 0usize;
+
+    for entry in archive.entries().context("invalid tar.gz archive")? {
+        let mut entry = entry.context("failed to read tar entry")?;
+        seen += 1;
+        if seen > limits.max_entries {
+            return Err(anyhow!(
+                "archive has more than {} entries",
+                limits.max_entries
+            ));
+        }
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let Some(file_name) = entry_file_name(&path) else {
+            entries.push(rejected(path, None, "path escapes the archive root"));
+            continue;
+        };
+        if file_name.starts_with('.') {
+            continue;
+        }
+        if entry.size() > limits.max_entry_bytes {
+            entries.push(rejected(path, Some(file_name), "file is too large"));
+            continue;
+        }
+
+        let contents = read_limited(&mut entry, limits.max_entry_bytes)
+            .with_context(|| format!("failed to read '{path}'"))?;
+        match contents {
+            Some(contents) => {
+                budget.spend(contents.len() as u64)?;
+                entries.push(ArchiveEntry {
+                    path,
+                    file_name: Some(file_name),
+                    contents: Ok(contents),
+                });
+            }
+            None => entries.push(rejected(path, Some(file_name), "file is too large")),
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the file name of an archive entry, or `None` when the path is
+/// absolute or climbs out of the archive root.
+fn entry_file_name(path: &str) -> Option<String> {
+    let normalized = path.replace('\\', "/");
+    let path = Path::new(&normalized);
+    let mut file_name = None;
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => file_name = part.to_str(),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    // windows drive letters survive as a normal component on unix
+    if normalized.as_bytes().get(1) == Some(&b':') {
+        return None;
+    }
+    file_name.map(str::to_string)
+}
+
+/// Reads at most `limit` bytes; `None` means the stream was longer, which
+/// catches entries whose header lies about their size.
+fn read_limited(reader: &mut impl Read, limit: u64) -> Result<Option<Vec<u8>>> {
+    let mut contents = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut contents)?;
+    if contents.len() as u64 > limit {
+        Ok(None)
+    } else {
+        Ok(Some(contents))
+    }
+}
+
+fn rejected(path: String, file_name: Option<String>, reason: &str) -> ArchiveEntry {
+    ArchiveEntry {
+        path,
+        file_name,
+        contents: Err(reason.to_string()),
+    }
+}
+
+struct ByteBudget {
+    remaining: u64,
+    limit: u64,
+}
+
+impl ByteBudget {
+    fn new(limit: u64) -> Self {
+        Self {
+            remaining: limit,
+            limit,
+        }
+    }
+
+    fn spend(&mut self, bytes: u64) -> Result<()> {
+        self.remaining = self.remaining.checked_sub(bytes).ok_or_else(|| {
+            anyhow!(
+                "archive expands to more than {} bytes; refusing archive",
+                self.limit
+            )
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn zip_with(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        for (name, contents) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn zip_entries_are_flattened_and_traversal_rejected() {
+        let bytes = zip_with(&[
+            ("papers/a.md", b"# A"),
+            ("../../etc/passwd", b"root"),
+            ("papers/.DS_Store", b""),
+        ]);
+        let entries = unpack_archive(ArchiveKind::Zip, &bytes, &ArchiveLimits::default()).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].file_name.as_deref(), Some("a.md"));
+        assert_eq!(entries[0].contents.as_deref().unwrap(), b"# A");
+        assert!(entries[1].file_name.is_none());
+        assert!(entries[1].contents.is_err());
+    }
+
+    #[test]
+    fn zip_bombs_are_refused() {
+        let bytes = zip_with(&[("bomb.txt", &vec![b'a'; 1024 * 1024])]);
+        let limits = ArchiveLimits {
+            max_compression_ratio: 50,
+            ..ArchiveLimits::default()
+        };
+        assert!(unpack_archive(ArchiveKind::Zip, &bytes, &limits).is_err());
+
+        let limits = ArchiveLimits {
+            max_total_bytes: 1024,
+            ..ArchiveLimits::default()
+        };
+        let bytes = zip_with(&[("a.txt", &[b'x'; 800]), ("b.txt", &[b'y'; 800])]);
+        assert!(unpack_archive(ArchiveKind::Zip, &bytes, &limits).is_err());
+    }
+
+    #[test]
+    fn tar_gz_entries_are_read() {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let contents = b"hello";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "dump/notes.txt", &contents[..])
+            .unwrap();
+        let bytes = builder.into_inner().unwrap().finish().unwrap();
+
+        let entries =
+            unpack_archive(ArchiveKind::TarGz, &bytes, &ArchiveLimits::default()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].file_name.as_deref(), Some("notes.txt"));
+    }
+}
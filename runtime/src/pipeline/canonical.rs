@@ -0,0 +1,323 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::Result;
+use serde_json::{Value, json};
+
+use crate::storage::{JsonKvStorage, KvStorage};
+
+use super::{
+    normalization::DictionaryEntry,
+    types::{EntityNode, RelationEdge},
+    utils::compute_mdhash_id,
+};
+
+/// Lowercases, collapses whitespace and strips surrounding punctuation so
+/// "Rapamycin", "rapamycin " and "\"Rapamycin.\"" share one canonical entity.
+pub fn normalize_entity_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(|c: char| !c.is_alphanumeric() && !matches!(c, ')' | ']' | '+' | '-'))
+        .to_lowercase()
+}
+
+/// Id of the canonical entity for a name and type, shared by every document.
+pub fn canonical_entity_id(name: &str, entity_type: &str) -> String {
+    compute_mdhash_id(
+        &format!(
+            "{}:{}",
+            normalize_entity_name(name),
+            entity_type.trim().to_lowercase()
+        ),
+        "canonical-",
+    )
+}
+
+/// A per-document entity record about to be linked to its canonical entity.
+#[derive(Debug, Clone)]
+pub struct Mention {
+    pub mention_id: String,
+    pub entity_name: String,
+    pub entity_type: String,
+    pub entity_description: String,
+    pub doc_id: String,
+    pub normalization: Option<DictionaryEntry>,
+}
+
+impl Mention {
+    /// Canonical id this mention links to: that of its dictionary identifier
+    /// when it was normalized, so every surface form of it shares one.
+    pub fn canonical_id(&self) -> String {
+        match &self.normalization {
+            Some(entry) => canonical_entity_id(&entry.id, &self.entity_type),
+            None => canonical_entity_id(&self.entity_name, &self.entity_type),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LinkOutcome {
+    /// Canonical id each mention was linked to.
+    pub canonical_ids: HashMap<String, String>,
+}
+
+/// Longest chain of merge redirects followed before giving up.
+const MAX_REDIRECTS: usize = 8;
+
+/// Adds mentions to their canonical entities, creating them as needed.
+/// Canonical records keep the display name and description of the first
+/// mention, the ids of all mentions and the documents they came from. Names
+/// whose canonical entity was merged into another follow the redirect.
+pub async fn link_mentions(storage: &JsonKvStorage, mentions: &[Mention]) -> Result<LinkOutcome> {
+    let mut by_canonical: HashMap<String, (Option<Value>, Vec<&Mention>)> = HashMap::new();
+    for mention in mentions {
+        let derived = mention.canonical_id();
+        let (canonical_id, record) = follow_redirects(storage, derived).await?;
+        by_canonical
+            .entry(canonical_id)
+            .or_insert_with(|| (record, Vec::new()))
+            .1
+            .push(mention);
+    }
+
+    let mut outcome = LinkOutcome::default();
+    let mut updates = HashMap::new();
+    for (canonical_id, (record, mentions)) in by_canonical {
+        let first = mentions[0];
+        let mut record = match record {
+            Some(record) => record,
+            None => json!({
+                "entity_name": first.entity_name.trim(),
+                "entity_type": first.entity_type,
+                "entity_description": first.entity_description,
+                "normalized_name": normalize_entity_name(&first.entity_name),
+                "normalization": first.normalization,
+            }),
+        };
+        let mut mention_ids = string_set(&record, "mention_ids");
+        let mut doc_ids = string_set(&record, "doc_ids");
+        for mention in mentions {
+            mention_ids.insert(mention.mention_id.clone());
+            doc_ids.insert(mention.doc_id.clone());
+            outcome
+                .canonical_ids
+                .insert(mention.mention_id.clone(), canonical_id.clone());
+        }
+        record["mention_count"] = json!(mention_ids.len());
+        record["mention_ids"] = json!(mention_ids);
+        record["doc_ids"] = json!(doc_ids);
+        updates.insert(canonical_id, record);
+    }
+    storage.upsert(updates).await?;
+    Ok(outcome)
+}
+
+/// Resolves merge redirects starting at `canonical_id`. Returns the id to
+/// link to and its record, or `None` when a new entity has to be created.
+async fn follow_redirects(
+    storage: &JsonKvStorage,
+    canonical_id: String,
+) -> Result<(String, Option<Value>)> {
+    let mut current = canonical_id.clone();
+    for _ in 0..MAX_REDIRECTS {
+        let Some(record) = storage.get_by_id(&current).await? else {
+            // a redirect to an entity that has since lost all its mentions
+            return Ok((canonical_id, None));
+        };
+        match record.get("merged_into").and_then(Value::as_str) {
+            Some(target) => current = target.to_string(),
+            None => return Ok((current, Some(record))),
+        }
+    }
+    Ok((canonical_id, None))
+}
+
+/// Removes deleted mentions from their canonical entities. Canonical entities
+/// left without mentions are deleted and their ids returned.
+pub async fn unlink_mentions(
+    storage: &JsonKvStorage,
+    mentions: &[(String, Value)],
+) -> Result<Vec<String>> {
+    let mut by_canonical: HashMap<String, Vec<&str>> = HashMap::new();
+    for (mention_id, record) in mentions {
+        let Some(canonical_id) = record_canonical_id(record) else {
+            continue;
+        };
+        by_canonical
+            .entry(canonical_id)
+            .or_default()
+            .push(mention_id.as_str());
+    }
+    if by_canonical.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let ids: Vec<String> = by_canonical.keys().cloned().collect();
+    let existing = storage.get_by_ids(&ids).await?;
+    let mut updates = HashMap::new();
+    let mut emptied = Vec::new();
+    for (canonical_id, record) in ids.into_iter().zip(existing) {
+        let Some(mut record) = record else {
+            continue;
+        };
+        let mut mention_ids = string_set(&record, "mention_ids");
+        for mention_id in &by_canonical[&canonical_id] {
+            mention_ids.remove(*mention_id);
+        }
+        if mention_ids.is_empty() {
+            emptied.push(canonical_id);
+            continue;
+        }
+        record["mention_count"] = json!(mention_ids.len());
+        record["mention_ids"] = json!(mention_ids);
+        updates.insert(canonical_id, record);
+    }
+    storage.delete(&emptied).await?;
+    storage.upsert(updates).await?;
+    Ok(emptied)
+}
+
+/// Canonical id of a stored mention. Records written before the canonical
+/// layer existed don't carry one, so it is derived from name and type.
+pub fn record_canonical_id(record: &Value) -> Option<String> {
+    if let Some(id) = record.get("canonical_id").and_then(Value::as_str) {
+        return Some(id.to_string());
+    }
+    let name = record.get("entity_name").and_then(Value::as_str)?;
+    let entity_type = record
+        .get("entity_type")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    Some(canonical_entity_id(name, entity_type))
+}
+
+/// Merges per-document mentions into one node per canonical entity and
+/// points relations at those nodes, so paths can cross documents. Each node
+/// keeps the first mention's fields and counts how many mentions it merges.
+/// Relations that collapse onto a single entity are dropped.
+pub fn collapse_to_canonical(
+    entities: &HashMap<String, EntityNode>,
+    relationships: &HashMap<String, RelationEdge>,
+) -> (HashMap<String, EntityNode>, HashMap<String, RelationEdge>) {
+    let mut mention_to_canonical: HashMap<&str, String> = HashMap::new();
+    let mut nodes: HashMap<String, EntityNode> = HashMap::new();
+
+    // iterate in a stable order so the representative mention is deterministic
+    let mut mention_ids: Vec<&String> = entities.keys().collect();
+    mention_ids.sort();
+    for mention_id in mention_ids {
+        let entity = &entities[mention_id];
+        let canonical_id = entity
+            .canonical_id
+            .clone()
+            .unwrap_or_else(|| canonical_entity_id(&entity.entity_name, &entity.entity_type));
+        mention_to_canonical.insert(mention_id.as_str(), canonical_id.clone());
+        nodes
+            .entry(canonical_id.clone())
+            .and_modify(|node| node.mention_count += 1)
+            .or_insert_with(|| EntityNode {
+                canonical_id: Some(canonical_id),
+                mention_count: 1,
+                ..entity.clone()
+            });
+    }
+
+    let edges = relationships
+        .iter()
+        .filter_map(|(relation_id, relation)| {
+            let source = mention_to_canonical.get(relation.source_entity_id.as_str())?;
+            let target = mention_to_canonical.get(relation.target_entity_id.as_str())?;
+            if source == target {
+                return None;
+            }
+            Some((
+                relation_id.clone(),
+                RelationEdge {
+                    source_entity_id: source.clone(),
+                    target_entity_id: target.clone(),
+                    ..relation.clone()
+                },
+            ))
+        })
+        .collect();
+
+    (nodes, edges)
+}
+
+pub(crate) fn string_set(record: &Value, key: &str) -> BTreeSet<String> {
+    record
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mention(name: &str, doc_id: &str) -> EntityNode {
+        EntityNode {
+            entity_name: name.to_string(),
+            entity_type: "Drug".to_string(),
+            doc_id: doc_id.to_string(),
+            ..EntityNode::default()
+        }
+    }
+
+    #[test]
+    fn names_normalize_to_one_canonical_id() {
+        assert_eq!(normalize_entity_name("  \"Rapamycin.\" "), "rapamycin");
+        assert_eq!(normalize_entity_name("IL-6"), "il-6");
+        assert_eq!(
+            canonical_entity_id("Rapamycin", "Drug"),
+            canonical_entity_id("rapamycin ", "drug")
+        );
+        assert_ne!(
+            canonical_entity_id("Rapamycin", "Drug"),
+            canonical_entity_id("Rapamycin", "Gene")
+        );
+    }
+
+    #[test]
+    fn mentions_from_different_documents_share_a_node() {
+        let entities = HashMap::from([
+            ("m1".to_string(), mention("Rapamycin", "doc-1")),
+            ("m2".to_string(), mention("rapamycin", "doc-2")),
+            ("m3".to_string(), mention("Metformin", "doc-2")),
+        ]);
+        let relationships = HashMap::from([
+            (
+                "r1".to_string(),
+                RelationEdge {
+                    source_entity_id: "m2".to_string(),
+                    target_entity_id: "m3".to_string(),
+                    ..RelationEdge::default()
+                },
+            ),
+            (
+                "r2".to_string(),
+                RelationEdge {
+                    source_entity_id: "m1".to_string(),
+                    target_entity_id: "m2".to_string(),
+                    ..RelationEdge::default()
+                },
+            ),
+        ]);
+
+        let (nodes, edges) = collapse_to_canonical(&entities, &relationships);
+        let rapamycin = canonical_entity_id("Rapamycin", "Drug");
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[&rapamycin].mention_count, 2);
+        assert_eq!(nodes[&rapamycin].entity_name, "Rapamycin");
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges["r1"].source_entity_id, rapamycin);
+    }
+}
@@ -1,9 +1,30 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use anyhow::{Result, anyhow};
-use serde::Deserialize;
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use tracing::warn;
+use ts_rs::TS;
 
-use crate::pipeline::utils::{Tokenizer, chunking_by_token_size, compute_mdhash_id};
+use crate::{
+    ai::{
+        embeddings::{Embedder, cosine_similarity},
+        responses::ModelCall,
+    },
+    pipeline::utils::{
+        Tokenizer, chunking_by_sentences, chunking_by_token_size, compute_mdhash_id,
+        split_sentences,
+    },
+};
+
+/// Longest input the embedding models accept, in tokens. Longer sentences,
+/// such as tables or paragraphs without terminal punctuation, are embedded
+/// from their leading tokens.
+const MAX_EMBEDDING_INPUT_TOKENS: usize = 8000;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Chunk {
@@ -11,6 +32,57 @@ pub struct Chunk {
     pub content: String,
     pub order: usize,
     pub token_count: i64,
+    /// Heading breadcrumb of the section the chunk starts in, e.g.
+    /// "Results > Mouse models".
+    #[serde(default)]
+    pub heading: Option<String>,
+    /// Where the chunk text appears in the stored document. Chunks that
+    /// rewrite their input, such as CSV rows under a repeated header, have
+    /// no span.
+    #[serde(default)]
+    pub span: Option<Span>,
+}
+
+/// Byte range `start..end` in a document or chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Sets the span of every chunk found verbatim in `content`. Chunks are
+/// looked for in order, each starting after the previous one's start since
+/// neighbouring chunks may overlap.
+pub fn locate_chunks(content: &str, chunks: &mut [Chunk]) {
+    let mut cursor = 0;
+    for chunk in chunks.iter_mut() {
+        let found = content[cursor..]
+            .find(chunk.content.as_str())
+            .map(|offset| cursor + offset)
+            .or_else(|| content.find(chunk.content.as_str()));
+        chunk.span = found.map(|start| Span {
+            start,
+            end: start + chunk.content.len(),
+        });
+        if let Some(start) = found {
+            cursor = start + content[start..].chars().next().map_or(0, char::len_utf8);
+        }
+    }
+}
+
+/// How plain text is cut into chunks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkStrategy {
+    /// Fixed token windows, optionally split on `split_by_character` first.
+    #[default]
+    Token,
+    /// Whole sentences packed up to `max_tokens`.
+    Sentence,
+    /// Whole sentences, with a new chunk wherever the embedding similarity of
+    /// adjacent sentences drops below `semantic_threshold`.
+    Semantic,
 }
 
 #[derive(Debug, Clone)]
@@ -19,6 +91,8 @@ pub struct ChunkConfig {
     pub overlap_tokens: usize,
     pub split_by_character: Option<String>,
     pub split_by_character_only: bool,
+    pub strategy: ChunkStrategy,
+    pub semantic_threshold: f32,
 }
 
 impl Default for ChunkConfig {
@@ -28,27 +102,117 @@ impl Default for ChunkConfig {
             overlap_tokens: 50,
             split_by_character: None,
             split_by_character_only: false,
+            strategy: ChunkStrategy::Token,
+            semantic_threshold: 0.5,
         }
     }
 }
 
+#[async_trait]
 pub trait Chunker: Send + Sync {
-    fn chunk(&self, content: &str, config: &ChunkConfig) -> Result<Vec<Chunk>>;
+    async fn chunk(&self, content: &str, config: &ChunkConfig) -> Result<Vec<Chunk>>;
+
+    /// Like `chunk`, also returning the billed model calls made, such as
+    /// the embeddings of semantic chunking.
+    async fn chunk_with_usage(
+        &self,
+        content: &str,
+        config: &ChunkConfig,
+    ) -> Result<(Vec<Chunk>, Vec<ModelCall>)> {
+        Ok((self.chunk(content, config).await?, Vec::new()))
+    }
+}
+
+/// The chunkers for structured formats, keyed by file extension. Files of
+/// other types go to the default [`TokenizerChunker`].
+pub fn format_chunkers(tokenizer: Arc<dyn Tokenizer>) -> HashMap<String, Arc<dyn Chunker>> {
+    HashMap::from([
+        (
+            "csv".to_string(),
+            Arc::new(CsvChunker::new(tokenizer.clone())) as Arc<dyn Chunker>,
+        ),
+        (
+            "json".to_string(),
+            Arc::new(JsonChunker::new(tokenizer.clone())),
+        ),
+        ("md".to_string(), Arc::new(MarkdownChunker::new(tokenizer))),
+    ])
 }
 
+/// Default chunker for plain text. Supports every [`ChunkStrategy`]; the
+/// semantic strategy needs an embedder set through [`TokenizerChunker::with_embedder`].
 #[derive(Clone)]
 pub struct TokenizerChunker {
     tokenizer: Arc<dyn Tokenizer>,
+    embedder: Option<Arc<dyn Embedder>>,
 }
 
 impl TokenizerChunker {
     pub fn new(tokenizer: Arc<dyn Tokenizer>) -> Self {
-        Self { tokenizer }
+        Self {
+            tokenizer,
+            embedder: None,
+        }
+    }
+
+    pub fn with_embedder(mut self, embedder: Arc<dyn Embedder>) -> Self {
+        self.embedder = Some(embedder);
+        self
+    }
+
+    /// Sentence indices where adjacent-sentence similarity drops below the
+    /// configured threshold.
+    async fn semantic_breakpoints(
+        &self,
+        embedder: &dyn Embedder,
+        sentences: &[&str],
+        threshold: f32,
+    ) -> Result<(HashSet<usize>, Vec<ModelCall>)> {
+        if sentences.len() < 2 {
+            return Ok((HashSet::new(), Vec::new()));
+        }
+
+        let inputs = sentences
+            .iter()
+            .map(|sentence| {
+                let tokens = self.tokenizer.encode(sentence);
+                if tokens.len() > MAX_EMBEDDING_INPUT_TOKENS {
+                    self.tokenizer.decode(&tokens[..MAX_EMBEDDING_INPUT_TOKENS])
+                } else {
+                    Ok(sentence.to_string())
+                }
+            })
+            .collect::<Result<Vec<String>>>()?;
+        let (embeddings, calls) = embedder.embed_with_usage(&inputs).await?;
+        if embeddings.len() != sentences.len() {
+            return Err(anyhow!(
+                "embedder returned {} vectors for {} sentences",
+                embeddings.len(),
+                sentences.len()
+            ));
+        }
+
+        let breakpoints = embeddings
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| cosine_similarity(&pair[0], &pair[1]) < threshold)
+            .map(|(idx, _)| idx + 1)
+            .collect();
+        Ok((breakpoints, calls))
     }
 }
 
+#[async_trait]
 impl Chunker for TokenizerChunker {
-    fn chunk(&self, content: &str, config: &ChunkConfig) -> Result<Vec<Chunk>> {
+    async fn chunk(&self, content: &str, config: &ChunkConfig) -> Result<Vec<Chunk>> {
+        Ok(self.chunk_with_usage(content, config).await?.0)
+    }
+
+    async fn chunk_with_usage(
+        &self,
+        content: &str,
+        config: &ChunkConfig,
+    ) -> Result<(Vec<Chunk>, Vec<ModelCall>)> {
         if config.overlap_tokens >= config.max_tokens {
             return Err(anyhow!(
                 "overlap_token_size ({}) must be smaller than max_token_size ({})",
@@ -57,14 +221,54 @@ impl Chunker for TokenizerChunker {
             ));
         }
 
-        let token_chunks = chunking_by_token_size(
-            self.tokenizer.as_ref(),
-            content,
-            config.split_by_character.as_deref(),
-            config.split_by_character_only,
-            config.overlap_tokens,
-            config.max_tokens,
-        )?;
+        let mut calls = Vec::new();
+        let token_chunks = match config.strategy {
+            ChunkStrategy::Token => chunking_by_token_size(
+                self.tokenizer.as_ref(),
+                content,
+                config.split_by_character.as_deref(),
+                config.split_by_character_only,
+                config.overlap_tokens,
+                config.max_tokens,
+            )?,
+            ChunkStrategy::Sentence => chunking_by_sentences(
+                self.tokenizer.as_ref(),
+                content,
+                &split_sentences(content),
+                &HashSet::new(),
+                config.overlap_tokens,
+                config.max_tokens,
+            )?,
+            ChunkStrategy::Semantic => {
+                let embedder = self
+                    .embedder
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("semantic chunking requires an embedder"))?;
+                let sentences = split_sentences(content);
+                // without breakpoints this is the sentence strategy
+                let breakpoints = match self
+                    .semantic_breakpoints(embedder, &sentences, config.semantic_threshold)
+                    .await
+                {
+                    Ok((breakpoints, embedding_calls)) => {
+                        calls = embedding_calls;
+                        breakpoints
+                    }
+                    Err(err) => {
+                        warn!(error = %err, "semantic chunking failed, chunking by sentence");
+                        HashSet::new()
+                    }
+                };
+                chunking_by_sentences(
+                    self.tokenizer.as_ref(),
+                    content,
+                    &sentences,
+                    &breakpoints,
+                    config.overlap_tokens,
+                    config.max_tokens,
+                )?
+            }
+        };
 
         let chunks = token_chunks
             .into_iter()
@@ -73,9 +277,605 @@ impl Chunker for TokenizerChunker {
                 content: chunk.content,
                 order: chunk.chunk_order_index,
                 token_count: chunk.tokens as i64,
+                heading: None,
+                span: None,
+            })
+            .collect();
+
+        Ok((chunks, calls))
+    }
+}
+
+/// Chunks CSV input by groups of whole rows, repeating the header line at the
+/// top of every chunk so each chunk can be read on its own.
+#[derive(Clone)]
+pub struct CsvChunker {
+    tokenizer: Arc<dyn Tokenizer>,
+}
+
+impl CsvChunker {
+    pub fn new(tokenizer: Arc<dyn Tokenizer>) -> Self {
+        Self { tokenizer }
+    }
+}
+
+#[async_trait]
+impl Chunker for CsvChunker {
+    async fn chunk(&self, content: &str, config: &ChunkConfig) -> Result<Vec<Chunk>> {
+        let rows = split_csv_records(content)?;
+        let Some((header, rows)) = rows.split_first() else {
+            return Ok(Vec::new());
+        };
+
+        let header_tokens = self.tokenizer.encode(header).len();
+        let row_budget = config.max_tokens.saturating_sub(header_tokens).max(1);
+
+        let mut groups: Vec<(Vec<&str>, usize)> = Vec::new();
+        let mut current: Vec<&str> = Vec::new();
+        let mut current_tokens = 0usize;
+        for row in rows {
+            // +1 accounts for the newline joining rows
+            let row_tokens = self.tokenizer.encode(row).len() + 1;
+            if !current.is_empty() && current_tokens + row_tokens > row_budget {
+                groups.push((std::mem::take(&mut current), current_tokens));
+                current_tokens = 0;
+            }
+            current.push(row);
+            current_tokens += row_tokens;
+        }
+        if !current.is_empty() {
+            groups.push((current, current_tokens));
+        }
+
+        let chunks = groups
+            .into_iter()
+            .enumerate()
+            .map(|(order, (group, tokens))| {
+                let mut lines = Vec::with_capacity(group.len() + 1);
+                lines.push(header.as_str());
+                lines.extend(group);
+                let content = lines.join("\n");
+                Chunk {
+                    id: compute_mdhash_id(&content, "chunk-"),
+                    content,
+                    order,
+                    token_count: (header_tokens + tokens) as i64,
+                    heading: None,
+                    span: None,
+                }
             })
             .collect();
 
         Ok(chunks)
     }
 }
+
+/// Splits CSV text into raw record strings (header first) without breaking
+/// quoted fields that span several lines.
+pub fn split_csv_records(content: &str) -> Result<Vec<String>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.as_bytes());
+
+    let mut starts = Vec::new();
+    let mut record = csv::StringRecord::new();
+    loop {
+        let start = reader.position().byte() as usize;
+        if !reader
+            .read_record(&mut record)
+            .context("failed to parse CSV record")?
+        {
+            break;
+        }
+        starts.push(start);
+    }
+
+    let records = starts
+        .iter()
+        .enumerate()
+        .map(|(idx, &start)| {
+            let end = starts.get(idx + 1).copied().unwrap_or(content.len());
+            content[start..end]
+                .trim_end_matches(['\r', '\n'])
+                .to_string()
+        })
+        .filter(|record| !record.trim().is_empty())
+        .collect();
+
+    Ok(records)
+}
+
+/// Chunks a top-level JSON array one element per chunk, each cut verbatim
+/// from the input so its span can be located. Anything that is not an array,
+/// or an element larger than `max_tokens`, falls back to token windows.
+#[derive(Clone)]
+pub struct JsonChunker {
+    tokenizer: Arc<dyn Tokenizer>,
+    fallback: TokenizerChunker,
+}
+
+impl JsonChunker {
+    pub fn new(tokenizer: Arc<dyn Tokenizer>) -> Self {
+        Self {
+            fallback: TokenizerChunker::new(tokenizer.clone()),
+            tokenizer,
+        }
+    }
+}
+
+#[async_trait]
+impl Chunker for JsonChunker {
+    async fn chunk(&self, content: &str, config: &ChunkConfig) -> Result<Vec<Chunk>> {
+        // sentence boundaries mean nothing inside JSON, always use token windows
+        let config = &ChunkConfig {
+            strategy: ChunkStrategy::Token,
+            ..config.clone()
+        };
+        let items = match serde_json::from_str::<Vec<&RawValue>>(content) {
+            Ok(items) => items,
+            Err(_) => return self.fallback.chunk(content, config).await,
+        };
+
+        let mut chunks = Vec::with_capacity(items.len());
+        for item in items {
+            let text = item.get();
+            let tokens = self.tokenizer.encode(text).len();
+            if tokens <= config.max_tokens {
+                chunks.push(Chunk {
+                    id: compute_mdhash_id(text, "chunk-"),
+                    content: text.to_string(),
+                    order: chunks.len(),
+                    token_count: tokens as i64,
+                    heading: None,
+                    span: None,
+                });
+                continue;
+            }
+
+            for piece in self.fallback.chunk(text, config).await? {
+                chunks.push(Chunk {
+                    order: chunks.len(),
+                    ..piece
+                });
+            }
+        }
+
+        Ok(chunks)
+    }
+}
+
+/// Chunks Markdown on ATX heading boundaries. Whole sections are packed into
+/// a chunk until `max_tokens` is reached; a section that is larger on its own
+/// is split into token windows overlapping by `overlap_tokens`. Chunks of
+/// whole sections do not overlap, since headings already delimit them. Every
+/// chunk carries the heading breadcrumb of the section it starts in.
+#[derive(Clone)]
+pub struct MarkdownChunker {
+    tokenizer: Arc<dyn Tokenizer>,
+}
+
+impl MarkdownChunker {
+    pub fn new(tokenizer: Arc<dyn Tokenizer>) -> Self {
+        Self { tokenizer }
+    }
+}
+
+struct MarkdownSection {
+    breadcrumb: Option<String>,
+    text: String,
+}
+
+#[async_trait]
+impl Chunker for MarkdownChunker {
+    async fn chunk(&self, content: &str, config: &ChunkConfig) -> Result<Vec<Chunk>> {
+        if config.overlap_tokens >= config.max_tokens {
+            return Err(anyhow!(
+                "overlap_token_size ({}) must be smaller than max_token_size ({})",
+                config.overlap_tokens,
+                config.max_tokens
+            ));
+        }
+
+        let mut pieces: Vec<(Option<String>, String, usize)> = Vec::new();
+        let mut current: Option<(Option<String>, Vec<String>, usize)> = None;
+
+        for section in split_markdown_sections(content) {
+            let tokens = self.tokenizer.encode(&section.text).len();
+
+            if tokens > config.max_tokens {
+                if let Some((breadcrumb, texts, tokens)) = current.take() {
+                    pieces.push((breadcrumb, texts.join("\n\n"), tokens));
+                }
+                for window in chunking_by_token_size(
+                    self.tokenizer.as_ref(),
+                    &section.text,
+                    None,
+                    false,
+                    config.overlap_tokens,
+                    config.max_tokens,
+                )? {
+                    pieces.push((section.breadcrumb.clone(), window.content, window.tokens));
+                }
+                continue;
+            }
+
+            match current.as_mut() {
+                Some((_, texts, packed)) if *packed + tokens <= config.max_tokens => {
+                    texts.push(section.text);
+                    *packed += tokens;
+                }
+                _ => {
+                    if let Some((breadcrumb, texts, tokens)) = current.take() {
+                        pieces.push((breadcrumb, texts.join("\n\n"), tokens));
+                    }
+                    current = Some((section.breadcrumb, vec![section.text], tokens));
+                }
+            }
+        }
+        if let Some((breadcrumb, texts, tokens)) = current.take() {
+            pieces.push((breadcrumb, texts.join("\n\n"), tokens));
+        }
+
+        let chunks = pieces
+            .into_iter()
+            .filter(|(_, text, _)| !text.trim().is_empty())
+            .enumerate()
+            .map(|(order, (heading, content, tokens))| Chunk {
+                id: compute_mdhash_id(&content, "chunk-"),
+                content,
+                order,
+                token_count: tokens as i64,
+                heading,
+                span: None,
+            })
+            .collect();
+
+        Ok(chunks)
+    }
+}
+
+/// Splits Markdown into sections that each start at an ATX heading (`#` to
+/// `######`), ignoring `#` lines inside fenced code blocks. Text before the
+/// first heading becomes a section without a breadcrumb.
+fn split_markdown_sections(content: &str) -> Vec<MarkdownSection> {
+    let mut sections = Vec::new();
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut breadcrumb: Option<String> = None;
+    let mut lines: Vec<&str> = Vec::new();
+    let mut fence: Option<&str> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            lines.push(line);
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            lines.push(line);
+            continue;
+        }
+
+        let Some((level, title)) = parse_atx_heading(line) else {
+            lines.push(line);
+            continue;
+        };
+
+        let text = lines.join("\n");
+        if !text.trim().is_empty() {
+            sections.push(MarkdownSection {
+                breadcrumb: breadcrumb.clone(),
+                text: text.trim().to_string(),
+            });
+        }
+        lines.clear();
+        lines.push(line);
+
+        while stack.last().is_some_and(|(parent, _)| *parent >= level) {
+            stack.pop();
+        }
+        stack.push((level, title));
+        breadcrumb = Some(
+            stack
+                .iter()
+                .map(|(_, title)| title.as_str())
+                .collect::<Vec<_>>()
+                .join(" > "),
+        );
+    }
+
+    let text = lines.join("\n");
+    if !text.trim().is_empty() {
+        sections.push(MarkdownSection {
+            breadcrumb,
+            text: text.trim().to_string(),
+        });
+    }
+    sections
+}
+
+fn parse_atx_heading(line: &str) -> Option<(usize, String)> {
+    // up to three spaces of indentation are allowed before the hashes
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let level = rest.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let after = &rest[level..];
+    if !after.is_empty() && !after.starts_with([' ', '\t']) {
+        return None;
+    }
+    let title = after.trim().trim_end_matches('#').trim();
+    if title.is_empty() {
+        return None;
+    }
+    Some((level, title.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::utils::TiktokenTokenizer;
+
+    #[test]
+    fn overlapping_chunks_are_located_in_order() {
+        let content = "alpha beta alpha beta gamma";
+        let chunk = |text: &str| Chunk {
+            id: text.to_string(),
+            content: text.to_string(),
+            order: 0,
+            token_count: 0,
+            heading: None,
+            span: None,
+        };
+        let mut chunks = vec![
+            chunk("alpha beta"),
+            chunk("alpha beta gamma"),
+            chunk("delta"),
+        ];
+
+        locate_chunks(content, &mut chunks);
+
+        assert_eq!(chunks[0].span, Some(Span { start: 0, end: 10 }));
+        assert_eq!(chunks[1].span, Some(Span { start: 11, end: 27 }));
+        assert_eq!(chunks[2].span, None);
+    }
+
+    fn tokenizer() -> Arc<dyn Tokenizer> {
+        Arc::new(TiktokenTokenizer::new().unwrap())
+    }
+
+    #[tokio::test]
+    async fn csv_chunks_repeat_header_and_keep_rows_whole() {
+        let mut content = String::from("compound,target,effect\n");
+        for idx in 0..40 {
+            content.push_str(&format!(
+                "Compound {idx},\"Target {idx}\nsecond line\",inhibits\n"
+            ));
+        }
+        let config = ChunkConfig {
+            max_tokens: 60,
+            ..ChunkConfig::default()
+        };
+
+        let chunks = CsvChunker::new(tokenizer())
+            .chunk(&content, &config)
+            .await
+            .unwrap();
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.content.starts_with("compound,target,effect\n"));
+            let rows = split_csv_records(&chunk.content).unwrap();
+            assert!(rows[1..].iter().all(|row| row.ends_with(",inhibits")));
+        }
+        let total_rows: usize = chunks
+            .iter()
+            .map(|chunk| split_csv_records(&chunk.content).unwrap().len() - 1)
+            .sum();
+        assert_eq!(total_rows, 40);
+    }
+
+    #[tokio::test]
+    async fn csv_without_data_rows_has_no_chunks() {
+        let chunks = CsvChunker::new(tokenizer())
+            .chunk("compound,target,effect\n", &ChunkConfig::default())
+            .await
+            .unwrap();
+
+        assert!(chunks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn json_array_is_chunked_per_object() {
+        let content = r#"[{"name": "Rapamycin", "dose": 1}, {"name": "Metformin"}, 3]"#;
+        let mut chunks = JsonChunker::new(tokenizer())
+            .chunk(content, &ChunkConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        // elements are cut from the input as written, keeping key order
+        assert_eq!(chunks[0].content, r#"{"name": "Rapamycin", "dose": 1}"#);
+        assert_eq!(chunks[1].content, r#"{"name": "Metformin"}"#);
+        assert_eq!(chunks[2].order, 2);
+
+        locate_chunks(content, &mut chunks);
+        assert_eq!(chunks[0].span, Some(Span { start: 1, end: 33 }));
+        assert!(chunks.iter().all(|chunk| chunk.span.is_some()));
+    }
+
+    #[tokio::test]
+    async fn json_object_falls_back_to_token_windows() {
+        let content = r#"{"name": "Rapamycin"}"#;
+        let chunks = JsonChunker::new(tokenizer())
+            .chunk(content, &ChunkConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].content, content);
+    }
+
+    #[tokio::test]
+    async fn markdown_chunks_carry_heading_breadcrumbs() {
+        let content = "Intro text.\n\n# Results\n\nSummary.\n\n## Mouse models\n\n```\n# not a heading\n```\nMice lived longer.\n\n## Human trials\n\nNo effect.\n\n# Discussion\n\nDone.";
+        let config = ChunkConfig {
+            max_tokens: 12,
+            overlap_tokens: 2,
+            ..ChunkConfig::default()
+        };
+
+        let chunks = MarkdownChunker::new(tokenizer())
+            .chunk(content, &config)
+            .await
+            .unwrap();
+        let headings: Vec<Option<&str>> = chunks
+            .iter()
+            .map(|chunk| chunk.heading.as_deref())
+            .collect();
+
+        assert_eq!(headings.first(), Some(&None));
+        assert!(headings.contains(&Some("Results > Mouse models")));
+        assert!(headings.contains(&Some("Results > Human trials")));
+        assert!(
+            chunks
+                .iter()
+                .any(|chunk| chunk.content.contains("# not a heading"))
+        );
+    }
+
+    #[tokio::test]
+    async fn markdown_packs_small_sections_together() {
+        let content = "# A\n\none\n\n# B\n\ntwo";
+        let chunks = MarkdownChunker::new(tokenizer())
+            .chunk(content, &ChunkConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].heading.as_deref(), Some("A"));
+        assert!(chunks[0].content.contains("# B"));
+    }
+
+    #[tokio::test]
+    async fn markdown_windows_of_oversized_sections_overlap() {
+        let words: Vec<String> = (0..60).map(|idx| format!("w{idx}")).collect();
+        let content = format!("# Short\n\nBrief.\n\n# Long\n\n{}", words.join(" "));
+        let config = ChunkConfig {
+            max_tokens: 40,
+            overlap_tokens: 10,
+            ..ChunkConfig::default()
+        };
+
+        let chunks = MarkdownChunker::new(tokenizer())
+            .chunk(&content, &config)
+            .await
+            .unwrap();
+
+        let windows: Vec<&Chunk> = chunks
+            .iter()
+            .filter(|chunk| chunk.heading.as_deref() == Some("Long"))
+            .collect();
+        assert!(windows.len() > 1);
+        for pair in windows.windows(2) {
+            let earlier: HashSet<&str> = pair[0].content.split_whitespace().collect();
+            let shared = pair[1]
+                .content
+                .split_whitespace()
+                .filter(|word| earlier.contains(word))
+                .count();
+            assert!(
+                shared >= 2,
+                "{:?} does not overlap {:?}",
+                pair[1].content,
+                pair[0].content
+            );
+        }
+        assert!(!chunks[0].content.contains("Long"));
+    }
+
+    struct KeywordEmbedder;
+
+    #[async_trait]
+    impl Embedder for KeywordEmbedder {
+        async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+            Ok(inputs
+                .iter()
+                .map(|input| {
+                    if input.contains("mouse") {
+                        vec![1.0, 0.0]
+                    } else {
+                        vec![0.0, 1.0]
+                    }
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn semantic_strategy_breaks_on_topic_shift() {
+        let content = "The mouse was fed. The mouse grew. Yeast cells divide. Yeast cells age.";
+        let config = ChunkConfig {
+            strategy: ChunkStrategy::Semantic,
+            overlap_tokens: 0,
+            ..ChunkConfig::default()
+        };
+
+        let chunker = TokenizerChunker::new(tokenizer()).with_embedder(Arc::new(KeywordEmbedder));
+        let chunks = chunker.chunk(content, &config).await.unwrap();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].content, "The mouse was fed. The mouse grew.");
+        assert_eq!(chunks[1].content, "Yeast cells divide. Yeast cells age.");
+
+        let without_embedder = TokenizerChunker::new(tokenizer());
+        assert!(without_embedder.chunk(content, &config).await.is_err());
+    }
+
+    /// Rejects inputs over the model limit, like the embeddings API.
+    struct LimitedEmbedder {
+        max_chars: usize,
+    }
+
+    #[async_trait]
+    impl Embedder for LimitedEmbedder {
+        async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+            if inputs.iter().any(|input| input.len() > self.max_chars) {
+                return Err(anyhow!("input too long"));
+            }
+            KeywordEmbedder.embed(inputs).await
+        }
+    }
+
+    #[tokio::test]
+    async fn semantic_strategy_truncates_long_sentences_and_falls_back_on_errors() {
+        // a table without terminal punctuation is one sentence
+        let table = "mouse lifespan ".repeat(20_000);
+        let content = format!("{table}\n\nYeast cells divide. Yeast cells age.");
+        let config = ChunkConfig {
+            strategy: ChunkStrategy::Semantic,
+            overlap_tokens: 0,
+            max_tokens: 100_000,
+            ..ChunkConfig::default()
+        };
+
+        let truncating = TokenizerChunker::new(tokenizer())
+            .with_embedder(Arc::new(LimitedEmbedder { max_chars: 100_000 }));
+        assert_eq!(truncating.chunk(&content, &config).await.unwrap().len(), 2);
+
+        let failing = TokenizerChunker::new(tokenizer())
+            .with_embedder(Arc::new(LimitedEmbedder { max_chars: 10 }));
+        let chunks = failing.chunk(&content, &config).await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].content.ends_with("Yeast cells age."));
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+
+use crate::ai::schemas::{EntitiesRelationships, ExtractedEntity, ExtractedRelationship, Modality};
+
+/// The first mapping that applies to a CSV file, judged by its name and
+/// header row.
+pub fn find_mapping<'a>(
+    mappings: &'a [CsvMapping],
+    file_path: &str,
+    content: &str,
+) -> Option<&'a CsvMapping> {
+    if mappings.is_empty() {
+        return None;
+    }
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let headers: Vec<String> = reader
+        .records()
+        .next()?
+        .ok()?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+    mappings
+        .iter()
+        .find(|mapping| mapping.applies_to(file_path, &headers))
+}
+
+/// Maps the columns of a curated CSV table straight onto entities and
+/// relations, so matching files skip the LLM extraction step entirely.
+///
+/// ```yaml
+/// ingestion:
+///   csv_mappings:
+///     - name: drug_targets
+///       entities:
+///         - column: compound
+///           entity_type: Compound
+///         - column: target
+///           entity_type: Protein
+///       relations:
+///         - source_column: compound
+///           target_column: target
+///           description_column: effect
+///           keywords: [drug target]
+///           predicate: inhibits
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CsvMapping {
+    pub name: String,
+    /// Only apply to files with this exact name. When unset the mapping applies
+    /// to every CSV whose header contains all referenced columns.
+    #[serde(default)]
+    pub file_name: Option<String>,
+    pub entities: Vec<CsvEntityMapping>,
+    #[serde(default)]
+    pub relations: Vec<CsvRelationMapping>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CsvEntityMapping {
+    pub column: String,
+    /// Must be a type of the configured ontology.
+    pub entity_type: String,
+    #[serde(default)]
+    pub description_column: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CsvRelationMapping {
+    pub source_column: String,
+    pub target_column: String,
+    #[serde(default)]
+    pub description_column: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub keywords_column: Option<String>,
+    /// Must be a predicate of the configured ontology that allows the types
+    /// of both columns.
+    #[serde(default)]
+    pub predicate: Option<String>,
+}
+
+impl CsvMapping {
+    pub fn validate(&self) -> Result<()> {
+        if self.entities.is_empty() {
+            return Err(anyhow!(
+                "csv mapping '{}' must declare at least one entity column",
+                self.name
+            ));
+        }
+
+        let entity_columns: HashSet<&str> =
+            self.entities.iter().map(|e| e.column.as_str()).collect();
+        for relation in &self.relations {
+            for column in [&relation.source_column, &relation.target_column] {
+                if !entity_columns.contains(column.as_str()) {
+                    return Err(anyhow!(
+                        "csv mapping '{}' relation column '{}' is not mapped to an entity",
+                        self.name,
+                        column
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true when the mapping should be used for `file_name`, whose
+    /// first CSV record is `headers`.
+    pub fn applies_to(&self, file_name: &str, headers: &[String]) -> bool {
+        if let Some(expected) = self.file_name.as_deref()
+            && expected != file_name
+        {
+            return false;
+        }
+        self.columns()
+            .all(|column| headers.iter().any(|header| header == column))
+    }
+
+    fn columns(&self) -> impl Iterator<Item = &str> {
+        let entity_columns = self.entities.iter().flat_map(|entity| {
+            std::iter::once(entity.column.as_str()).chain(entity.description_column.as_deref())
+        });
+        let relation_columns = self.relations.iter().flat_map(|relation| {
+            [
+                Some(relation.source_column.as_str()),
+                Some(relation.target_column.as_str()),
+                relation.description_column.as_deref(),
+                relation.keywords_column.as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+        });
+        entity_columns.chain(relation_columns)
+    }
+
+    /// Turns a CSV chunk (header line plus rows) into entities and relations.
+    pub fn extract(&self, chunk_content: &str) -> Result<EntitiesRelationships> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(chunk_content.as_bytes());
+        let headers: Vec<String> = reader
+            .headers()
+            .context("failed to read CSV header")?
+            .iter()
+            .map(|h| h.trim().to_string())
+            .collect();
+
+        let mut result = EntitiesRelationships::default();
+        let mut seen_entities: HashSet<(String, String)> = HashSet::new();
+
+        for record in reader.records() {
+            let record = record.context("failed to parse CSV row")?;
+            let row: HashMap<&str, &str> = headers
+                .iter()
+                .map(String::as_str)
+                .zip(record.iter().map(str::trim))
+                .collect();
+
+            for entity in &self.entities {
+                let Some(name) = row.get(entity.column.as_str()).filter(|v| !v.is_empty()) else {
+                    continue;
+                };
+                if !seen_entities.insert((name.to_string(), entity.entity_type.clone())) {
+                    continue;
+                }
+                let description = entity
+                    .description_column
+                    .as_deref()
+                    .and_then(|column| row.get(column))
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| describe_row(&self.name, &headers, &record));
+                result.entities.push(ExtractedEntity {
+                    entity_name: name.to_string(),
+                    entity_type: entity.entity_type.clone(),
+                    entity_description: description,
+                    evidence: name.to_string(),
+                });
+            }
+
+            for relation in &self.relations {
+                let source = row.get(relation.source_column.as_str()).copied();
+                let target = row.get(relation.target_column.as_str()).copied();
+                let (Some(source), Some(target)) = (source, target) else {
+                    continue;
+                };
+                if source.is_empty() || target.is_empty() {
+                    continue;
+                }
+
+                let mut keywords = relation.keywords.clone();
+                if let Some(value) = relation
+                    .keywords_column
+                    .as_deref()
+                    .and_then(|column| row.get(column))
+                {
+                    keywords.extend(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|k| !k.is_empty())
+                            .map(str::to_string),
+                    );
+                }
+
+                let description = relation
+                    .description_column
+                    .as_deref()
+                    .and_then(|column| row.get(column))
+                    .filter(|v| !v.is_empty())
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| describe_row(&self.name, &headers, &record));
+
+                result.relationships.push(ExtractedRelationship {
+                    source_entity: source.to_string(),
+                    target_entity: target.to_string(),
+                    relationship_keywords: keywords,
+                    relationship_description: description,
+                    evidence: record.iter().collect::<Vec<_>>().join(","),
+                    // curated tables state their relations outright
+                    confidence: Some(1.0),
+                    modality: Modality::Asserted,
+                    hedge_cues: Vec::new(),
+                    predicate: relation.predicate.clone(),
+                });
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+fn describe_row(mapping: &str, headers: &[String], record: &csv::StringRecord) -> String {
+    let fields = headers
+        .iter()
+        .zip(record.iter())
+        .filter(|(_, value)| !value.trim().is_empty())
+        .map(|(header, value)| format!("{header}: {}", value.trim()))
+        .collect::<Vec<_>>()
+        .join("; ");
+    format!("Imported from table '{mapping}' ({fields})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping() -> CsvMapping {
+        serde_yaml::from_str(
+            r#"
+name: drug_targets
+entities:
+  - column: compound
+    entity_type: Compound
+  - column: target
+    entity_type: Protein
+relations:
+  - source_column: compound
+    target_column: target
+    description_column: effect
+    keywords: [drug target]
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn applies_only_when_all_columns_present() {
+        let mapping = mapping();
+        mapping.validate().unwrap();
+        let headers = |cols: &[&str]| cols.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+
+        assert!(mapping.applies_to("a.csv", &headers(&["compound", "target", "effect", "x"])));
+        assert!(!mapping.applies_to("a.csv", &headers(&["compound", "target"])));
+    }
+
+    #[test]
+    fn rows_become_entities_and_relations() {
+        let chunk = "compound,target,effect\nRapamycin,MTOR,inhibits mTORC1\nEverolimus,MTOR,";
+        let er = mapping().extract(chunk).unwrap();
+
+        assert_eq!(er.entities.len(), 3);
+        assert_eq!(er.relationships.len(), 2);
+        assert_eq!(
+            er.relationships[0].relationship_description,
+            "inhibits mTORC1"
+        );
+        assert!(
+            er.relationships[1]
+                .relationship_description
+                .contains("compound: Everolimus")
+        );
+    }
+
+    #[test]
+    fn relation_columns_must_be_entities() {
+        let mut mapping = mapping();
+        mapping.relations[0].target_column = "effect".into();
+        assert!(mapping.validate().is_err());
+    }
+}
@@ -0,0 +1,374 @@
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use ts_rs::TS;
+
+use crate::ai::{
+    responses::{ModelCall, ResponsesClient},
+    schemas::{DescriptionSummary, description_summary_schema},
+};
+
+use super::utils::{Tokenizer, compute_mdhash_id};
+
+/// Hash of the descriptions the current summary was written from, so a
+/// summary is only redone when the descriptions change.
+const SUMMARY_OF_KEY: &str = "description_summary_of";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DescriptionConfig {
+    /// Size above which the descriptions of an entity or relation are
+    /// summarized into one; smaller sets are joined as they are.
+    pub token_budget: usize,
+}
+
+impl Default for DescriptionConfig {
+    fn default() -> Self {
+        Self { token_budget: 400 }
+    }
+}
+
+/// A description as extracted from one chunk. Entity and relation records
+/// keep all of them under `descriptions`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SourcedDescription {
+    pub chunk_id: String,
+    pub description: String,
+}
+
+/// Descriptions of a record. Records written before descriptions were kept
+/// per chunk only carry the description in `field`.
+pub fn sourced_descriptions(record: &Value, field: &str) -> Vec<SourcedDescription> {
+    if let Some(descriptions) = record.get("descriptions") {
+        return serde_json::from_value(descriptions.clone()).unwrap_or_default();
+    }
+    let description = record
+        .get(field)
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if description.trim().is_empty() {
+        return Vec::new();
+    }
+    vec![SourcedDescription {
+        chunk_id: record
+            .get("chunk_id")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        description: description.to_string(),
+    }]
+}
+
+/// Adds the description extracted from `chunk_id`, unless it is already there.
+pub fn push_description(record: &mut Value, field: &str, chunk_id: &str, description: &str) {
+    if description.trim().is_empty() {
+        return;
+    }
+    let mut descriptions = sourced_descriptions(record, field);
+    let entry = SourcedDescription {
+        chunk_id: chunk_id.to_string(),
+        description: description.trim().to_string(),
+    };
+    if !descriptions.contains(&entry) {
+        descriptions.push(entry);
+    }
+    record["descriptions"] = json!(descriptions);
+}
+
+/// Adds the descriptions of the stored version of a record to the one about
+/// to replace it. An existing summary is carried over and reused if the
+/// descriptions turn out unchanged.
+pub fn merge_descriptions(existing: &Value, record: &mut Value, field: &str) {
+    let mut descriptions = sourced_descriptions(existing, field);
+    for entry in sourced_descriptions(record, field) {
+        if !descriptions.contains(&entry) {
+            descriptions.push(entry);
+        }
+    }
+    record["descriptions"] = json!(descriptions);
+    if let Some(summary_of) = existing.get(SUMMARY_OF_KEY) {
+        record[SUMMARY_OF_KEY] = summary_of.clone();
+        record[field] = existing[field].clone();
+    }
+}
+
+/// Drops the descriptions extracted from `removed` chunks.
+pub fn retire_descriptions(record: &mut Value, removed: &HashSet<String>) {
+    let Some(descriptions) = record.get("descriptions") else {
+        return;
+    };
+    let remaining: Vec<SourcedDescription> =
+        serde_json::from_value::<Vec<SourcedDescription>>(descriptions.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| !removed.contains(&entry.chunk_id))
+            .collect();
+    record["descriptions"] = json!(remaining);
+}
+
+/// Writes the description `consolidated` settled on into `current`, the
+/// record as stored now, leaving its other fields as they are. Returns false
+/// when its descriptions changed meanwhile, so the summary no longer fits.
+pub fn apply_consolidated(consolidated: &Value, current: &mut Value, field: &str) -> bool {
+    if sourced_descriptions(consolidated, field) != sourced_descriptions(current, field) {
+        return false;
+    }
+    current[field] = consolidated[field].clone();
+    match consolidated.get(SUMMARY_OF_KEY) {
+        Some(summary_of) => current[SUMMARY_OF_KEY] = summary_of.clone(),
+        None => {
+            if let Some(map) = current.as_object_mut() {
+                map.remove(SUMMARY_OF_KEY);
+            }
+        }
+    }
+    true
+}
+
+#[async_trait]
+pub trait DescriptionSummarizer: Send + Sync {
+    /// Merges the descriptions of `subject` into one of at most `max_tokens`.
+    async fn summarize(
+        &self,
+        subject: &str,
+        descriptions: &[String],
+        max_tokens: usize,
+    ) -> Result<String>;
+
+    /// Like `summarize`, also returning the billed model call, if any.
+    async fn summarize_with_usage(
+        &self,
+        subject: &str,
+        descriptions: &[String],
+        max_tokens: usize,
+    ) -> Result<(String, Option<ModelCall>)> {
+        Ok((
+            self.summarize(subject, descriptions, max_tokens).await?,
+            None,
+        ))
+    }
+}
+
+#[derive(Clone)]
+pub struct LlmDescriptionSummarizer {
+    ai_client: Arc<ResponsesClient>,
+    model: String,
+}
+
+impl LlmDescriptionSummarizer {
+    pub fn new(ai_client: Arc<ResponsesClient>, model: impl Into<String>) -> Self {
+        Self {
+            ai_client,
+            model: model.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl DescriptionSummarizer for LlmDescriptionSummarizer {
+    async fn summarize(
+        &self,
+        subject: &str,
+        descriptions: &[String],
+        max_tokens: usize,
+    ) -> Result<String> {
+        Ok(self
+            .summarize_with_usage(subject, descriptions, max_tokens)
+            .await?
+            .0)
+    }
+
+    async fn summarize_with_usage(
+        &self,
+        subject: &str,
+        descriptions: &[String],
+        max_tokens: usize,
+    ) -> Result<(String, Option<ModelCall>)> {
+        let system = format!(
+            "You merge descriptions of the same entity or relationship, each extracted from a \
+             different passage, into one coherent description of at most {max_tokens} tokens. \
+             Keep every distinct fact, drop repetition, state contradictions explicitly and add \
+             nothing that is not in the descriptions."
+        );
+        let user = format!(
+            "Subject: {subject}\n\nDescriptions:\n{}",
+            descriptions
+                .iter()
+                .map(|description| format!("- {description}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        let (summary, usage): (DescriptionSummary, _) = self
+            .ai_client
+            .responses_structured_with_usage(
+                &self.model,
+                &system,
+                &user,
+                None,
+                "description_summary",
+                description_summary_schema(),
+                true,
+            )
+            .await?;
+        Ok((
+            summary.description,
+            Some(ModelCall::new(&self.model, usage)),
+        ))
+    }
+}
+
+/// Sets the description of an entity or relation from all of its sourced
+/// descriptions: joined while they fit the token budget, summarized once
+/// they don't.
+pub struct DescriptionConsolidator {
+    tokenizer: Arc<dyn Tokenizer>,
+    summarizer: Option<Arc<dyn DescriptionSummarizer>>,
+    config: DescriptionConfig,
+}
+
+impl DescriptionConsolidator {
+    pub fn new(tokenizer: Arc<dyn Tokenizer>, config: DescriptionConfig) -> Self {
+        Self {
+            tokenizer,
+            summarizer: None,
+            config,
+        }
+    }
+
+    pub fn with_summarizer(mut self, summarizer: Arc<dyn DescriptionSummarizer>) -> Self {
+        self.summarizer = Some(summarizer);
+        self
+    }
+
+    /// Sets `record[field]` to the joined descriptions, unless its summary
+    /// was written from the same ones. Returns the descriptions when they are
+    /// over budget and still need to be summarized.
+    pub fn join(&self, record: &mut Value, field: &str) -> Option<Vec<String>> {
+        let mut texts: Vec<String> = Vec::new();
+        for entry in sourced_descriptions(record, field) {
+            if !texts.contains(&entry.description) {
+                texts.push(entry.description);
+            }
+        }
+        if texts.is_empty() {
+            return None;
+        }
+
+        let joined = texts.join("\n");
+        let summary_of = compute_mdhash_id(&joined, "");
+        if record.get(SUMMARY_OF_KEY).and_then(Value::as_str) == Some(summary_of.as_str()) {
+            return None;
+        }
+        let over_budget = self.tokenizer.encode(&joined).len() > self.config.token_budget;
+        record[field] = json!(joined);
+        if let Some(map) = record.as_object_mut() {
+            map.remove(SUMMARY_OF_KEY);
+        }
+        over_budget.then_some(texts)
+    }
+
+    /// Updates `record[field]` like [`Self::join`], summarizing descriptions
+    /// over budget. When summarization fails the joined descriptions are
+    /// kept and the error returned, so it is retried the next time the
+    /// record is consolidated. Returns the summarization call made.
+    pub async fn consolidate(
+        &self,
+        subject: &str,
+        record: &mut Value,
+        field: &str,
+    ) -> Result<Option<ModelCall>> {
+        let Some(texts) = self.join(record, field) else {
+            return Ok(None);
+        };
+        let Some(summarizer) = &self.summarizer else {
+            return Ok(None);
+        };
+
+        let (summary, call) = summarizer
+            .summarize_with_usage(subject, &texts, self.config.token_budget)
+            .await?;
+        record[field] = json!(summary);
+        record[SUMMARY_OF_KEY] = json!(compute_mdhash_id(&texts.join("\n"), ""));
+        Ok(call)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::pipeline::utils::TiktokenTokenizer;
+
+    #[derive(Default)]
+    struct CountingSummarizer {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl DescriptionSummarizer for CountingSummarizer {
+        async fn summarize(
+            &self,
+            subject: &str,
+            descriptions: &[String],
+            _: usize,
+        ) -> Result<String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("{subject}: {} descriptions", descriptions.len()))
+        }
+    }
+
+    #[tokio::test]
+    async fn descriptions_are_summarized_over_budget_and_again_on_new_ones() {
+        let summarizer = Arc::new(CountingSummarizer::default());
+        let consolidator = DescriptionConsolidator::new(
+            Arc::new(TiktokenTokenizer::new().unwrap()),
+            DescriptionConfig { token_budget: 8 },
+        )
+        .with_summarizer(summarizer.clone());
+        let field = "entity_description";
+
+        let mut record = json!({ "entity_description": "mTOR inhibitor", "chunk_id": "c1" });
+        push_description(&mut record, field, "c2", "mTOR inhibitor");
+        consolidator
+            .consolidate("Rapamycin", &mut record, field)
+            .await
+            .unwrap();
+        assert_eq!(record[field], "mTOR inhibitor");
+        assert_eq!(summarizer.calls.load(Ordering::SeqCst), 0);
+
+        let mut update = json!({});
+        push_description(
+            &mut update,
+            field,
+            "c3",
+            "Extends lifespan in mice fed late in life",
+        );
+        merge_descriptions(&record, &mut update, field);
+        consolidator
+            .consolidate("Rapamycin", &mut update, field)
+            .await
+            .unwrap();
+        assert_eq!(update[field], "Rapamycin: 2 descriptions");
+        assert_eq!(update["descriptions"].as_array().unwrap().len(), 3);
+
+        let mut same = update.clone();
+        consolidator
+            .consolidate("Rapamycin", &mut same, field)
+            .await
+            .unwrap();
+        assert_eq!(summarizer.calls.load(Ordering::SeqCst), 1);
+
+        retire_descriptions(&mut same, &HashSet::from(["c3".to_string()]));
+        consolidator
+            .consolidate("Rapamycin", &mut same, field)
+            .await
+            .unwrap();
+        assert_eq!(same[field], "mTOR inhibitor");
+        assert!(same.get(SUMMARY_OF_KEY).is_none());
+    }
+}
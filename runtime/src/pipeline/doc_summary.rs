@@ -0,0 +1,259 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::ai::{
+    responses::{ModelCall, ResponsesClient},
+    schemas::{DocumentAbstract, document_abstract_schema},
+};
+
+use super::utils::Tokenizer;
+
+/// Fields of a `full_docs` record the summary is stored under.
+pub const ABSTRACT_KEY: &str = "abstract";
+pub const KEYWORDS_KEY: &str = "keywords";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DocumentSummaryConfig {
+    /// Writes an abstract and keywords for each document before extraction.
+    pub enabled: bool,
+    pub max_keywords: usize,
+    /// Documents are summarized from at most this many leading tokens.
+    pub max_input_tokens: usize,
+    /// Sends the abstract and keywords along with every chunk extracted.
+    pub extraction_context: bool,
+}
+
+impl Default for DocumentSummaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_keywords: 10,
+            max_input_tokens: 8000,
+            extraction_context: true,
+        }
+    }
+}
+
+#[async_trait]
+pub trait DocumentSummarizer: Send + Sync {
+    async fn summarize(&self, content: &str, max_keywords: usize) -> Result<DocumentAbstract>;
+
+    /// Like `summarize`, also returning the billed model call, if any.
+    async fn summarize_with_usage(
+        &self,
+        content: &str,
+        max_keywords: usize,
+    ) -> Result<(DocumentAbstract, Option<ModelCall>)> {
+        Ok((self.summarize(content, max_keywords).await?, None))
+    }
+}
+
+#[derive(Clone)]
+pub struct LlmDocumentSummarizer {
+    ai_client: Arc<ResponsesClient>,
+    model: String,
+}
+
+impl LlmDocumentSummarizer {
+    pub fn new(ai_client: Arc<ResponsesClient>, model: impl Into<String>) -> Self {
+        Self {
+            ai_client,
+            model: model.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl DocumentSummarizer for LlmDocumentSummarizer {
+    async fn summarize(&self, content: &str, max_keywords: usize) -> Result<DocumentAbstract> {
+        Ok(self.summarize_with_usage(content, max_keywords).await?.0)
+    }
+
+    async fn summarize_with_usage(
+        &self,
+        content: &str,
+        max_keywords: usize,
+    ) -> Result<(DocumentAbstract, Option<ModelCall>)> {
+        let system = format!(
+            "You write the abstract of a scientific document: a few sentences on its subject, \
+             methods and main findings, followed by at most {max_keywords} keywords. Use only \
+             what the text states."
+        );
+        let (summary, usage) = self
+            .ai_client
+            .responses_structured_with_usage(
+                &self.model,
+                &system,
+                content,
+                None,
+                "document_abstract",
+                document_abstract_schema(max_keywords),
+                true,
+            )
+            .await?;
+        Ok((summary, Some(ModelCall::new(&self.model, usage))))
+    }
+}
+
+/// Writes abstracts and keywords onto `full_docs` records and turns them
+/// into context for the extraction model.
+pub struct DocumentSummaries {
+    tokenizer: Arc<dyn Tokenizer>,
+    summarizer: Option<Arc<dyn DocumentSummarizer>>,
+    config: DocumentSummaryConfig,
+}
+
+impl DocumentSummaries {
+    pub fn new(tokenizer: Arc<dyn Tokenizer>, config: DocumentSummaryConfig) -> Self {
+        Self {
+            tokenizer,
+            summarizer: None,
+            config,
+        }
+    }
+
+    pub fn with_summarizer(mut self, summarizer: Arc<dyn DocumentSummarizer>) -> Self {
+        self.summarizer = Some(summarizer);
+        self
+    }
+
+    /// Summarizes the content of `record` unless it already has an abstract
+    /// or summaries are disabled. Returns the billed model calls made, or
+    /// `None` when the record was left unchanged.
+    pub async fn summarize(&self, record: &mut Value) -> Result<Option<Vec<ModelCall>>> {
+        let Some(summarizer) = self.summarizer.as_ref().filter(|_| self.config.enabled) else {
+            return Ok(None);
+        };
+        if document_abstract(record).is_some() {
+            return Ok(None);
+        }
+        let content = record
+            .get("content")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if content.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let tokens = self.tokenizer.encode(content);
+        let input = if tokens.len() > self.config.max_input_tokens {
+            self.tokenizer
+                .decode(&tokens[..self.config.max_input_tokens])?
+        } else {
+            content.to_string()
+        };
+        let (mut summary, call) = summarizer
+            .summarize_with_usage(&input, self.config.max_keywords)
+            .await?;
+        summary
+            .keywords
+            .retain(|keyword| !keyword.trim().is_empty());
+        summary.keywords.truncate(self.config.max_keywords);
+
+        record[ABSTRACT_KEY] = json!(summary.summary.trim());
+        record[KEYWORDS_KEY] = json!(summary.keywords);
+        Ok(Some(call.into_iter().collect()))
+    }
+
+    /// Prompt context for extracting a chunk of the document in `record`.
+    pub fn extraction_context(&self, record: &Value) -> Option<String> {
+        if !self.config.extraction_context {
+            return None;
+        }
+        let summary = document_abstract(record)?;
+        let mut context = format!("Summary: {}", summary.summary);
+        if !summary.keywords.is_empty() {
+            context.push_str(&format!("\nKeywords: {}", summary.keywords.join(", ")));
+        }
+        Some(context)
+    }
+}
+
+/// The abstract and keywords stored on a `full_docs` record, if any.
+pub fn document_abstract(record: &Value) -> Option<DocumentAbstract> {
+    let summary = record.get(ABSTRACT_KEY)?.as_str()?.to_string();
+    let keywords = record
+        .get(KEYWORDS_KEY)
+        .and_then(Value::as_array)
+        .map(|keywords| {
+            keywords
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(DocumentAbstract { summary, keywords })
+}
+
+/// Removes the abstract and keywords from a `full_docs` record, so the next
+/// summarize call writes new ones.
+pub fn clear_summary(record: &mut Value) {
+    if let Some(fields) = record.as_object_mut() {
+        fields.remove(ABSTRACT_KEY);
+        fields.remove(KEYWORDS_KEY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::pipeline::utils::TiktokenTokenizer;
+
+    #[derive(Default)]
+    struct RecordingSummarizer {
+        inputs: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl DocumentSummarizer for RecordingSummarizer {
+        async fn summarize(&self, content: &str, _max_keywords: usize) -> Result<DocumentAbstract> {
+            self.inputs.lock().unwrap().push(content.to_string());
+            Ok(DocumentAbstract {
+                summary: " Rapamycin extends lifespan in mice. ".to_string(),
+                keywords: vec![
+                    "rapamycin".to_string(),
+                    " ".to_string(),
+                    "mTOR".to_string(),
+                    "lifespan".to_string(),
+                ],
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn summarizes_once_from_leading_tokens_and_builds_context() {
+        let summarizer = Arc::new(RecordingSummarizer::default());
+        let summaries = DocumentSummaries::new(
+            Arc::new(TiktokenTokenizer::new().unwrap()),
+            DocumentSummaryConfig {
+                enabled: true,
+                max_keywords: 2,
+                max_input_tokens: 20,
+                ..DocumentSummaryConfig::default()
+            },
+        )
+        .with_summarizer(summarizer.clone());
+
+        let mut record = json!({ "content": "Rapamycin inhibits mTOR. ".repeat(50) });
+        assert!(summaries.summarize(&mut record).await.unwrap().is_some());
+        assert!(summaries.summarize(&mut record).await.unwrap().is_none());
+
+        let inputs = summarizer.inputs.lock().unwrap();
+        assert_eq!(inputs.len(), 1);
+        assert!(inputs[0].len() < 200);
+        assert_eq!(record[ABSTRACT_KEY], "Rapamycin extends lifespan in mice.");
+        assert_eq!(record[KEYWORDS_KEY], json!(["rapamycin", "mTOR"]));
+        assert_eq!(
+            summaries.extraction_context(&record).unwrap(),
+            "Summary: Rapamycin extends lifespan in mice.\nKeywords: rapamycin, mTOR"
+        );
+    }
+}
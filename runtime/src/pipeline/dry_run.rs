@@ -0,0 +1,240 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::ai::responses::TokenUsage;
+
+use super::{
+    chunker::{ChunkStrategy, Chunker, TokenizerChunker, format_chunkers},
+    csv_mapping::find_mapping,
+    document_manager::normalize_extension,
+    extractor::{EXTRACTION_SYSTEM_PROMPT, decode_text, extraction_schema},
+    hooks::{HookConfig, PipelineHook},
+    pipeline::{PipelineConfig, file_extension},
+    utils::{TiktokenTokenizer, Tokenizer},
+};
+
+/// Assumptions behind dry-run cost estimates.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DryRunConfig {
+    /// Output tokens expected per chunk token, reasoning included.
+    pub output_token_ratio: f64,
+}
+
+impl Default for DryRunConfig {
+    fn default() -> Self {
+        Self {
+            output_token_ratio: 1.0,
+        }
+    }
+}
+
+/// What ingesting one file would produce, without ingesting it.
+#[derive(Debug, Default, Clone, Serialize, TS)]
+#[ts(export)]
+pub struct FileEstimate {
+    pub file_name: String,
+    pub chunks: usize,
+    /// Tokens across all chunks, overlap included.
+    pub tokens: u64,
+    /// Extraction calls if every gleaning pass runs; mapped CSV tables need none.
+    pub llm_calls: u64,
+    pub estimated_input_tokens: u64,
+    pub estimated_output_tokens: u64,
+    /// `None` when the model has no configured price.
+    #[ts(optional)]
+    pub estimated_cost_usd: Option<f64>,
+    /// Why the file could not be estimated; it would be rejected on upload too.
+    #[ts(optional)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, TS)]
+#[ts(export)]
+pub struct DryRunReport {
+    pub model: String,
+    pub files: Vec<FileEstimate>,
+    pub chunks: usize,
+    pub tokens: u64,
+    pub llm_calls: u64,
+    pub estimated_input_tokens: u64,
+    pub estimated_output_tokens: u64,
+    /// Cost of the files that could be priced.
+    pub estimated_cost_usd: f64,
+}
+
+impl DryRunReport {
+    pub fn push(&mut self, file: FileEstimate) {
+        self.chunks += file.chunks;
+        self.tokens += file.tokens;
+        self.llm_calls += file.llm_calls;
+        self.estimated_input_tokens += file.estimated_input_tokens;
+        self.estimated_output_tokens += file.estimated_output_tokens;
+        self.estimated_cost_usd += file.estimated_cost_usd.unwrap_or_default();
+        self.files.push(file);
+    }
+}
+
+/// Chunks files the way ingestion would and estimates the extraction calls
+/// they need, after the configured hooks have rewritten the text. Nothing is
+/// stored and no model is called; the semantic strategy is estimated with
+/// sentence chunking, as it needs embeddings.
+pub struct CostEstimator {
+    config: PipelineConfig,
+    supported_extensions: Vec<String>,
+    chunker: Arc<dyn Chunker>,
+    format_chunkers: HashMap<String, Arc<dyn Chunker>>,
+    hooks: Vec<Arc<dyn PipelineHook>>,
+    /// Tokens sent with every call besides the chunk: system prompt and schema.
+    prompt_overhead: u64,
+}
+
+impl CostEstimator {
+    pub fn new(config: PipelineConfig, supported_extensions: &[&str]) -> Result<Self> {
+        let tokenizer: Arc<dyn Tokenizer> = Arc::new(TiktokenTokenizer::new()?);
+        let schema = extraction_schema(&config.ontology).to_string();
+        let prompt_overhead = (tokenizer.encode(EXTRACTION_SYSTEM_PROMPT).len()
+            + tokenizer.encode(&schema).len()) as u64;
+        Ok(Self {
+            supported_extensions: supported_extensions
+                .iter()
+                .map(|ext| normalize_extension(ext))
+                .collect(),
+            format_chunkers: format_chunkers(tokenizer.clone()),
+            chunker: Arc::new(TokenizerChunker::new(tokenizer)),
+            hooks: config.hooks.iter().map(HookConfig::build).collect(),
+            prompt_overhead,
+            config,
+        })
+    }
+
+    pub fn is_supported(&self, file_name: &str) -> bool {
+        file_extension(file_name).is_some_and(|ext| self.supported_extensions.contains(&ext))
+    }
+
+    pub fn report(&self) -> DryRunReport {
+        DryRunReport {
+            model: self.config.llm_model.clone(),
+            ..DryRunReport::default()
+        }
+    }
+
+    /// Estimates one file; failures are reported on the estimate.
+    pub async fn estimate(&self, file_name: &str, bytes: Vec<u8>) -> FileEstimate {
+        match self.try_estimate(file_name, bytes).await {
+            Ok(estimate) => estimate,
+            Err(err) => FileEstimate {
+                file_name: file_name.to_string(),
+                error: Some(err.to_string()),
+                ..FileEstimate::default()
+            },
+        }
+    }
+
+    async fn try_estimate(&self, file_name: &str, bytes: Vec<u8>) -> Result<FileEstimate> {
+        if !self.is_supported(file_name) {
+            return Err(anyhow!(
+                "unsupported file type. supported types: {:?}",
+                self.supported_extensions
+            ));
+        }
+        let mut content = decode_text(bytes)?;
+        for hook in &self.hooks {
+            content = hook.before_chunking(file_name, content).await?;
+        }
+
+        let mut chunk_config = self.config.chunk_config();
+        if chunk_config.strategy == ChunkStrategy::Semantic {
+            chunk_config.strategy = ChunkStrategy::Sentence;
+        }
+        let chunker = file_extension(file_name)
+            .and_then(|ext| self.format_chunkers.get(&ext))
+            .unwrap_or(&self.chunker);
+        let chunks = chunker.chunk(&content, &chunk_config).await?;
+
+        let mut estimate = FileEstimate {
+            file_name: file_name.to_string(),
+            chunks: chunks.len(),
+            tokens: chunks.iter().map(|chunk| chunk.token_count as u64).sum(),
+            ..FileEstimate::default()
+        };
+        let mapped = file_extension(file_name).as_deref() == Some("csv")
+            && find_mapping(&self.config.csv_mappings, file_name, &content).is_some();
+        if mapped {
+            estimate.estimated_cost_usd = Some(0.0);
+            return Ok(estimate);
+        }
+
+        for chunk in &chunks {
+            let chunk_tokens = chunk.token_count as u64;
+            let output = (chunk_tokens as f64 * self.config.dry_run.output_token_ratio) as u64;
+            let mut input = self.prompt_overhead + chunk_tokens;
+            for _ in 0..=self.config.gleaning.passes {
+                estimate.llm_calls += 1;
+                estimate.estimated_input_tokens += input;
+                estimate.estimated_output_tokens += output;
+                // gleaning passes repeat the chunk with what was found so far
+                input += output;
+            }
+        }
+        estimate.estimated_cost_usd = self.config.pricing.price(
+            &self.config.llm_model,
+            &TokenUsage {
+                input_tokens: estimate.estimated_input_tokens,
+                output_tokens: estimate.estimated_output_tokens,
+                reasoning_tokens: 0,
+            },
+        );
+        Ok(estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{
+        extractor::GleaningConfig,
+        usage::{ModelPricing, PricingConfig},
+    };
+
+    #[tokio::test]
+    async fn estimates_chunks_calls_and_cost_without_ingesting() {
+        let config = PipelineConfig {
+            chunk_size: 50,
+            chunk_overlap: 0,
+            gleaning: GleaningConfig { passes: 1 },
+            pricing: PricingConfig {
+                models: HashMap::from([(
+                    "gpt-5-mini".to_string(),
+                    ModelPricing {
+                        input_per_million: 0.25,
+                        output_per_million: 2.0,
+                    },
+                )]),
+            },
+            ..PipelineConfig::default()
+        };
+        let estimator = CostEstimator::new(config, &[".txt", ".md"]).unwrap();
+        let text = "Rapamycin inhibits mTOR and extends lifespan in mice. ".repeat(20);
+
+        let estimate = estimator.estimate("notes.txt", text.into_bytes()).await;
+        assert!(estimate.error.is_none());
+        assert!(estimate.chunks > 1);
+        assert_eq!(estimate.llm_calls, 2 * estimate.chunks as u64);
+        assert!(estimate.estimated_input_tokens > 2 * estimate.tokens);
+        assert_eq!(estimate.estimated_output_tokens, 2 * estimate.tokens);
+        assert!(estimate.estimated_cost_usd.unwrap() > 0.0);
+
+        let rejected = estimator.estimate("table.pdf", b"%PDF".to_vec()).await;
+        assert!(rejected.error.is_some());
+
+        let mut report = estimator.report();
+        report.push(estimate.clone());
+        report.push(rejected);
+        assert_eq!(report.chunks, estimate.chunks);
+        assert_eq!(report.files.len(), 2);
+    }
+}
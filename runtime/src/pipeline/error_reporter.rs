@@ -3,7 +3,7 @@ use std::{collections::HashMap, path::Path, sync::Arc};
 use anyhow::Result;
 use serde_json::json;
 
-use crate::storage::{DocProcessingStatus, DocStatus, DocStatusStorage};
+use crate::storage::{DocProcessingStatus, DocStatus, DocStatusStorage, ERROR_ID_PREFIX};
 
 use super::utils::compute_mdhash_id;
 
@@ -47,7 +47,7 @@ impl ErrorReporter {
             error_msg: Some(err.to_string()),
         };
 
-        let doc_id = compute_mdhash_id(&format!("error-{track_id}-{filename}"), "error-");
+        let doc_id = compute_mdhash_id(&format!("error-{track_id}-{filename}"), ERROR_ID_PREFIX);
         let mut payload = HashMap::new();
         payload.insert(doc_id, error_doc);
 
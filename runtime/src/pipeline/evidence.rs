@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use ts_rs::TS;
+
+use super::chunker::Span;
+
+/// A quote supporting an extracted entity or relation, verified to occur in
+/// the chunk it was extracted from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Evidence {
+    pub chunk_id: String,
+    /// The quote as it appears in the chunk, which may differ from what the
+    /// model returned in case and whitespace.
+    pub quote: String,
+    /// Position of the quote in the chunk text.
+    pub chunk_span: Span,
+    /// Position of the quote in the stored document, when the chunk's own
+    /// position is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub span: Option<Span>,
+}
+
+impl Evidence {
+    /// Verifies `quote` against the chunk text. Returns `None` when the quote
+    /// can't be found, so made-up evidence is never stored.
+    pub fn locate(
+        chunk_id: &str,
+        chunk_text: &str,
+        chunk_span: Option<Span>,
+        quote: &str,
+    ) -> Option<Self> {
+        let found = locate_quote(chunk_text, quote)?;
+        Some(Self {
+            chunk_id: chunk_id.to_string(),
+            quote: chunk_text[found.start..found.end].to_string(),
+            chunk_span: found,
+            span: chunk_span.map(|chunk| Span {
+                start: chunk.start + found.start,
+                end: chunk.start + found.end,
+            }),
+        })
+    }
+}
+
+/// Finds `quote` in `text`, first verbatim, then ignoring case and runs of
+/// whitespace. Surrounding quotation marks and ellipses are ignored.
+pub fn locate_quote(text: &str, quote: &str) -> Option<Span> {
+    let quote = quote
+        .trim()
+        .trim_matches(|c: char| matches!(c, '"' | '\'' | '“' | '”' | '…'))
+        .trim_end_matches("...")
+        .trim();
+    if quote.is_empty() {
+        return None;
+    }
+    if let Some(start) = text.find(quote) {
+        return Some(Span {
+            start,
+            end: start + quote.len(),
+        });
+    }
+
+    let haystack = folded(text);
+    let needle: Vec<char> = folded(quote).into_iter().map(|(c, _, _)| c).collect();
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return None;
+    }
+    (0..=haystack.len() - needle.len())
+        .find(|&i| {
+            haystack[i..i + needle.len()]
+                .iter()
+                .map(|(c, _, _)| *c)
+                .eq(needle.iter().copied())
+        })
+        .map(|i| {
+            let (_, start, _) = haystack[i];
+            let (_, last, len) = haystack[i + needle.len() - 1];
+            Span {
+                start,
+                end: last + len,
+            }
+        })
+}
+
+/// Lowercased characters of `text` with whitespace runs collapsed to one
+/// space, each with the byte offset and length of the character it came from.
+fn folded(text: &str) -> Vec<(char, usize, usize)> {
+    let mut chars = Vec::with_capacity(text.len());
+    let mut in_space = false;
+    for (offset, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if !in_space && !chars.is_empty() {
+                chars.push((' ', offset, c.len_utf8()));
+            }
+            in_space = true;
+            continue;
+        }
+        in_space = false;
+        for lower in c.to_lowercase() {
+            chars.push((lower, offset, c.len_utf8()));
+        }
+    }
+    chars
+}
+
+/// Evidence stored on an entity or relation record.
+pub fn record_evidence(record: &Value) -> Vec<Evidence> {
+    record
+        .get("evidence")
+        .and_then(|evidence| serde_json::from_value(evidence.clone()).ok())
+        .unwrap_or_default()
+}
+
+pub fn push_evidence(record: &mut Value, evidence: Evidence) {
+    let mut all = record_evidence(record);
+    if !all.contains(&evidence) {
+        all.push(evidence);
+    }
+    record["evidence"] = json!(all);
+}
+
+/// Adds the evidence of the stored version of a record to the one about to
+/// replace it.
+pub fn merge_evidence(existing: &Value, record: &mut Value) {
+    let mut all = record_evidence(existing);
+    for evidence in record_evidence(record) {
+        if !all.contains(&evidence) {
+            all.push(evidence);
+        }
+    }
+    record["evidence"] = json!(all);
+}
+
+/// Drops the evidence found in `removed` chunks.
+pub fn retire_evidence(record: &mut Value, removed: &HashSet<String>) {
+    if record.get("evidence").is_none() {
+        return;
+    }
+    let remaining: Vec<Evidence> = record_evidence(record)
+        .into_iter()
+        .filter(|evidence| !removed.contains(&evidence.chunk_id))
+        .collect();
+    record["evidence"] = json!(remaining);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_are_located_loosely_and_offset_into_the_document() {
+        let chunk = "Rapamycin extended\n  median lifespan in Mice.";
+
+        let exact = locate_quote(chunk, "median lifespan").unwrap();
+        assert_eq!(&chunk[exact.start..exact.end], "median lifespan");
+
+        let loose = Evidence::locate(
+            "chunk-1",
+            chunk,
+            Some(Span {
+                start: 100,
+                end: 100 + chunk.len(),
+            }),
+            "\"rapamycin extended median lifespan in mice\"",
+        )
+        .unwrap();
+        assert_eq!(loose.quote, "Rapamycin extended\n  median lifespan in Mice");
+        assert_eq!(loose.chunk_span.start, 0);
+        assert_eq!(loose.span.unwrap().start, 100);
+        assert_eq!(loose.span.unwrap().end, 100 + loose.quote.len());
+
+        assert!(locate_quote(chunk, "metformin extended lifespan").is_none());
+        assert!(locate_quote(chunk, "  ").is_none());
+    }
+}
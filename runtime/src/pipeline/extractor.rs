@@ -1,16 +1,24 @@
-use std::{path::Path, sync::Arc};
+use std::{collections::HashSet, path::Path, sync::Arc};
 
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use ts_rs::TS;
 
 use super::{
+    canonical::normalize_entity_name,
     chunker::Chunk,
     document_manager::{DocumentManager, FileRepository, normalize_extension},
+    ontology::Ontology,
 };
 
 use crate::ai::{
-    responses::ResponsesClient,
-    schemas::{EntitiesRelationships, entities_relationships_schema},
+    responses::{ModelCall, ResponsesClient, TokenUsage},
+    schemas::{
+        EntitiesRelationships, ExtractedEntity, ExtractedRelationship,
+        entities_relationships_schema,
+    },
 };
 
 #[async_trait]
@@ -48,53 +56,330 @@ impl DocumentExtractor for Utf8DocumentExtractor {
             return Err(anyhow!("unsupported file type: {}", extension));
         }
 
-        let text = String::from_utf8(bytes).map_err(|_| anyhow!("file is not valid UTF-8"))?;
-        if text.trim().is_empty() {
-            return Err(anyhow!("file contains only whitespace"));
-        }
+        decode_text(bytes)
+    }
+}
+
+/// Text of a UTF-8 document, rejecting files without any.
+pub fn decode_text(bytes: Vec<u8>) -> Result<String> {
+    let text = String::from_utf8(bytes).map_err(|_| anyhow!("file is not valid UTF-8"))?;
+    if text.trim().is_empty() {
+        return Err(anyhow!("file contains only whitespace"));
+    }
+    Ok(text)
+}
+
+pub const EXTRACTION_SYSTEM_PROMPT: &str = "You are helpful assistant";
+
+/// Structured output schema for extracting entities and relationships with
+/// the types and predicates of `ontology`.
+pub fn extraction_schema(ontology: &Ontology) -> serde_json::Value {
+    entities_relationships_schema(
+        &ontology.type_names(),
+        &ontology.type_guide(),
+        &ontology.predicate_names(),
+        &ontology.predicate_guide(),
+    )
+}
 
-        Ok(text)
+/// Follow-up extraction passes per chunk. Each pass shows the model what
+/// was found so far and asks for what it missed; gleaning stops early once a
+/// pass finds nothing new.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GleaningConfig {
+    /// Passes after the first one; 0 extracts each chunk once.
+    pub passes: usize,
+}
+
+/// What one extraction pass over a chunk added and what it cost. Stored on
+/// the chunk record under `extraction_passes`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ExtractionPass {
+    /// 0 for the initial extraction, then 1 for the first gleaning pass.
+    pub pass: usize,
+    pub entities_added: usize,
+    pub relationships_added: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub reasoning_tokens: u64,
+}
+
+/// Entities and relationships extracted from a chunk over all passes.
+#[derive(Debug, Default, Clone)]
+pub struct ChunkExtraction {
+    pub result: EntitiesRelationships,
+    pub passes: Vec<ExtractionPass>,
+    /// Model the passes were made with, for pricing their tokens.
+    pub model: String,
+}
+
+impl ChunkExtraction {
+    /// Adds what a pass found that isn't already there, comparing entities
+    /// by normalized name and type, and relationships by their normalized
+    /// endpoints and predicate.
+    pub fn merge_pass(&mut self, pass: usize, found: EntitiesRelationships, usage: TokenUsage) {
+        let mut entities: HashSet<(String, String)> =
+            self.result.entities.iter().map(entity_key).collect();
+        let mut relationships: HashSet<(String, String, Option<String>)> = self
+            .result
+            .relationships
+            .iter()
+            .map(relationship_key)
+            .collect();
+
+        let mut record = ExtractionPass {
+            pass,
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            reasoning_tokens: usage.reasoning_tokens,
+            ..ExtractionPass::default()
+        };
+        for entity in found.entities {
+            if entities.insert(entity_key(&entity)) {
+                record.entities_added += 1;
+                self.result.entities.push(entity);
+            }
+        }
+        for relationship in found.relationships {
+            if relationships.insert(relationship_key(&relationship)) {
+                record.relationships_added += 1;
+                self.result.relationships.push(relationship);
+            }
+        }
+        self.passes.push(record);
     }
 }
 
+fn entity_key(entity: &ExtractedEntity) -> (String, String) {
+    (
+        normalize_entity_name(&entity.entity_name),
+        entity.entity_type.clone(),
+    )
+}
+
+fn relationship_key(relationship: &ExtractedRelationship) -> (String, String, Option<String>) {
+    (
+        normalize_entity_name(&relationship.source_entity),
+        normalize_entity_name(&relationship.target_entity),
+        relationship.predicate.clone(),
+    )
+}
+
 #[derive(Clone)]
 pub struct EntityRelationshipExtract {
     ai_client: Arc<ResponsesClient>,
+    model: String,
+    ontology: Arc<Ontology>,
+    gleaning: GleaningConfig,
 }
 
 impl EntityRelationshipExtract {
-    pub fn new(ai_client: Arc<ResponsesClient>) -> Self {
-        Self { ai_client }
+    pub fn new(ai_client: Arc<ResponsesClient>, model: impl Into<String>) -> Self {
+        Self {
+            ai_client,
+            model: model.into(),
+            ontology: Arc::new(Ontology::builtin()),
+            gleaning: GleaningConfig::default(),
+        }
+    }
+
+    /// Entity types offered to the model instead of the built-in ones.
+    pub fn with_ontology(mut self, ontology: Arc<Ontology>) -> Self {
+        self.ontology = ontology;
+        self
+    }
+
+    pub fn with_gleaning(mut self, gleaning: GleaningConfig) -> Self {
+        self.gleaning = gleaning;
+        self
+    }
+
+    async fn extract_pass(
+        &self,
+        chunk: &Chunk,
+        prompt: &str,
+    ) -> Result<(EntitiesRelationships, TokenUsage)> {
+        let schema = extraction_schema(&self.ontology);
+        let (mut er, usage): (EntitiesRelationships, TokenUsage) = self
+            .ai_client
+            .responses_structured_with_usage(
+                &self.model,
+                EXTRACTION_SYSTEM_PROMPT,
+                prompt,
+                Some(&chunk.id),
+                "entities_relarionships",
+                schema,
+                true,
+            )
+            .await?;
+        for entity in &mut er.entities {
+            entity.entity_type = self.ontology.resolve_type(&entity.entity_type).to_string();
+        }
+        Ok((er, usage))
     }
 }
 
+/// Prompt for a gleaning pass: the chunk followed by what was extracted so far.
+fn gleaning_prompt(chunk_prompt: &str, found: &EntitiesRelationships) -> String {
+    let entities = found
+        .entities
+        .iter()
+        .map(|entity| format!("- {} ({})", entity.entity_name, entity.entity_type))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let relationships = found
+        .relationships
+        .iter()
+        .map(|relationship| {
+            format!(
+                "- {} -> {}",
+                relationship.source_entity, relationship.target_entity
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "{chunk_prompt}\n\nThe following were already extracted from this text.\n\nEntities:\n{entities}\n\nRelationships:\n{relationships}\n\nMany entities and relationships were missed. Extract only the ones missing from the lists above, using the same names for entities already listed. Return empty lists if nothing was missed."
+    )
+}
+
 #[async_trait]
 pub trait EntityRelationshipExtractor: Send + Sync {
-    async fn extract_entities_and_relationships(
+    async fn extract_entities_and_relationships(&self, chunk: &Chunk) -> Result<ChunkExtraction>;
+
+    /// Extracts `chunk` with `context` about the whole document, such as its
+    /// abstract, given to the model alongside it.
+    async fn extract_with_context(
         &self,
         chunk: &Chunk,
-    ) -> Result<EntitiesRelationships>;
+        context: Option<&str>,
+    ) -> Result<ChunkExtraction> {
+        let _ = context;
+        self.extract_entities_and_relationships(chunk).await
+    }
 }
 
 #[async_trait]
 impl EntityRelationshipExtractor for EntityRelationshipExtract {
-    async fn extract_entities_and_relationships(
+    async fn extract_entities_and_relationships(&self, chunk: &Chunk) -> Result<ChunkExtraction> {
+        self.extract_with_context(chunk, None).await
+    }
+
+    async fn extract_with_context(
         &self,
         chunk: &Chunk,
-    ) -> Result<EntitiesRelationships> {
-        let schema = entities_relationships_schema();
-        let er: EntitiesRelationships = self
-            .ai_client
-            .responses_structured(
-                "gpt-5-mini",
-                "You are helpful assistant",
-                &chunk.content,
-                Some(&chunk.id),
-                "entities_relarionships",
-                schema,
-                true,
-            )
-            .await?;
-        Ok(er)
+        context: Option<&str>,
+    ) -> Result<ChunkExtraction> {
+        let mut user_prompt = match chunk.heading.as_deref() {
+            Some(heading) => format!("Section: {heading}\n\n{}", chunk.content),
+            None => chunk.content.clone(),
+        };
+        if let Some(context) = context {
+            user_prompt = format!(
+                "About the document this text is from; extract only from the text below it.\n{context}\n\nText:\n{user_prompt}"
+            );
+        }
+        let mut extraction = ChunkExtraction {
+            model: self.model.clone(),
+            ..ChunkExtraction::default()
+        };
+        let (found, usage) = self.extract_pass(chunk, &user_prompt).await?;
+        extraction.merge_pass(0, found, usage);
+
+        for pass in 1..=self.gleaning.passes {
+            let prompt = gleaning_prompt(&user_prompt, &extraction.result);
+            // a failed follow-up pass keeps what the earlier passes found,
+            // and the tokens it was billed for
+            let (found, usage) = match self.extract_pass(chunk, &prompt).await {
+                Ok(found) => found,
+                Err(err) => {
+                    warn!(error = %err, chunk_id = %chunk.id, pass, "gleaning pass failed");
+                    if let Some(call) = ModelCall::from_error(&self.model, &err) {
+                        extraction.merge_pass(pass, EntitiesRelationships::default(), call.usage);
+                    }
+                    break;
+                }
+            };
+            extraction.merge_pass(pass, found, usage);
+            let added = extraction.passes.last().copied().unwrap_or_default();
+            if added.entities_added == 0 && added.relationships_added == 0 {
+                break;
+            }
+        }
+        Ok(extraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::schemas::Modality;
+
+    fn entity(name: &str) -> ExtractedEntity {
+        ExtractedEntity {
+            entity_name: name.to_string(),
+            entity_type: "Compound".to_string(),
+            entity_description: String::new(),
+            evidence: String::new(),
+        }
+    }
+
+    fn relationship(source: &str, target: &str) -> ExtractedRelationship {
+        ExtractedRelationship {
+            source_entity: source.to_string(),
+            target_entity: target.to_string(),
+            relationship_keywords: Vec::new(),
+            relationship_description: String::new(),
+            evidence: String::new(),
+            confidence: None,
+            modality: Modality::Asserted,
+            hedge_cues: Vec::new(),
+            predicate: None,
+        }
+    }
+
+    #[test]
+    fn gleaned_passes_only_add_what_was_missed() {
+        let usage = TokenUsage {
+            input_tokens: 100,
+            output_tokens: 20,
+            reasoning_tokens: 5,
+        };
+        let mut extraction = ChunkExtraction::default();
+        extraction.merge_pass(
+            0,
+            EntitiesRelationships {
+                entities: vec![entity("Rapamycin"), entity("Metformin")],
+                relationships: vec![relationship("Rapamycin", "Metformin")],
+            },
+            usage,
+        );
+        extraction.merge_pass(
+            1,
+            EntitiesRelationships {
+                entities: vec![entity("rapamycin"), entity("Acarbose")],
+                relationships: vec![
+                    relationship("RAPAMYCIN", "metformin"),
+                    relationship("Acarbose", "Metformin"),
+                ],
+            },
+            usage,
+        );
+
+        assert_eq!(extraction.result.entities.len(), 3);
+        assert_eq!(extraction.result.relationships.len(), 2);
+        assert_eq!(
+            extraction.passes[1],
+            ExtractionPass {
+                pass: 1,
+                entities_added: 1,
+                relationships_added: 1,
+                input_tokens: 100,
+                output_tokens: 20,
+                reasoning_tokens: 5,
+            }
+        );
     }
 }
@@ -0,0 +1,389 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::ai::schemas::EntitiesRelationships;
+
+use super::canonical::normalize_entity_name;
+
+/// Hooks each workspace runs, in order.
+///
+/// ```yaml
+/// hooks:
+///   default:
+///     - kind: text_cleanup
+///   workspaces:
+///     aging:
+///       - kind: text_cleanup
+///         dehyphenate: true
+///       - kind: entity_filter
+///         drop_types: [Other]
+///       - kind: enrich
+///         fields: { curated_by: aging-team }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    pub default: Vec<HookConfig>,
+    /// Hooks for individual workspaces, used instead of `default`.
+    pub workspaces: HashMap<String, Vec<HookConfig>>,
+}
+
+impl HooksConfig {
+    pub fn for_workspace(&self, workspace: Option<&str>) -> Vec<HookConfig> {
+        workspace
+            .and_then(|workspace| self.workspaces.get(workspace))
+            .unwrap_or(&self.default)
+            .clone()
+    }
+}
+
+/// A built-in hook and its settings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HookConfig {
+    TextCleanup(TextCleanup),
+    EntityFilter(EntityFilter),
+    Enrich(Enrich),
+}
+
+impl HookConfig {
+    pub fn build(&self) -> Arc<dyn PipelineHook> {
+        match self {
+            Self::TextCleanup(hook) => Arc::new(hook.clone()),
+            Self::EntityFilter(hook) => Arc::new(hook.clone()),
+            Self::Enrich(hook) => Arc::new(hook.clone()),
+        }
+    }
+}
+
+/// Records a [`PipelineHook::before_upsert`] call is about to store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    Entities,
+    Relations,
+}
+
+/// Runs between the pipeline stages. Every method defaults to passing its
+/// input through, so a hook only implements the points it cares about; an
+/// error fails the document like any other stage.
+#[async_trait]
+pub trait PipelineHook: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Rewrites document text before it is identified, stored and chunked.
+    async fn before_chunking(&self, _file_path: &str, content: String) -> Result<String> {
+        Ok(content)
+    }
+
+    /// Edits what was extracted from a chunk, by the LLM or a CSV mapping,
+    /// before it becomes entity and relation records.
+    async fn after_extraction(
+        &self,
+        _doc_id: &str,
+        _chunk_id: &str,
+        extraction: EntitiesRelationships,
+    ) -> Result<EntitiesRelationships> {
+        Ok(extraction)
+    }
+
+    /// Edits entity or relation records right before they are stored.
+    async fn before_upsert(
+        &self,
+        _doc_id: &str,
+        _kind: RecordKind,
+        _records: &mut HashMap<String, Value>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Normalizes text extracted from PDFs and web pages.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextCleanup {
+    /// Collapses runs of spaces and tabs, and of more than two newlines.
+    pub collapse_whitespace: bool,
+    /// Joins words hyphenated across a line break, e.g. `inhi-\nbits`.
+    pub dehyphenate: bool,
+    /// Literal strings removed wherever they occur, e.g. page footers.
+    pub remove: Vec<String>,
+}
+
+impl Default for TextCleanup {
+    fn default() -> Self {
+        Self {
+            collapse_whitespace: true,
+            dehyphenate: false,
+            remove: Vec::new(),
+        }
+    }
+}
+
+impl TextCleanup {
+    pub fn clean(&self, content: &str) -> String {
+        let mut text = content.to_string();
+        for pattern in self.remove.iter().filter(|pattern| !pattern.is_empty()) {
+            text = text.replace(pattern.as_str(), "");
+        }
+        if self.dehyphenate {
+            text = dehyphenate(&text);
+        }
+        if self.collapse_whitespace {
+            text = collapse_whitespace(&text);
+        }
+        text.trim().to_string()
+    }
+}
+
+fn dehyphenate(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find("-\n") {
+        let before = &rest[..idx];
+        let after = &rest[idx + 2..];
+        let joins_words = before.chars().last().is_some_and(char::is_alphabetic)
+            && after.chars().next().is_some_and(char::is_lowercase);
+        out.push_str(before);
+        if !joins_words {
+            out.push_str("-\n");
+        }
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut newlines = 0;
+    let mut space = false;
+    for ch in text.chars() {
+        match ch {
+            '\n' => {
+                newlines += 1;
+                space = false;
+            }
+            ' ' | '\t' => space = true,
+            _ => {
+                if newlines > 0 {
+                    // trailing spaces before a newline are dropped
+                    out.truncate(out.trim_end_matches([' ', '\t']).len());
+                    out.push_str(if newlines > 1 { "\n\n" } else { "\n" });
+                } else if space && !out.is_empty() {
+                    out.push(' ');
+                }
+                newlines = 0;
+                space = false;
+                out.push(ch);
+            }
+        }
+    }
+    out
+}
+
+#[async_trait]
+impl PipelineHook for TextCleanup {
+    fn name(&self) -> &str {
+        "text_cleanup"
+    }
+
+    async fn before_chunking(&self, _file_path: &str, content: String) -> Result<String> {
+        Ok(self.clean(&content))
+    }
+}
+
+/// Drops extracted entities by type or name, and relationships to them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EntityFilter {
+    pub drop_types: Vec<String>,
+    /// Compared after normalization, so case and spacing do not matter.
+    pub drop_names: Vec<String>,
+    /// Drops entities with shorter names, in characters.
+    pub min_name_length: usize,
+}
+
+impl EntityFilter {
+    pub fn apply(&self, extraction: EntitiesRelationships) -> EntitiesRelationships {
+        let drop_names: HashSet<String> = self
+            .drop_names
+            .iter()
+            .map(|name| normalize_entity_name(name))
+            .collect();
+        let mut dropped = HashSet::new();
+        let entities = extraction
+            .entities
+            .into_iter()
+            .filter(|entity| {
+                let name = normalize_entity_name(&entity.entity_name);
+                let keep = !drop_names.contains(&name)
+                    && name.chars().count() >= self.min_name_length
+                    && !self
+                        .drop_types
+                        .iter()
+                        .any(|ty| ty.eq_ignore_ascii_case(&entity.entity_type));
+                if !keep {
+                    dropped.insert(name);
+                }
+                keep
+            })
+            .collect();
+        let relationships = extraction
+            .relationships
+            .into_iter()
+            .filter(|relationship| {
+                !dropped.contains(&normalize_entity_name(&relationship.source_entity))
+                    && !dropped.contains(&normalize_entity_name(&relationship.target_entity))
+            })
+            .collect();
+        EntitiesRelationships {
+            entities,
+            relationships,
+        }
+    }
+}
+
+#[async_trait]
+impl PipelineHook for EntityFilter {
+    fn name(&self) -> &str {
+        "entity_filter"
+    }
+
+    async fn after_extraction(
+        &self,
+        _doc_id: &str,
+        _chunk_id: &str,
+        extraction: EntitiesRelationships,
+    ) -> Result<EntitiesRelationships> {
+        Ok(self.apply(extraction))
+    }
+}
+
+/// Sets fixed fields on stored entity and relation records.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Enrich {
+    pub fields: Map<String, Value>,
+    /// Only enriches this kind of record; both when unset.
+    pub records: Option<RecordKind>,
+}
+
+#[async_trait]
+impl PipelineHook for Enrich {
+    fn name(&self) -> &str {
+        "enrich"
+    }
+
+    async fn before_upsert(
+        &self,
+        _doc_id: &str,
+        kind: RecordKind,
+        records: &mut HashMap<String, Value>,
+    ) -> Result<()> {
+        if self.records.is_some_and(|only| only != kind) {
+            return Ok(());
+        }
+        for record in records.values_mut() {
+            if let Some(record) = record.as_object_mut() {
+                for (field, value) in &self.fields {
+                    record.insert(field.clone(), value.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::ai::schemas::{ExtractedEntity, ExtractedRelationship, Modality};
+
+    #[test]
+    fn cleanup_joins_hyphenated_words_and_collapses_whitespace() {
+        let cleanup = TextCleanup {
+            dehyphenate: true,
+            remove: vec!["Page 1 of 9".to_string()],
+            ..TextCleanup::default()
+        };
+        let text = "Rapamycin  inhi-\nbits mTOR.   \n\n\n\nPage 1 of 9\nSee mTOR-\nAMPK crosstalk.";
+        assert_eq!(
+            cleanup.clean(text),
+            "Rapamycin inhibits mTOR.\n\nSee mTOR-\nAMPK crosstalk."
+        );
+    }
+
+    #[tokio::test]
+    async fn configured_hooks_filter_entities_and_enrich_records() {
+        let hooks: Vec<HookConfig> = serde_yaml::from_str(
+            "- kind: entity_filter\n  drop_types: [other]\n  min_name_length: 2\n- kind: enrich\n  fields: { source: curated }\n  records: entities\n",
+        )
+        .unwrap();
+        let hooks: Vec<_> = hooks.iter().map(HookConfig::build).collect();
+
+        let entity = |name: &str, entity_type: &str| ExtractedEntity {
+            entity_name: name.to_string(),
+            entity_type: entity_type.to_string(),
+            entity_description: String::new(),
+            evidence: String::new(),
+        };
+        let relationship = |source: &str, target: &str| ExtractedRelationship {
+            source_entity: source.to_string(),
+            target_entity: target.to_string(),
+            relationship_keywords: Vec::new(),
+            relationship_description: String::new(),
+            evidence: String::new(),
+            confidence: None,
+            modality: Modality::Asserted,
+            hedge_cues: Vec::new(),
+            predicate: None,
+        };
+        let mut extraction = EntitiesRelationships {
+            entities: vec![
+                entity("Rapamycin", "Compound"),
+                entity("mTOR", "Gene"),
+                entity("the study", "Other"),
+                entity("X", "Gene"),
+            ],
+            relationships: vec![
+                relationship("Rapamycin", "mTOR"),
+                relationship("The Study", "mTOR"),
+            ],
+        };
+        let mut records =
+            HashMap::from([("entity-1".to_string(), json!({ "entity_name": "mTOR" }))]);
+        let mut relations = HashMap::from([("rel-1".to_string(), json!({}))]);
+        for hook in &hooks {
+            extraction = hook
+                .after_extraction("doc-1", "chunk-1", extraction)
+                .await
+                .unwrap();
+            hook.before_upsert("doc-1", RecordKind::Entities, &mut records)
+                .await
+                .unwrap();
+            hook.before_upsert("doc-1", RecordKind::Relations, &mut relations)
+                .await
+                .unwrap();
+        }
+
+        let names: Vec<_> = extraction
+            .entities
+            .iter()
+            .map(|entity| entity.entity_name.as_str())
+            .collect();
+        assert_eq!(names, ["Rapamycin", "mTOR"]);
+        assert_eq!(extraction.relationships.len(), 1);
+        assert_eq!(records["entity-1"]["source"], "curated");
+        assert!(relations["rel-1"].get("source").is_none());
+    }
+}
@@ -1,19 +1,60 @@
+pub mod archive;
+pub mod canonical;
 pub mod chunker;
+pub mod csv_mapping;
+pub mod descriptions;
+pub mod doc_summary;
 pub mod document_manager;
+pub mod dry_run;
 pub mod error_reporter;
+pub mod evidence;
 pub mod extractor;
+pub mod hooks;
+pub mod near_duplicate;
+pub mod normalization;
+pub mod ontology;
 pub mod pipeline;
+pub mod relation_repair;
+pub mod resolution;
 pub mod scheduler;
 pub mod status_service;
 pub mod types;
+pub mod url_fetcher;
+pub mod usage;
+pub mod versioning;
+pub mod watcher;
 
 pub mod utils;
 
-pub use chunker::{Chunk, ChunkConfig, Chunker, TokenizerChunker};
+pub use chunker::{
+    Chunk, ChunkConfig, ChunkStrategy, Chunker, CsvChunker, JsonChunker, MarkdownChunker, Span,
+    TokenizerChunker,
+};
+pub use csv_mapping::CsvMapping;
+pub use descriptions::{
+    DescriptionConfig, DescriptionConsolidator, DescriptionSummarizer, LlmDescriptionSummarizer,
+};
+pub use doc_summary::{
+    DocumentSummaries, DocumentSummarizer, DocumentSummaryConfig, LlmDocumentSummarizer,
+};
 pub use document_manager::{
     DocumentManager, FileRepository, FsFileRepository, normalize_extension,
 };
+pub use dry_run::{CostEstimator, DryRunConfig, DryRunReport, FileEstimate};
 pub use error_reporter::ErrorReporter;
-pub use extractor::{DocumentExtractor, Utf8DocumentExtractor};
-pub use pipeline::{AppStorages, Pipeline, PipelineConfig};
+pub use evidence::Evidence;
+pub use extractor::{DocumentExtractor, ExtractionPass, GleaningConfig, Utf8DocumentExtractor};
+pub use hooks::{HookConfig, HooksConfig, PipelineHook, RecordKind};
+pub use near_duplicate::{NearDuplicateConfig, NearDuplicatePolicy};
+pub use normalization::{DictionaryEntry, EntityNormalizer, NormalizationConfig};
+pub use ontology::{Ontology, OntologyConfig};
+pub use pipeline::{
+    AppStorages, DocumentInput, EnqueueStatus, EnqueuedDocument, Pipeline, PipelineConfig,
+};
+pub use relation_repair::{RelationRepairConfig, RepairCounts};
+pub use resolution::{DecideError, EntityResolver, MergeRecord, MergeStatus, ResolutionConfig};
 pub use status_service::{DocStatusService, PendingDocument};
+pub use url_fetcher::{FetchedDocument, UrlFetchConfig, UrlFetcher};
+pub use usage::{PricingConfig, UsageKind, UsageLedger, UsageReport, UsageTotals};
+pub use versioning::ChunkDiff;
+pub use watcher::{InputWatcher, WatcherConfig};
@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+
+/// What to do with a document whose MinHash similarity to an existing one is
+/// above the threshold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NearDuplicatePolicy {
+    /// Do not store the document; the match is recorded on the existing one.
+    Skip,
+    /// Store the document as a version of the match, without extracting it.
+    Link,
+    /// Ingest as usual and only record the match.
+    #[default]
+    Ingest,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NearDuplicateConfig {
+    pub policy: NearDuplicatePolicy,
+    /// Estimated Jaccard similarity at or above which documents match.
+    pub threshold: f32,
+    /// Words per shingle.
+    pub shingle_size: usize,
+    pub num_hashes: usize,
+}
+
+impl Default for NearDuplicateConfig {
+    fn default() -> Self {
+        Self {
+            policy: NearDuplicatePolicy::Ingest,
+            threshold: 0.85,
+            shingle_size: 5,
+            num_hashes: 128,
+        }
+    }
+}
+
+/// MinHash signatures over word shingles. Hashing is seeded with fixed
+/// constants so signatures stay comparable across restarts.
+#[derive(Debug, Clone)]
+pub struct MinHasher {
+    shingle_size: usize,
+    seeds: Vec<(u64, u64)>,
+}
+
+impl MinHasher {
+    pub fn new(shingle_size: usize, num_hashes: usize) -> Self {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let seeds = (0..num_hashes)
+            .map(|_| (splitmix64(&mut state) | 1, splitmix64(&mut state)))
+            .collect();
+        Self {
+            shingle_size: shingle_size.max(1),
+            seeds,
+        }
+    }
+
+    pub fn from_config(config: &NearDuplicateConfig) -> Self {
+        Self::new(config.shingle_size, config.num_hashes)
+    }
+
+    /// Signature of `text` after lowercasing and dropping punctuation, so
+    /// line endings, spacing and casing don't affect the result.
+    pub fn signature(&self, text: &str) -> Vec<u64> {
+        let normalized = text.to_lowercase();
+        let words: Vec<&str> = normalized
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        let shingles: Vec<u64> = if words.len() <= self.shingle_size {
+            vec![fnv1a(words.join(" ").as_bytes())]
+        } else {
+            words
+                .windows(self.shingle_size)
+                .map(|window| fnv1a(window.join(" ").as_bytes()))
+                .collect()
+        };
+
+        self.seeds
+            .iter()
+            .map(|(a, b)| {
+                shingles
+                    .iter()
+                    .map(|shingle| shingle.wrapping_mul(*a).wrapping_add(*b))
+                    .min()
+                    .unwrap_or(u64::MAX)
+            })
+            .collect()
+    }
+}
+
+/// Estimated Jaccard similarity of two signatures of the same length.
+pub fn similarity(a: &[u64], b: &[u64]) -> f32 {
+    if a.is_empty() || a.len() != b.len() {
+        return 0.0;
+    }
+    let matching = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    matching as f32 / a.len() as f32
+}
+
+/// Locality-sensitive index over MinHash signatures. Signatures are cut into
+/// bands and only documents sharing a band with the query are compared, so
+/// a lookup costs about the number of likely matches rather than the number
+/// of indexed documents. Bands are sized so pairs at the threshold become
+/// candidates with high probability.
+#[derive(Debug, Default)]
+pub struct SignatureIndex {
+    rows: usize,
+    threshold: f32,
+    signatures: Vec<(String, Vec<u64>)>,
+    buckets: HashMap<(usize, u64), Vec<usize>>,
+}
+
+impl SignatureIndex {
+    pub fn new(config: &NearDuplicateConfig) -> Self {
+        Self {
+            rows: band_rows(config.num_hashes, config.threshold),
+            threshold: config.threshold,
+            ..Self::default()
+        }
+    }
+
+    pub fn insert(&mut self, doc_id: String, signature: Vec<u64>) {
+        let idx = self.signatures.len();
+        for key in self.band_keys(&signature) {
+            self.buckets.entry(key).or_default().push(idx);
+        }
+        self.signatures.push((doc_id, signature));
+    }
+
+    /// The most similar indexed document at or above the threshold.
+    pub fn best_match(&self, signature: &[u64]) -> Option<(String, f32)> {
+        let mut candidates = HashSet::new();
+        for key in self.band_keys(signature) {
+            if let Some(bucket) = self.buckets.get(&key) {
+                candidates.extend(bucket.iter().copied());
+            }
+        }
+        candidates
+            .into_iter()
+            .map(|idx| {
+                let (doc_id, other) = &self.signatures[idx];
+                (doc_id, similarity(signature, other))
+            })
+            .filter(|(_, score)| *score >= self.threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(doc_id, score)| (doc_id.clone(), score))
+    }
+
+    fn band_keys(&self, signature: &[u64]) -> Vec<(usize, u64)> {
+        signature
+            .chunks_exact(self.rows.max(1))
+            .enumerate()
+            .map(|(band, rows)| {
+                let bytes: Vec<u8> = rows.iter().flat_map(|row| row.to_le_bytes()).collect();
+                (band, fnv1a(&bytes))
+            })
+            .collect()
+    }
+}
+
+/// Rows per band: the most that keep the band collision threshold,
+/// `(1 / bands) ^ (1 / rows)`, well below the similarity threshold.
+fn band_rows(num_hashes: usize, threshold: f32) -> usize {
+    let target = (threshold - 0.15).max(0.0) as f64;
+    (1..=num_hashes)
+        .filter(|rows| {
+            let bands = (num_hashes / rows) as f64;
+            (1.0 / bands).powf(1.0 / *rows as f64) <= target
+        })
+        .max()
+        .unwrap_or(1)
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABSTRACT: &str = "Rapamycin extends lifespan in genetically heterogeneous mice. \
+        Treatment started late in life increased median and maximal lifespan in both sexes, \
+        suggesting that mTOR signalling regulates ageing in mammals. We discuss implications \
+        for interventions that target nutrient sensing pathways and for future human trials.";
+
+    #[test]
+    fn formatting_changes_keep_documents_similar() {
+        let hasher = MinHasher::new(5, 128);
+        let original = hasher.signature(ABSTRACT);
+        let reformatted = hasher.signature(&format!(
+            "PREPRINT\r\n\r\n{}",
+            ABSTRACT.to_uppercase().replace(". ", ".\r\n")
+        ));
+        let unrelated = hasher.signature(
+            "Yeast replicative lifespan is shortened by loss of Sir2, which silences rDNA \
+             repeats and limits the accumulation of extrachromosomal circles in mother cells.",
+        );
+
+        assert!(similarity(&original, &reformatted) > 0.85);
+        assert!(similarity(&original, &unrelated) < 0.2);
+    }
+
+    #[test]
+    fn index_finds_similar_documents_through_shared_bands() {
+        let config = NearDuplicateConfig::default();
+        let hasher = MinHasher::from_config(&config);
+        let mut index = SignatureIndex::new(&config);
+        assert_eq!(band_rows(128, 0.85), 7);
+
+        index.insert("doc-original".to_string(), hasher.signature(ABSTRACT));
+        for idx in 0..50 {
+            index.insert(
+                format!("doc-{idx}"),
+                hasher.signature(&format!(
+                    "Unrelated abstract number {idx} on yeast, worms and flies."
+                )),
+            );
+        }
+
+        let (doc_id, score) = index
+            .best_match(&hasher.signature(&format!("{ABSTRACT} Preprint.")))
+            .unwrap();
+        assert_eq!(doc_id, "doc-original");
+        assert!(score >= config.threshold);
+        assert!(
+            index
+                .best_match(&hasher.signature("Metformin activates AMPK in the liver."))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn signatures_are_stable() {
+        let a = MinHasher::new(5, 16).signature(ABSTRACT);
+        let b = MinHasher::new(5, 16).signature(ABSTRACT);
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,315 @@
+use std::{collections::HashMap, fmt};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use ts_rs::TS;
+
+use super::canonical::normalize_entity_name;
+
+/// Field of an entity record the matched dictionary entry is stored under.
+pub const NORMALIZATION_KEY: &str = "normalization";
+
+/// Local dictionaries entity names are normalized against, per entity type.
+///
+/// ```yaml
+/// normalization:
+///   dictionaries:
+///     - entity_type: Gene
+///       source: HGNC
+///       path: data/dictionaries/hgnc_complete_set.tsv
+///       id_column: hgnc_id
+///       name_column: symbol
+///       synonym_columns: [name, alias_symbol, prev_symbol]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NormalizationConfig {
+    pub dictionaries: Vec<DictionaryConfig>,
+}
+
+/// A tab-separated dictionary file with a header row.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DictionaryConfig {
+    pub entity_type: String,
+    /// Name of the vocabulary, e.g. `HGNC`, `MeSH`, `UniProt` or `ChEBI`.
+    pub source: String,
+    pub path: String,
+    #[serde(default = "default_id_column")]
+    pub id_column: String,
+    #[serde(default = "default_name_column")]
+    pub name_column: String,
+    #[serde(default)]
+    pub synonym_columns: Vec<String>,
+    /// Separates several synonyms within one cell.
+    #[serde(default = "default_separator")]
+    pub separator: String,
+    /// Prepended to ids that don't carry it yet, e.g. `MESH:` for `D009369`.
+    #[serde(default)]
+    pub id_prefix: Option<String>,
+}
+
+fn default_id_column() -> String {
+    "id".to_string()
+}
+
+fn default_name_column() -> String {
+    "name".to_string()
+}
+
+fn default_separator() -> String {
+    "|".to_string()
+}
+
+/// The dictionary entry an entity name was matched to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct DictionaryEntry {
+    /// Canonical identifier, e.g. `HGNC:3942`.
+    pub id: String,
+    pub source: String,
+    /// Preferred name, e.g. `MTOR`.
+    pub name: String,
+    pub synonyms: Vec<String>,
+}
+
+impl DictionaryEntry {
+    /// Whether `query` occurs in the preferred name or any synonym,
+    /// ignoring case.
+    pub fn mentions(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        std::iter::once(&self.name)
+            .chain(&self.synonyms)
+            .any(|name| name.to_lowercase().contains(&query))
+    }
+}
+
+struct Dictionary {
+    entries: Vec<DictionaryEntry>,
+    /// Normalized names and synonyms to entries. Preferred names win over
+    /// synonyms, and synonyms shared by several entries are left out.
+    index: HashMap<String, usize>,
+}
+
+impl Dictionary {
+    fn load(config: &DictionaryConfig) -> Result<Self> {
+        let contents = std::fs::read_to_string(&config.path)
+            .with_context(|| format!("failed to read dictionary {}", config.path))?;
+        Self::parse(config, &contents)
+            .with_context(|| format!("invalid dictionary {}", config.path))
+    }
+
+    fn parse(config: &DictionaryConfig, contents: &str) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(b'\t')
+            .flexible(true)
+            .quoting(false)
+            .from_reader(contents.as_bytes());
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header.trim() == name)
+                .ok_or_else(|| anyhow!("missing column '{name}'"))
+        };
+        let id_column = column(&config.id_column)?;
+        let name_column = column(&config.name_column)?;
+        let synonym_columns = config
+            .synonym_columns
+            .iter()
+            .map(|name| column(name))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut entries = Vec::new();
+        for row in reader.records() {
+            let row = row?;
+            let cell = |idx: usize| row.get(idx).unwrap_or_default().trim();
+            let (id, name) = (cell(id_column), cell(name_column));
+            if id.is_empty() || name.is_empty() {
+                continue;
+            }
+            let id = match &config.id_prefix {
+                Some(prefix) if !id.starts_with(prefix.as_str()) => format!("{prefix}{id}"),
+                _ => id.to_string(),
+            };
+            let mut synonyms: Vec<String> = Vec::new();
+            for value in synonym_columns.iter().flat_map(|&idx| {
+                cell(idx)
+                    .split(config.separator.as_str())
+                    .map(|value| value.trim().trim_matches('"'))
+            }) {
+                if !value.is_empty() && value != name && !synonyms.iter().any(|s| s == value) {
+                    synonyms.push(value.to_string());
+                }
+            }
+            entries.push(DictionaryEntry {
+                id,
+                source: config.source.clone(),
+                name: name.to_string(),
+                synonyms,
+            });
+        }
+
+        let mut index = HashMap::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            index
+                .entry(normalize_entity_name(&entry.name))
+                .or_insert(idx);
+            index.entry(normalize_entity_name(&entry.id)).or_insert(idx);
+        }
+        let mut synonyms: HashMap<String, Option<usize>> = HashMap::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            for synonym in &entry.synonyms {
+                synonyms
+                    .entry(normalize_entity_name(synonym))
+                    .and_modify(|owner| {
+                        if *owner != Some(idx) {
+                            *owner = None;
+                        }
+                    })
+                    .or_insert(Some(idx));
+            }
+        }
+        for (key, owner) in synonyms {
+            if let Some(idx) = owner {
+                index.entry(key).or_insert(idx);
+            }
+        }
+        Ok(Self { entries, index })
+    }
+
+    fn lookup(&self, name: &str) -> Option<&DictionaryEntry> {
+        self.index
+            .get(&normalize_entity_name(name))
+            .map(|&idx| &self.entries[idx])
+    }
+}
+
+/// Matches entity names to dictionary entries of their type, so surface
+/// forms such as "mTOR", "MTOR" and "mechanistic target of rapamycin" share
+/// one identifier. Types without a dictionary are left as they are.
+#[derive(Default)]
+pub struct EntityNormalizer {
+    /// By lowercased entity type, searched in configuration order.
+    dictionaries: HashMap<String, Vec<Dictionary>>,
+}
+
+impl fmt::Debug for EntityNormalizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: HashMap<&str, usize> = self
+            .dictionaries
+            .iter()
+            .map(|(entity_type, dictionaries)| {
+                (
+                    entity_type.as_str(),
+                    dictionaries.iter().map(|d| d.entries.len()).sum(),
+                )
+            })
+            .collect();
+        f.debug_struct("EntityNormalizer")
+            .field("entries", &entries)
+            .finish()
+    }
+}
+
+impl EntityNormalizer {
+    pub fn load(config: &NormalizationConfig) -> Result<Self> {
+        let mut normalizer = Self::default();
+        for dictionary in &config.dictionaries {
+            normalizer.add(dictionary, Dictionary::load(dictionary)?);
+        }
+        Ok(normalizer)
+    }
+
+    fn add(&mut self, config: &DictionaryConfig, dictionary: Dictionary) {
+        self.dictionaries
+            .entry(config.entity_type.trim().to_lowercase())
+            .or_default()
+            .push(dictionary);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dictionaries.is_empty()
+    }
+
+    pub fn lookup(&self, name: &str, entity_type: &str) -> Option<&DictionaryEntry> {
+        self.dictionaries
+            .get(&entity_type.trim().to_lowercase())?
+            .iter()
+            .find_map(|dictionary| dictionary.lookup(name))
+    }
+
+    /// Attaches the matching dictionary entry to an entity record. Returns
+    /// whether one was found.
+    pub fn normalize(&self, record: &mut Value) -> bool {
+        let name = record
+            .get("entity_name")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let entity_type = record
+            .get("entity_type")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        match self.lookup(name, entity_type) {
+            Some(entry) => {
+                record[NORMALIZATION_KEY] = json!(entry);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The dictionary entry stored on an entity record, if any.
+pub fn record_normalization(record: &Value) -> Option<DictionaryEntry> {
+    serde_json::from_value(record.get(NORMALIZATION_KEY)?.clone()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HGNC: &str = "hgnc_id\tsymbol\tname\talias_symbol\tprev_symbol\n\
+        HGNC:3942\tMTOR\tmechanistic target of rapamycin kinase\tRAFT1|RAPT1|FRAP|FLJ44809\tFRAP1|FRAP2\n\
+        HGNC:3943\tFRAP9\tan unrelated gene\tFRAP\t\n\
+        HGNC:583\tAMPK\tprotein kinase AMP-activated\t\t\n";
+
+    #[test]
+    fn surface_forms_resolve_to_one_identifier_and_ambiguous_synonyms_to_none() {
+        let config = DictionaryConfig {
+            entity_type: "Gene".to_string(),
+            source: "HGNC".to_string(),
+            path: String::new(),
+            id_column: "hgnc_id".to_string(),
+            name_column: "symbol".to_string(),
+            synonym_columns: vec![
+                "name".to_string(),
+                "alias_symbol".to_string(),
+                "prev_symbol".to_string(),
+            ],
+            separator: "|".to_string(),
+            id_prefix: None,
+        };
+        let mut normalizer = EntityNormalizer::default();
+        normalizer.add(&config, Dictionary::parse(&config, HGNC).unwrap());
+
+        for name in [
+            "mTOR",
+            "MTOR",
+            "Mechanistic target of rapamycin kinase",
+            "FRAP1",
+        ] {
+            assert_eq!(normalizer.lookup(name, "gene").unwrap().id, "HGNC:3942");
+        }
+        assert!(normalizer.lookup("FRAP", "Gene").is_none());
+        assert!(normalizer.lookup("mTOR", "Compound").is_none());
+
+        let mut record = json!({ "entity_name": "mtor", "entity_type": "Gene" });
+        assert!(normalizer.normalize(&mut record));
+        let entry = record_normalization(&record).unwrap();
+        assert_eq!(entry.name, "MTOR");
+        assert!(entry.mentions("raft1"));
+        assert!(entry.synonyms.contains(&"FRAP2".to_string()));
+    }
+}
@@ -0,0 +1,472 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Type given to entities that fit none of the ontology's types. Every
+/// ontology has it, so the model always has somewhere to put them.
+pub const OTHER_ENTITY_TYPE: &str = "Other";
+
+const OTHER_COLOR: &str = "#7f7f7f";
+
+/// Ontology used when no file is configured.
+const BUILTIN_ONTOLOGY: &str = include_str!("../../config/ontologies/biomedical.yaml");
+
+/// How many ontologies may be chained through `extends`.
+const MAX_EXTENDS_DEPTH: usize = 8;
+
+/// Which ontology file each workspace extracts entities with.
+///
+/// ```yaml
+/// ontology:
+///   path: config/ontologies/biomedical.yaml
+///   workspaces:
+///     aging: config/ontologies/longevity.yaml
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OntologyConfig {
+    /// YAML file with the entity types. The built-in biomedical ontology is
+    /// used when unset.
+    pub path: Option<String>,
+    /// Ontology files for individual workspaces, used instead of `path`.
+    pub workspaces: HashMap<String, String>,
+}
+
+impl OntologyConfig {
+    pub fn load(&self, workspace: Option<&str>) -> Result<Ontology> {
+        let path = workspace
+            .and_then(|workspace| self.workspaces.get(workspace))
+            .or(self.path.as_ref());
+        match path {
+            Some(path) => Ontology::load(Path::new(path)),
+            None => Ok(Ontology::builtin()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct EntityTypeDefinition {
+    pub name: String,
+    /// Shown to the extraction model next to the type name.
+    #[serde(default)]
+    pub description: String,
+    /// Color the graph view draws entities of this type in, e.g. `#1f77b4`.
+    #[serde(default)]
+    #[ts(optional)]
+    pub color: Option<String>,
+    /// The type this one specializes, e.g. `Organism` for `LifespanModel`.
+    #[serde(default)]
+    #[ts(optional)]
+    pub parent: Option<String>,
+}
+
+/// A relationship predicate such as `inhibits`. `domain` and `range` list
+/// the entity types its source and target may have, subtypes included; an
+/// empty list allows any type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PredicateDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub domain: Vec<String>,
+    #[serde(default)]
+    pub range: Vec<String>,
+}
+
+/// The entity types extraction may assign, and optionally the predicates
+/// relations may be typed with, loaded from YAML.
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export)]
+pub struct Ontology {
+    pub name: String,
+    pub entity_types: Vec<EntityTypeDefinition>,
+    pub predicates: Vec<PredicateDefinition>,
+}
+
+/// An ontology as written in its file. `extends` names another ontology
+/// file, relative to this one, whose types come first.
+#[derive(Debug, Deserialize)]
+struct OntologyFile {
+    name: String,
+    #[serde(default)]
+    extends: Option<String>,
+    #[serde(default)]
+    entity_types: Vec<EntityTypeDefinition>,
+    #[serde(default)]
+    predicates: Vec<PredicateDefinition>,
+}
+
+impl Default for Ontology {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl Ontology {
+    /// The biomedical ontology shipped in `config/ontologies`.
+    pub fn builtin() -> Self {
+        Self::from_yaml(BUILTIN_ONTOLOGY, None).expect("built-in ontology is valid")
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read ontology at {}", path.display()))?;
+        Self::from_yaml(&contents, path.parent())
+            .with_context(|| format!("invalid ontology at {}", path.display()))
+    }
+
+    /// Parses an ontology. `extends` paths are resolved against `base_dir`.
+    pub fn from_yaml(contents: &str, base_dir: Option<&Path>) -> Result<Self> {
+        let mut chain = Vec::new();
+        let mut ontology = Self::parse(contents, base_dir, &mut chain)?;
+        if ontology.get(OTHER_ENTITY_TYPE).is_none() {
+            ontology.entity_types.push(EntityTypeDefinition {
+                name: OTHER_ENTITY_TYPE.to_string(),
+                description: "Anything that fits none of the other types.".to_string(),
+                color: Some(OTHER_COLOR.to_string()),
+                parent: None,
+            });
+        }
+        ontology.validate()?;
+        Ok(ontology)
+    }
+
+    fn parse(contents: &str, base_dir: Option<&Path>, chain: &mut Vec<PathBuf>) -> Result<Self> {
+        let file: OntologyFile =
+            serde_yaml::from_str(contents).context("failed to parse ontology YAML")?;
+        let mut entity_types = Vec::new();
+        let mut predicates = Vec::new();
+        if let Some(extends) = &file.extends {
+            let base_dir = base_dir.ok_or_else(|| {
+                anyhow!(
+                    "ontology '{}' extends '{extends}' but has no file",
+                    file.name
+                )
+            })?;
+            let path = base_dir.join(extends);
+            if chain.contains(&path) || chain.len() >= MAX_EXTENDS_DEPTH {
+                return Err(anyhow!("ontology '{}' extends itself", file.name));
+            }
+            chain.push(path.clone());
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read ontology at {}", path.display()))?;
+            let base = Self::parse(&contents, path.parent(), chain)?;
+            entity_types = base.entity_types;
+            predicates = base.predicates;
+        }
+        entity_types.extend(file.entity_types);
+        predicates.extend(file.predicates);
+        Ok(Self {
+            name: file.name,
+            entity_types,
+            predicates,
+        })
+    }
+
+    /// Checks names are unique, every parent exists without forming a cycle
+    /// and predicates only constrain endpoints to defined types.
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+        let mut seen = HashSet::new();
+        for entity_type in &self.entity_types {
+            let name = entity_type.name.trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                errors.push(format!(
+                    "entity type '{}' must be a single word",
+                    entity_type.name
+                ));
+            }
+            if !seen.insert(name.to_lowercase()) {
+                errors.push(format!("entity type '{name}' is defined twice"));
+            }
+            if let Some(color) = &entity_type.color
+                && !is_hex_color(color)
+            {
+                errors.push(format!(
+                    "entity type '{name}' has color '{color}', expected #rrggbb"
+                ));
+            }
+            match &entity_type.parent {
+                Some(parent) if self.get(parent).is_none() => errors.push(format!(
+                    "entity type '{name}' has unknown parent '{parent}'"
+                )),
+                Some(_) if self.has_parent_cycle(entity_type) => {
+                    errors.push(format!("entity type '{name}' is its own ancestor"))
+                }
+                _ => {}
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for predicate in &self.predicates {
+            let name = predicate.name.trim();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                errors.push(format!(
+                    "predicate '{}' must be a single word",
+                    predicate.name
+                ));
+            }
+            if !seen.insert(lookup_key(name)) {
+                errors.push(format!("predicate '{name}' is defined twice"));
+            }
+            for entity_type in predicate.domain.iter().chain(&predicate.range) {
+                if self.get(entity_type).is_none() {
+                    errors.push(format!(
+                        "predicate '{name}' refers to unknown entity type '{entity_type}'"
+                    ));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "ontology '{}':\n  - {}",
+                self.name,
+                errors.join("\n  - ")
+            ))
+        }
+    }
+
+    fn has_parent_cycle(&self, entity_type: &EntityTypeDefinition) -> bool {
+        let mut current = entity_type.parent.as_deref();
+        for _ in 0..self.entity_types.len() {
+            match current.and_then(|name| self.get(name)) {
+                Some(parent) if parent.name == entity_type.name => return true,
+                Some(parent) => current = parent.parent.as_deref(),
+                None => return false,
+            }
+        }
+        current.is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EntityTypeDefinition> {
+        self.entity_types
+            .iter()
+            .find(|entity_type| entity_type.name == name)
+    }
+
+    pub fn type_names(&self) -> Vec<&str> {
+        self.entity_types
+            .iter()
+            .map(|entity_type| entity_type.name.as_str())
+            .collect()
+    }
+
+    /// The ontology's spelling of an extracted type. Case, spaces and
+    /// underscores are ignored; anything else unknown becomes `Other`.
+    pub fn resolve_type(&self, name: &str) -> &str {
+        if let Some(entity_type) = self.get(name) {
+            return &entity_type.name;
+        }
+        let key = lookup_key(name);
+        self.entity_types
+            .iter()
+            .find(|entity_type| lookup_key(&entity_type.name) == key)
+            .map(|entity_type| entity_type.name.as_str())
+            .unwrap_or(OTHER_ENTITY_TYPE)
+    }
+
+    /// One line per type for the extraction prompt, e.g.
+    /// `- LifespanModel (a kind of Organism): A model organism ...`.
+    pub fn type_guide(&self) -> String {
+        self.entity_types
+            .iter()
+            .map(|entity_type| {
+                let mut line = format!("- {}", entity_type.name);
+                if let Some(parent) = &entity_type.parent {
+                    line.push_str(&format!(" (a kind of {parent})"));
+                }
+                if !entity_type.description.is_empty() {
+                    line.push_str(&format!(": {}", entity_type.description));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Whether `entity_type` is `ancestor` or one of its subtypes.
+    pub fn is_a(&self, entity_type: &str, ancestor: &str) -> bool {
+        let mut current = Some(entity_type);
+        for _ in 0..=self.entity_types.len() {
+            match current {
+                Some(name) if name == ancestor => return true,
+                Some(name) => current = self.get(name).and_then(|t| t.parent.as_deref()),
+                None => return false,
+            }
+        }
+        false
+    }
+
+    pub fn predicate_names(&self) -> Vec<&str> {
+        self.predicates
+            .iter()
+            .map(|predicate| predicate.name.as_str())
+            .collect()
+    }
+
+    /// Checks an extracted predicate against the vocabulary and the types of
+    /// the relation's endpoints, returning the ontology's spelling of it.
+    /// Endpoints of no known type, or of a type outside the ontology, are
+    /// not constrained.
+    pub fn resolve_predicate(
+        &self,
+        predicate: &str,
+        source_type: Option<&str>,
+        target_type: Option<&str>,
+    ) -> Result<&str> {
+        let key = lookup_key(predicate);
+        let definition = self
+            .predicates
+            .iter()
+            .find(|definition| lookup_key(&definition.name) == key)
+            .ok_or_else(|| anyhow!("unknown predicate '{predicate}'"))?;
+        let allows = |allowed: &[String], entity_type: Option<&str>| match entity_type {
+            Some(entity_type) if self.get(entity_type).is_some() && !allowed.is_empty() => allowed
+                .iter()
+                .any(|allowed| self.is_a(entity_type, allowed)),
+            _ => true,
+        };
+        if !allows(&definition.domain, source_type) {
+            return Err(anyhow!(
+                "'{}' does not take a {} source",
+                definition.name,
+                source_type.unwrap_or_default()
+            ));
+        }
+        if !allows(&definition.range, target_type) {
+            return Err(anyhow!(
+                "'{}' does not take a {} target",
+                definition.name,
+                target_type.unwrap_or_default()
+            ));
+        }
+        Ok(&definition.name)
+    }
+
+    /// One line per predicate for the extraction prompt, e.g.
+    /// `- treats (Compound, Intervention -> Disease, Symptom): ...`.
+    pub fn predicate_guide(&self) -> String {
+        let types = |types: &[String]| match types {
+            [] => "any".to_string(),
+            types => types.join(", "),
+        };
+        self.predicates
+            .iter()
+            .map(|predicate| {
+                let mut line = format!(
+                    "- {} ({} -> {})",
+                    predicate.name,
+                    types(&predicate.domain),
+                    types(&predicate.range)
+                );
+                if !predicate.description.is_empty() {
+                    line.push_str(&format!(": {}", predicate.description));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn lookup_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color
+        .strip_prefix('#')
+        .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ontologies_extend_others_and_resolve_extracted_types() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("biomedical.yaml"), BUILTIN_ONTOLOGY).unwrap();
+        let path = dir.path().join("longevity.yaml");
+        std::fs::write(
+            &path,
+            include_str!("../../config/ontologies/longevity.yaml"),
+        )
+        .unwrap();
+
+        let longevity = Ontology::load(&path).unwrap();
+        let names = longevity.type_names();
+        assert_eq!(names.first(), Some(&"Gene"));
+        assert!(names.contains(&"LifespanModel"));
+        assert_eq!(names.last(), Some(&OTHER_ENTITY_TYPE));
+
+        assert_eq!(longevity.resolve_type("aging hallmark"), "AgingHallmark");
+        assert_eq!(longevity.resolve_type("Cell_Type"), "CellType");
+        assert_eq!(longevity.resolve_type("Vehicle"), OTHER_ENTITY_TYPE);
+        assert!(
+            longevity
+                .type_guide()
+                .contains("- LifespanModel (a kind of Organism): ")
+        );
+        assert!(Ontology::builtin().get("AgingHallmark").is_none());
+
+        // subtypes satisfy domain and range, unknown types are unconstrained
+        assert_eq!(
+            longevity
+                .resolve_predicate("Biomarker Of", Some("Protein"), Some("AgingHallmark"))
+                .unwrap(),
+            "biomarker_of"
+        );
+        assert!(
+            longevity
+                .resolve_predicate("treats", Some("Compound"), Some("Unknown"))
+                .is_ok()
+        );
+        assert!(
+            longevity
+                .resolve_predicate("treats", Some("Gene"), Some("Disease"))
+                .is_err()
+        );
+        assert!(longevity.resolve_predicate("cures", None, None).is_err());
+    }
+
+    #[test]
+    fn invalid_ontologies_are_rejected() {
+        let err = Ontology::from_yaml(
+            r#"
+name: broken
+entity_types:
+  - name: Vehicle
+    parent: Car
+  - name: Car
+    parent: Vehicle
+  - name: Boat
+    parent: Ship
+    color: blue
+  - name: car
+"#,
+            None,
+        )
+        .unwrap_err();
+        let message = format!("{err:#}");
+
+        assert!(message.contains("'Vehicle' is its own ancestor"));
+        assert!(message.contains("'Boat' has unknown parent 'Ship'"));
+        assert!(message.contains("color 'blue'"));
+        assert!(message.contains("'car' is defined twice"));
+    }
+}
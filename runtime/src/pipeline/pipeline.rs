@@ -1,31 +1,60 @@
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
 use anyhow::{Result, anyhow};
-use futures::stream::{self, StreamExt, TryStreamExt};
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
 use serde_json::{Value, json};
 use tokio::sync::Mutex;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use crate::{
-    ai::{responses::ResponsesClient, schemas::EntitiesRelationships},
+    ai::{
+        embeddings::{DEFAULT_EMBEDDING_MODEL, OpenAiEmbedder},
+        responses::{ModelCall, ResponsesClient},
+        schemas::EntitiesRelationships,
+    },
     storage::{
-        DocProcessingStatus, DocStatus, DocStatusStorage, JsonKvStorage, KvStorage, StorageResult,
+        DocProcessingStatus, DocStatus, DocStatusStorage, DocumentMetadata, JsonKvStorage,
+        KvStorage, StorageResult,
     },
 };
 
 use super::{
-    chunker::{ChunkConfig, Chunker},
-    document_manager::DocumentManager,
+    canonical::{Mention, canonical_entity_id, link_mentions, unlink_mentions},
+    chunker::{Chunk, ChunkConfig, ChunkStrategy, Chunker, Span, format_chunkers, locate_chunks},
+    csv_mapping::{CsvMapping, find_mapping},
+    descriptions::{
+        DescriptionConfig, DescriptionConsolidator, DescriptionSummarizer,
+        LlmDescriptionSummarizer, apply_consolidated, merge_descriptions, push_description,
+    },
+    doc_summary::{
+        DocumentSummaries, DocumentSummarizer, DocumentSummaryConfig, LlmDocumentSummarizer,
+        clear_summary,
+    },
+    document_manager::{DocumentManager, normalize_extension},
+    dry_run::DryRunConfig,
     error_reporter::ErrorReporter,
-    extractor::{DocumentExtractor, EntityRelationshipExtract, EntityRelationshipExtractor},
+    evidence::{Evidence, merge_evidence, push_evidence},
+    extractor::{
+        ChunkExtraction, DocumentExtractor, EntityRelationshipExtract, EntityRelationshipExtractor,
+        GleaningConfig,
+    },
+    hooks::{HookConfig, PipelineHook, RecordKind},
+    near_duplicate::{MinHasher, NearDuplicateConfig, NearDuplicatePolicy, SignatureIndex},
+    normalization::{EntityNormalizer, record_normalization},
+    ontology::{OTHER_ENTITY_TYPE, Ontology},
+    relation_repair::{EndpointResolver, Placeholder, RelationRepairConfig, RepairCounts},
+    resolution::{EntityResolver, ResolutionConfig},
     scheduler::{Job, Scheduler},
     status_service::{DocStatusService, PendingDocument},
+    usage::{PricingConfig, UsageKind, UsageLedger, UsageTotals},
     utils::{TiktokenTokenizer, Tokenizer, compute_mdhash_id},
+    versioning::{ChunkDiff, push_version, retire_sources, source_chunk_ids, version_count},
 };
 
 #[derive(Clone)]
@@ -35,6 +64,17 @@ pub struct AppStorages {
     pub full_entities: Arc<JsonKvStorage>,
     pub full_relations: Arc<JsonKvStorage>,
     pub llm_response_cache: Arc<JsonKvStorage>,
+    /// One record per LLM call with its tokens and cost.
+    pub llm_usage: Arc<JsonKvStorage>,
+    /// MinHash signatures used for near-duplicate detection.
+    pub doc_fingerprints: Arc<JsonKvStorage>,
+    /// Embeddings of canonical entities, used by entity resolution.
+    pub entity_embeddings: Arc<JsonKvStorage>,
+    /// Merge candidates and decisions made by entity resolution.
+    pub entity_merges: Arc<JsonKvStorage>,
+    /// One record per entity across documents, keyed by normalized name and
+    /// type, listing the per-document mentions in `full_entities`.
+    pub canonical_entities: Arc<JsonKvStorage>,
     pub doc_status: Arc<dyn DocStatusStorage>,
 }
 
@@ -50,6 +90,26 @@ pub struct PipelineConfig {
     pub chunk_overlap: usize,
     pub split_by_character: Option<String>,
     pub split_by_character_only: bool,
+    pub chunk_strategy: ChunkStrategy,
+    pub semantic_threshold: f32,
+    pub embedding_model: String,
+    pub llm_model: String,
+    pub max_concurrent_chunks: usize,
+    pub csv_mappings: Vec<CsvMapping>,
+    pub near_duplicate: NearDuplicateConfig,
+    pub resolution: ResolutionConfig,
+    pub descriptions: DescriptionConfig,
+    pub relation_repair: RelationRepairConfig,
+    /// Entity types extraction may assign; others are stored as `Other`.
+    pub ontology: Arc<Ontology>,
+    pub gleaning: GleaningConfig,
+    pub pricing: PricingConfig,
+    pub dry_run: DryRunConfig,
+    /// Hooks of the configured workspace, run in order.
+    pub hooks: Vec<HookConfig>,
+    pub document_summary: DocumentSummaryConfig,
+    /// Dictionaries entity names are normalized against, by entity type.
+    pub normalizer: Arc<EntityNormalizer>,
 }
 
 impl Default for PipelineConfig {
@@ -59,6 +119,36 @@ impl Default for PipelineConfig {
             chunk_overlap: 50,
             split_by_character: None,
             split_by_character_only: false,
+            chunk_strategy: ChunkStrategy::Token,
+            semantic_threshold: 0.5,
+            embedding_model: DEFAULT_EMBEDDING_MODEL.to_string(),
+            llm_model: "gpt-5-mini".to_string(),
+            max_concurrent_chunks: 10,
+            csv_mappings: Vec::new(),
+            near_duplicate: NearDuplicateConfig::default(),
+            resolution: ResolutionConfig::default(),
+            descriptions: DescriptionConfig::default(),
+            relation_repair: RelationRepairConfig::default(),
+            ontology: Arc::new(Ontology::builtin()),
+            gleaning: GleaningConfig::default(),
+            pricing: PricingConfig::default(),
+            dry_run: DryRunConfig::default(),
+            hooks: Vec::new(),
+            document_summary: DocumentSummaryConfig::default(),
+            normalizer: Arc::new(EntityNormalizer::default()),
+        }
+    }
+}
+
+impl PipelineConfig {
+    pub fn chunk_config(&self) -> ChunkConfig {
+        ChunkConfig {
+            max_tokens: self.chunk_size,
+            overlap_tokens: self.chunk_overlap,
+            split_by_character: self.split_by_character.clone(),
+            split_by_character_only: self.split_by_character_only,
+            strategy: self.chunk_strategy,
+            semantic_threshold: self.semantic_threshold,
         }
     }
 }
@@ -67,11 +157,20 @@ pub struct Pipeline {
     pub storages: Arc<AppStorages>,
     pub doc_manager: DocumentManager,
     pub chunker: Arc<dyn Chunker>,
+    pub format_chunkers: HashMap<String, Arc<dyn Chunker>>,
     pub extractor: Arc<dyn DocumentExtractor>,
     pub entity_relationship_extractor: Arc<dyn EntityRelationshipExtractor>,
     pub status_service: DocStatusService,
     pub error_reporter: ErrorReporter,
     pub processing_lock: Arc<Mutex<()>>,
+    pub entity_resolver: Option<Arc<EntityResolver>>,
+    pub descriptions: DescriptionConsolidator,
+    pub summaries: DocumentSummaries,
+    /// Held while a document is summarized, so callers asking for the same
+    /// document at once wait for one summary instead of paying for several.
+    summary_locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    pub usage: UsageLedger,
+    pub hooks: Vec<Arc<dyn PipelineHook>>,
     pub config: PipelineConfig,
 }
 
@@ -80,28 +179,62 @@ impl Pipeline {
         storages: Arc<AppStorages>,
         doc_manager: DocumentManager,
         ai_client: Arc<ResponsesClient>,
+        config: PipelineConfig,
     ) -> Self {
         let tokenizer: Arc<dyn Tokenizer> =
             Arc::new(TiktokenTokenizer::new().expect("failed to initialize tokenizer"));
-        let chunker = Arc::new(super::chunker::TokenizerChunker::new(tokenizer.clone()));
+        let embedder = Arc::new(OpenAiEmbedder::new(
+            ai_client.clone(),
+            config.embedding_model.clone(),
+        ));
+        let chunker = Arc::new(
+            super::chunker::TokenizerChunker::new(tokenizer.clone())
+                .with_embedder(embedder.clone()),
+        );
+        let entity_resolver = config.resolution.enabled.then(|| {
+            Arc::new(EntityResolver::new(
+                storages.clone(),
+                embedder,
+                config.resolution.clone(),
+            ))
+        });
         let extractor = Arc::new(super::extractor::Utf8DocumentExtractor::new(
             doc_manager.file_repo(),
         ));
-        let entity_relationship_extractor = Arc::new(EntityRelationshipExtract::new(ai_client));
+        let description_summarizer = Arc::new(LlmDescriptionSummarizer::new(
+            ai_client.clone(),
+            config.llm_model.clone(),
+        ));
+        let document_summarizer = Arc::new(LlmDocumentSummarizer::new(
+            ai_client.clone(),
+            config.llm_model.clone(),
+        ));
+        let entity_relationship_extractor = Arc::new(
+            EntityRelationshipExtract::new(ai_client, config.llm_model.clone())
+                .with_ontology(config.ontology.clone())
+                .with_gleaning(config.gleaning.clone()),
+        );
         let status_service =
             DocStatusService::new(storages.doc_status.clone(), storages.docs_storage());
         let error_reporter = ErrorReporter::new(storages.doc_status.clone());
 
-        Self::with_dependencies(
+        let mut pipeline = Self::with_dependencies(
             storages,
             doc_manager,
-            PipelineConfig::default(),
+            config,
             chunker,
             extractor,
             entity_relationship_extractor,
             status_service,
             error_reporter,
         )
+        .with_entity_resolver(entity_resolver)
+        .with_description_summarizer(description_summarizer)
+        .with_document_summarizer(document_summarizer);
+        for (extension, chunker) in format_chunkers(tokenizer) {
+            pipeline = pipeline.with_format_chunker(&extension, chunker);
+        }
+        pipeline
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -115,53 +248,297 @@ impl Pipeline {
         status_service: DocStatusService,
         error_reporter: ErrorReporter,
     ) -> Self {
+        let descriptions = DescriptionConsolidator::new(
+            Arc::new(TiktokenTokenizer::new().expect("failed to initialize tokenizer")),
+            config.descriptions.clone(),
+        );
+        let summaries = DocumentSummaries::new(
+            Arc::new(TiktokenTokenizer::new().expect("failed to initialize tokenizer")),
+            config.document_summary.clone(),
+        );
+        let usage = UsageLedger::new(storages.llm_usage.clone(), config.pricing.clone());
+        let hooks = config.hooks.iter().map(HookConfig::build).collect();
         Self {
             storages,
             doc_manager,
             chunker,
+            format_chunkers: HashMap::new(),
             extractor,
             status_service,
             error_reporter,
             entity_relationship_extractor,
             processing_lock: Arc::new(Mutex::new(())),
+            entity_resolver: None,
+            descriptions,
+            summaries,
+            summary_locks: Mutex::new(HashMap::new()),
+            usage,
+            hooks,
             config,
         }
     }
 
+    /// Registers a chunker used instead of the default one for files with
+    /// the given extension.
+    pub fn with_format_chunker(mut self, extension: &str, chunker: Arc<dyn Chunker>) -> Self {
+        self.format_chunkers
+            .insert(normalize_extension(extension), chunker);
+        self
+    }
+
+    /// Runs `hook` after the configured hooks.
+    pub fn with_hook(mut self, hook: Arc<dyn PipelineHook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Enables the entity resolution stage after extraction.
+    pub fn with_entity_resolver(mut self, resolver: Option<Arc<EntityResolver>>) -> Self {
+        self.entity_resolver = resolver;
+        self
+    }
+
+    /// Summarizes entity and relation descriptions that outgrow
+    /// `descriptions.token_budget`; without one they are only joined.
+    pub fn with_description_summarizer(
+        mut self,
+        summarizer: Arc<dyn DescriptionSummarizer>,
+    ) -> Self {
+        self.descriptions = self.descriptions.with_summarizer(summarizer);
+        self
+    }
+
+    /// Writes an abstract and keywords for each document when
+    /// `document_summary.enabled` is set.
+    pub fn with_document_summarizer(mut self, summarizer: Arc<dyn DocumentSummarizer>) -> Self {
+        self.summaries = self.summaries.with_summarizer(summarizer);
+        self
+    }
+
     pub fn document_manager(&self) -> &DocumentManager {
         &self.doc_manager
     }
 
+    pub fn chunker_for(&self, file_path: &str) -> Arc<dyn Chunker> {
+        file_extension(file_path)
+            .and_then(|ext| self.format_chunkers.get(&ext))
+            .cloned()
+            .unwrap_or_else(|| self.chunker.clone())
+    }
+
+    /// Chunks a document with the chunker for its format and records where
+    /// each chunk sits in the content. Also returns the model calls the
+    /// chunker made, for the caller to record once the document is stored.
+    pub async fn chunk_document(
+        &self,
+        file_path: &str,
+        content: &str,
+    ) -> Result<(Vec<Chunk>, Vec<ModelCall>)> {
+        let (mut chunks, calls) = self
+            .chunker_for(file_path)
+            .chunk_with_usage(content, &self.chunk_config())
+            .await?;
+        locate_chunks(content, &mut chunks);
+        Ok((chunks, calls))
+    }
+
+    pub fn chunk_config(&self) -> ChunkConfig {
+        self.config.chunk_config()
+    }
+
+    /// Writes the abstract and keywords of a document to its `full_docs`
+    /// record, unless it has them already. Failures are logged, as the
+    /// summary is optional.
+    pub async fn summarize_document(&self, doc_id: &str) {
+        let lock = self
+            .summary_locks
+            .lock()
+            .await
+            .entry(doc_id.to_string())
+            .or_default()
+            .clone();
+        let guard = lock.lock().await;
+        let result = async {
+            let Some(mut record) = self.storages.full_docs.get_by_id(doc_id).await? else {
+                return Ok(());
+            };
+            let summarized = self.summaries.summarize(&mut record).await;
+            if let Err(err) = &summarized
+                && let Some(call) = ModelCall::from_error(&self.config.llm_model, err)
+            {
+                self.record_calls(doc_id, None, UsageKind::DocumentSummary, &[call])
+                    .await;
+            }
+            if let Some(calls) = summarized? {
+                self.record_calls(doc_id, None, UsageKind::DocumentSummary, &calls)
+                    .await;
+                self.storages
+                    .full_docs
+                    .upsert(HashMap::from([(doc_id.to_string(), record)]))
+                    .await?;
+                self.storages.full_docs.sync_if_dirty().await?;
+            }
+            anyhow::Ok(())
+        }
+        .await;
+        if let Err(err) = result {
+            warn!(error = %err, doc_id = %doc_id, "failed to summarize document");
+        }
+        drop(guard);
+        let mut locks = self.summary_locks.lock().await;
+        // the map and this call hold the only references when nobody waits
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(doc_id);
+        }
+    }
+
+    /// Context about a document sent along with each of its chunks to the
+    /// extraction model.
+    pub async fn document_context(&self, doc_id: &str) -> Option<String> {
+        let record = self
+            .storages
+            .full_docs
+            .get_by_id(doc_id)
+            .await
+            .ok()
+            .flatten()?;
+        self.summaries.extraction_context(&record)
+    }
+
+    /// The text stored for a document: `content` after the before-chunking
+    /// hooks and sanitizing. Document ids and content ids are hashes of it.
+    async fn prepare_content(&self, file_path: &str, content: String) -> Result<String> {
+        Ok(sanitize_text(
+            &self.run_before_chunking(file_path, content).await?,
+        ))
+    }
+
+    async fn run_before_chunking(&self, file_path: &str, mut content: String) -> Result<String> {
+        for hook in &self.hooks {
+            content = hook
+                .before_chunking(file_path, content)
+                .await
+                .map_err(|err| anyhow!("hook {} failed: {err}", hook.name()))?;
+        }
+        Ok(content)
+    }
+
+    async fn run_after_extraction(
+        &self,
+        doc_id: &str,
+        chunk_id: &str,
+        mut extraction: EntitiesRelationships,
+    ) -> Result<EntitiesRelationships> {
+        for hook in &self.hooks {
+            extraction = hook
+                .after_extraction(doc_id, chunk_id, extraction)
+                .await
+                .map_err(|err| anyhow!("hook {} failed: {err}", hook.name()))?;
+        }
+        Ok(extraction)
+    }
+
+    async fn run_before_upsert(
+        &self,
+        doc_id: &str,
+        kind: RecordKind,
+        records: &mut HashMap<String, Value>,
+    ) -> Result<()> {
+        for hook in &self.hooks {
+            hook.before_upsert(doc_id, kind, records)
+                .await
+                .map_err(|err| anyhow!("hook {} failed: {err}", hook.name()))?;
+        }
+        Ok(())
+    }
+
+    fn csv_mapping_for(&self, file_path: &str, content: &str) -> Option<&CsvMapping> {
+        if file_extension(file_path).as_deref() != Some("csv") {
+            return None;
+        }
+        find_mapping(&self.config.csv_mappings, file_path, content)
+    }
+
+    /// Returns the status of a document already ingested under `file_name`.
+    pub async fn find_existing_document(
+        &self,
+        file_name: &str,
+    ) -> StorageResult<Option<DocProcessingStatus>> {
+        self.storages
+            .doc_status
+            .get_doc_by_file_path(file_name)
+            .await
+    }
+
+    /// Returns the document stored under `file_name` if its current version
+    /// has exactly `content`. Uploads and the input watcher use this to reject
+    /// duplicates while still accepting new versions of a file.
+    pub async fn find_unchanged_document(
+        &self,
+        file_name: &str,
+        content: &str,
+    ) -> StorageResult<Option<DocProcessingStatus>> {
+        let Some(existing) = self.find_existing_document(file_name).await? else {
+            return Ok(None);
+        };
+        // content ids are taken after the hooks, so the upload is compared in
+        // the same form; when a hook fails it can't be, and enqueueing the
+        // file reports the failure
+        let Ok(content) = self.prepare_content(file_name, content.to_string()).await else {
+            return Ok(None);
+        };
+        if current_content_id(&existing).as_deref() == Some(content_id(&content).as_str()) {
+            Ok(Some(existing))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads a file from the input directory and enqueues it, as a new
+    /// document or as a new version of the document stored under the same
+    /// name. Files whose text cannot be extracted are recorded as failed and
+    /// returned as an error; they stay in the input directory, as do new
+    /// versions of a document that is still in progress.
     pub async fn enqueue_file(
         &self,
         file_path: PathBuf,
-        track_id: Option<String>,
-    ) -> Result<String> {
-        let track_id = track_id.unwrap_or_else(|| generate_track_id("upload"));
-        match self.extractor.extract(&file_path, &self.doc_manager).await {
-            Ok(content) => {
-                let doc_input = DocumentInput {
-                    content,
-                    file_path: file_path
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or_default()
-                        .to_string(),
-                };
-                self.enqueue_documents(vec![doc_input], &track_id).await?;
-                if let Err(err) = self.doc_manager.move_to_enqueued(&file_path).await {
-                    warn!(error = %err, "failed moving file to enqueued directory");
-                }
-            }
+        track_id: &str,
+        metadata: Option<Value>,
+    ) -> Result<EnqueuedDocument> {
+        let file_name = file_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let content = match self.extractor.extract(&file_path, &self.doc_manager).await {
+            Ok(content) => content,
             Err(err) => {
-                error!(error = %err, "failed to extract file");
+                error!(error = %err, file = %file_name, "failed to extract file");
                 self.error_reporter
-                    .record(&file_path, &track_id, "file_extraction", &err)
+                    .record(&file_path, track_id, "file_extraction", &err)
                     .await?;
+                return Err(err.context(format!("failed to extract text from '{file_name}'")));
             }
+        };
+
+        let doc_input = DocumentInput {
+            content,
+            file_path: file_name.clone(),
+            metadata,
+        };
+        let outcome = self
+            .submit_documents(vec![doc_input], track_id)
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("no enqueue outcome for '{file_name}'"))?;
+        if outcome.status == EnqueueStatus::InProgress {
+            return Ok(outcome);
+        }
+        if let Err(err) = self.doc_manager.move_to_enqueued(&file_path).await {
+            warn!(error = %err, "failed moving file to enqueued directory");
         }
 
-        Ok(track_id)
+        Ok(outcome)
     }
 
     pub async fn enqueue_pending_docs(&self, scheduler: Arc<Scheduler>) -> Result<()> {
@@ -243,38 +620,73 @@ impl Pipeline {
     }
 
     async fn process_document(&self, doc_id: &str, status: &DocProcessingStatus) -> Result<()> {
-        let content_value = self
-            .storages
-            .full_docs
-            .get_by_id(doc_id)
-            .await?
-            .ok_or_else(|| anyhow!("document content missing"))?;
-
-        let content = content_value
-            .get("content")
-            .and_then(Value::as_str)
-            .ok_or_else(|| anyhow!("document content field missing"))?;
-
-        let chunk_config = ChunkConfig {
-            max_tokens: self.config.chunk_size,
-            overlap_tokens: self.config.chunk_overlap,
-            split_by_character: self.config.split_by_character.clone(),
-            split_by_character_only: self.config.split_by_character_only,
-        };
-
-        let chunks = self.chunker.chunk(content, &chunk_config)?;
-        let extraction_results: Vec<EntitiesRelationships> = stream::iter(chunks.iter().cloned())
+        // documents are chunked when they are enqueued; chunking again would
+        // embed a semantically chunked document twice
+        let mut chunks = self.stored_chunks(doc_id, status).await?;
+        if chunks.is_empty() {
+            let content_value = self
+                .storages
+                .full_docs
+                .get_by_id(doc_id)
+                .await?
+                .ok_or_else(|| anyhow!("document content missing"))?;
+            let content = content_value
+                .get("content")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("document content field missing"))?;
+            let file_path = status.file_path.clone().unwrap_or_default();
+            let (fresh, chunking_calls) = self.chunk_document(&file_path, content).await?;
+            self.record_calls(doc_id, None, UsageKind::SemanticChunking, &chunking_calls)
+                .await;
+            chunks = fresh;
+        }
+        self.summarize_document(doc_id).await;
+        let context = self.document_context(doc_id).await;
+        let attempts: Vec<Result<ChunkExtraction>> = stream::iter(chunks.iter().cloned())
             .map(|chunk| {
                 let extractor = Arc::clone(&self.entity_relationship_extractor);
-                async move { extractor.extract_entities_and_relationships(&chunk).await }
+                let context = context.clone();
+                async move {
+                    extractor
+                        .extract_with_context(&chunk, context.as_deref())
+                        .await
+                }
             })
-            .buffer_unordered(50)
-            .try_collect::<Vec<_>>()
-            .await?;
+            .buffered(self.config.max_concurrent_chunks)
+            .collect()
+            .await;
+        if attempts.iter().any(Result::is_err) {
+            // the document fails as a whole, but every attempt was billed
+            for (chunk, attempt) in chunks.iter().zip(&attempts) {
+                match attempt {
+                    Ok(extraction) => {
+                        self.record_extraction_usage(doc_id, &chunk.id, extraction)
+                            .await;
+                    }
+                    Err(err) => {
+                        if let Some(call) = ModelCall::from_error(&self.config.llm_model, err) {
+                            self.record_calls(
+                                doc_id,
+                                Some(&chunk.id),
+                                UsageKind::Extraction,
+                                &[call],
+                            )
+                            .await;
+                        }
+                    }
+                }
+            }
+        }
+        let mut extraction_results = attempts.into_iter().collect::<Result<Vec<_>>>()?;
 
         if chunks.is_empty() {
             warn!(doc_id = %doc_id, "no chunks created for document");
         }
+        for (chunk, extraction) in chunks.iter().zip(extraction_results.iter_mut()) {
+            extraction.result = self
+                .run_after_extraction(doc_id, &chunk.id, std::mem::take(&mut extraction.result))
+                .await?;
+        }
 
         if extraction_results.len() != chunks.len() {
             warn!(
@@ -290,7 +702,7 @@ impl Pipeline {
             .mark_processing(doc_id, status, &chunk_ids)
             .await?;
 
-        let chunk_map: HashMap<String, Value> = chunks
+        let mut chunk_map: HashMap<String, Value> = chunks
             .iter()
             .map(|chunk| {
                 let obj = json!({
@@ -299,110 +711,48 @@ impl Pipeline {
                     "chunk_order_index": chunk.order,
                     "file_path": status.file_path.clone().unwrap_or_default(),
                     "tokens": chunk.token_count,
+                    "heading": chunk.heading,
+                    "span": chunk.span,
+                    "status": "Success",
                 });
                 (chunk.id.clone(), obj)
             })
             .collect();
 
-        if !chunk_map.is_empty() {
-            self.storages.text_chunks.upsert(chunk_map).await?;
-        }
-
-        let mut entities_payload: HashMap<String, Value> = HashMap::new();
-        let mut relations_payload: HashMap<String, Value> = HashMap::new();
-        let mut entity_index: HashMap<String, String> = HashMap::new();
-
+        let mut records = ExtractedRecords::new(
+            doc_id,
+            status.document_metadata(),
+            self.config.relation_repair.clone(),
+        );
         for (chunk, extraction) in chunks.iter().zip(extraction_results.iter()) {
-            for entity in extraction.entities.iter() {
-                let key = format!(
-                    "{}::{}",
-                    doc_id,
-                    entity.entity_name.trim().to_ascii_lowercase()
-                );
-                let entry = entity_index.entry(key.clone());
-                let entity_id = entry.or_insert_with(|| {
-                    compute_mdhash_id(
-                        &format!(
-                            "{}:{}:{}",
-                            doc_id,
-                            entity.entity_name,
-                            entity.entity_type.as_str()
-                        ),
-                        "entity-",
-                    )
-                });
-
-                entities_payload
-                    .entry(entity_id.clone())
-                    .or_insert_with(|| {
-                        json!({
-                            "entity_name": entity.entity_name,
-                            "entity_type": entity.entity_type.as_str(),
-                            "entity_description": entity.entity_description,
-                            "doc_id": doc_id,
-                            "chunk_id": chunk.id,
-                            "chunk_order_index": chunk.order,
-                        })
-                    });
-            }
-
-            for relationship in extraction.relationships.iter() {
-                let source_key = format!(
-                    "{}::{}",
-                    doc_id,
-                    relationship.source_entity.trim().to_ascii_lowercase()
-                );
-                let target_key = format!(
-                    "{}::{}",
-                    doc_id,
-                    relationship.target_entity.trim().to_ascii_lowercase()
-                );
-
-                let source_id = entity_index.get(&source_key).cloned();
-                let target_id = entity_index.get(&target_key).cloned();
-
-                let (Some(source_id), Some(target_id)) = (source_id, target_id) else {
-                    warn!(
-                        doc_id = %doc_id,
-                        chunk_id = %chunk.id,
-                        source = %relationship.source_entity,
-                        target = %relationship.target_entity,
-                        "relationship references unknown entity"
-                    );
-                    continue;
-                };
-
-                let relation_id = compute_mdhash_id(
-                    &format!(
-                        "{}:{}:{}:{}",
-                        doc_id, source_id, target_id, relationship.relationship_description
-                    ),
-                    "relation-",
-                );
-
-                relations_payload.entry(relation_id).or_insert_with(|| {
-                    json!({
-                        "doc_id": doc_id,
-                        "chunk_id": chunk.id,
-                        "source_entity_id": source_id,
-                        "target_entity_id": target_id,
-                        "relationship_keywords": relationship.relationship_keywords,
-                        "relationship_description": relationship.relationship_description,
-                    })
-                });
+            let usage = self
+                .record_extraction_usage(doc_id, &chunk.id, extraction)
+                .await;
+            let repair_counts = self.add_extraction(
+                &mut records,
+                &SourceChunk {
+                    id: &chunk.id,
+                    order: chunk.order,
+                    content: &chunk.content,
+                    span: chunk.span,
+                },
+                &extraction.result,
+            );
+            if let Some(chunk_record) = chunk_map.get_mut(&chunk.id) {
+                chunk_record["extraction_passes"] = json!(extraction.passes);
+                chunk_record["usage"] = json!(usage);
+                chunk_record["relation_repair"] = json!(repair_counts);
             }
         }
-
-        if !entities_payload.is_empty() {
-            self.storages.full_entities.upsert(entities_payload).await?;
+        for (chunk_id, chunk_record) in chunk_map.iter_mut() {
+            chunk_record["entity_ids"] = json!(records.entity_ids_of(chunk_id));
         }
 
-        if !relations_payload.is_empty() {
-            self.storages
-                .full_relations
-                .upsert(relations_payload)
-                .await?;
+        if !chunk_map.is_empty() {
+            self.storages.text_chunks.upsert(chunk_map).await?;
         }
+        self.store_records(records).await?;
+        self.finish_document(doc_id).await;
 
         self.persist_all().await?;
 
@@ -413,22 +763,30 @@ impl Pipeline {
         Ok(())
     }
 
+    /// The stored chunks of `doc_id`, in document order.
+    async fn stored_chunks(
+        &self,
+        doc_id: &str,
+        status: &DocProcessingStatus,
+    ) -> Result<Vec<Chunk>> {
+        let chunk_ids = self.doc_chunk_ids(doc_id, status).await?;
+        let records = self.storages.text_chunks.get_by_ids(&chunk_ids).await?;
+        let mut chunks: Vec<Chunk> = chunk_ids
+            .into_iter()
+            .zip(records)
+            .filter_map(|(chunk_id, record)| stored_chunk(chunk_id, &record?))
+            .collect();
+        chunks.sort_by_key(|chunk| chunk.order);
+        Ok(chunks)
+    }
+
     async fn store_chunks(
         &self,
-        content: &str,
+        chunks: &[Chunk],
         doc_id: &str,
         file_path: &str,
-        track_id: &str,
+        chunk_status: &str,
     ) -> Result<()> {
-        let chunk_config = ChunkConfig {
-            max_tokens: self.config.chunk_size,
-            overlap_tokens: self.config.chunk_overlap,
-            split_by_character: self.config.split_by_character.clone(),
-            split_by_character_only: self.config.split_by_character_only,
-        };
-
-        let chunks = self.chunker.chunk(content, &chunk_config)?;
-
         let chunk_map: HashMap<String, Value> = chunks
             .iter()
             .map(|chunk| {
@@ -438,7 +796,9 @@ impl Pipeline {
                     "chunk_order_index": chunk.order,
                     "file_path": file_path,
                     "token": chunk.token_count,
-                    "status": "Pending"
+                    "heading": chunk.heading,
+                    "span": chunk.span,
+                    "status": chunk_status
                 });
                 (chunk.id.clone(), obj)
             })
@@ -453,49 +813,238 @@ impl Pipeline {
         Ok(())
     }
 
-    async fn enqueue_documents(&self, docs: Vec<DocumentInput>, track_id: &str) -> Result<()> {
-        if docs.is_empty() {
-            return Ok(());
+    /// Enqueues documents, storing those whose `file_path` names a known
+    /// document as its new version and the rest as new documents. Outcomes
+    /// are returned in input order.
+    pub async fn submit_documents(
+        &self,
+        docs: Vec<DocumentInput>,
+        track_id: &str,
+    ) -> Result<Vec<EnqueuedDocument>> {
+        let mut outcomes: Vec<Option<EnqueuedDocument>> = Vec::with_capacity(docs.len());
+        let mut fresh = Vec::new();
+        let mut fresh_slots = Vec::new();
+        for doc in docs {
+            let existing = if doc.file_path.is_empty() {
+                None
+            } else {
+                self.find_existing_document(&doc.file_path).await?
+            };
+            match existing {
+                Some(existing) => {
+                    let doc_id = existing.id.clone();
+                    let file_path = doc.file_path.clone();
+                    let reingestion = self.reingest_document(existing, doc, track_id).await?;
+                    outcomes.push(Some(EnqueuedDocument {
+                        doc_id,
+                        file_path,
+                        status: reingestion.status(),
+                        near_duplicate_of: None,
+                        similarity: None,
+                    }));
+                }
+                None => {
+                    fresh_slots.push(outcomes.len());
+                    outcomes.push(None);
+                    fresh.push(doc);
+                }
+            }
+        }
+
+        if !fresh.is_empty() {
+            let enqueued = self.enqueue_documents(fresh, track_id).await?;
+            for (slot, outcome) in fresh_slots.into_iter().zip(enqueued) {
+                outcomes[slot] = Some(outcome);
+            }
         }
+        Ok(outcomes.into_iter().flatten().collect())
+    }
 
-        let mut unique_contents: HashMap<String, String> = HashMap::new();
+    /// Chunks and stores new documents as pending. Documents are identified by
+    /// a hash of their cleaned content, so resubmitting the same text is
+    /// reported as a duplicate instead of being ingested twice. Documents
+    /// without a file path are stored under `text/<doc_id>`.
+    pub async fn enqueue_documents(
+        &self,
+        docs: Vec<DocumentInput>,
+        track_id: &str,
+    ) -> Result<Vec<EnqueuedDocument>> {
+        let mut outcomes = Vec::with_capacity(docs.len());
+        let mut contents: HashMap<String, (String, String, Option<Value>)> = HashMap::new();
+        let mut order = Vec::new();
         for doc in docs {
-            let cleaned = sanitize_text(&doc.content);
+            let cleaned = match self.prepare_content(&doc.file_path, doc.content).await {
+                Ok(cleaned) => cleaned,
+                Err(err) => {
+                    error!(error = %err, file = %doc.file_path, "failed to prepare document");
+                    self.error_reporter
+                        .record(Path::new(&doc.file_path), track_id, "before_chunking", &err)
+                        .await?;
+                    outcomes.push(EnqueuedDocument {
+                        doc_id: None,
+                        file_path: doc.file_path,
+                        status: EnqueueStatus::Failed,
+                        near_duplicate_of: None,
+                        similarity: None,
+                    });
+                    continue;
+                }
+            };
             if cleaned.is_empty() {
+                outcomes.push(EnqueuedDocument {
+                    doc_id: None,
+                    file_path: doc.file_path,
+                    status: EnqueueStatus::Empty,
+                    near_duplicate_of: None,
+                    similarity: None,
+                });
                 continue;
             }
-            unique_contents
-                .entry(cleaned)
-                .or_insert_with(|| doc.file_path.clone());
-        }
-
-        if unique_contents.is_empty() {
-            return Ok(());
+            let doc_id = content_id(&cleaned);
+            // path-less text gets a path of its own, as documents are also
+            // looked up by path
+            let file_path = if doc.file_path.trim().is_empty() {
+                format!("text/{doc_id}")
+            } else {
+                doc.file_path
+            };
+            let status = if contents.contains_key(&doc_id) {
+                EnqueueStatus::Duplicate
+            } else {
+                contents.insert(
+                    doc_id.clone(),
+                    (
+                        cleaned,
+                        file_path.clone(),
+                        doc.metadata.map(normalize_metadata),
+                    ),
+                );
+                order.push(doc_id.clone());
+                EnqueueStatus::New
+            };
+            outcomes.push(EnqueuedDocument {
+                doc_id: Some(doc_id),
+                file_path,
+                status,
+                near_duplicate_of: None,
+                similarity: None,
+            });
         }
 
-        let mut contents: HashMap<String, (String, String)> = HashMap::new();
-        for (content, path) in unique_contents {
-            let doc_id = compute_mdhash_id(&content, "doc-");
-            contents.insert(doc_id, (content, path));
+        if contents.is_empty() {
+            return Ok(outcomes);
         }
 
         let doc_ids: HashSet<String> = contents.keys().cloned().collect();
         let unique_ids = self.status_service.filter_new_ids(&doc_ids).await?;
+        for outcome in &mut outcomes {
+            if let Some(doc_id) = &outcome.doc_id
+                && !unique_ids.contains(doc_id)
+            {
+                outcome.status = EnqueueStatus::Duplicate;
+            }
+        }
 
         if unique_ids.is_empty() {
             warn!("no new documents to enqueue");
-            return Ok(());
+            return Ok(outcomes);
         }
 
         let now = chrono::Utc::now().to_rfc3339();
         let mut pending = Vec::new();
+        let mut mapped = Vec::new();
+        let mut linked = Vec::new();
+        let mut chunking_calls = Vec::new();
+
+        let near_dup = &self.config.near_duplicate;
+        let hasher = MinHasher::from_config(near_dup);
+        let mut fingerprints = self.load_fingerprints().await?;
+        let mut new_fingerprints = HashMap::new();
+        let mut skipped = Vec::new();
+
+        for doc_id in order.into_iter().filter(|id| unique_ids.contains(id)) {
+            if let Some((content, path, mut metadata)) = contents.remove(&doc_id) {
+                let signature = hasher.signature(&content);
+                let best_match = fingerprints.best_match(&signature);
+
+                if let Some((original_id, score)) = &best_match {
+                    info!(doc_id = %doc_id, original = %original_id, similarity = score, policy = ?near_dup.policy, "near-duplicate document");
+                    if let Some(outcome) = outcomes
+                        .iter_mut()
+                        .find(|o| o.doc_id.as_deref() == Some(doc_id.as_str()))
+                    {
+                        outcome.near_duplicate_of = Some(original_id.clone());
+                        outcome.similarity = Some(*score);
+                        outcome.status = match near_dup.policy {
+                            NearDuplicatePolicy::Skip => EnqueueStatus::NearDuplicate,
+                            NearDuplicatePolicy::Link => EnqueueStatus::Linked,
+                            NearDuplicatePolicy::Ingest => EnqueueStatus::New,
+                        };
+                    }
+                    if near_dup.policy == NearDuplicatePolicy::Skip {
+                        skipped.push((
+                            original_id.clone(),
+                            json!({
+                                "doc_id": doc_id,
+                                "file_path": path,
+                                "similarity": score,
+                                "track_id": track_id,
+                                "skipped_at": now,
+                            }),
+                        ));
+                        continue;
+                    }
+                    metadata = Some(with_metadata_field(
+                        metadata,
+                        "near_duplicate",
+                        json!({
+                            "doc_id": original_id,
+                            "similarity": score,
+                            "policy": format!("{:?}", near_dup.policy).to_lowercase(),
+                        }),
+                    ));
+                }
+
+                new_fingerprints.insert(
+                    doc_id.clone(),
+                    json!({ "signature": signature, "file_path": path }),
+                );
+                fingerprints.insert(doc_id.clone(), signature);
 
-        for doc_id in unique_ids {
-            if let Some((content, path)) = contents.remove(&doc_id) {
                 let summary = summarize_content(&content);
                 let length = content.chars().count() as i64;
-                self.store_chunks(&content, &doc_id, &path, track_id)
+                if let Some((original_id, _)) = best_match
+                    && near_dup.policy == NearDuplicatePolicy::Link
+                {
+                    linked.push((doc_id.clone(), original_id));
+                    pending.push(PendingDocument {
+                        id: doc_id,
+                        content,
+                        summary,
+                        length,
+                        file_path: path,
+                        track_id: track_id.to_string(),
+                        created_at: now.clone(),
+                        metadata,
+                    });
+                    continue;
+                }
+
+                let (chunks, calls) = self.chunk_document(&path, &content).await?;
+                chunking_calls.push((doc_id.clone(), calls));
+                let mapping = self.csv_mapping_for(&path, &content);
+                // mapped tables never reach the scheduler, so their chunks are
+                // stored as already extracted
+                let chunk_status = if mapping.is_some() {
+                    "Success"
+                } else {
+                    "Pending"
+                };
+                self.store_chunks(&chunks, &doc_id, &path, chunk_status)
                     .await?;
+                if let Some(mapping) = mapping {
+                    mapped.push((doc_id.clone(), mapping, chunks));
+                }
                 pending.push(PendingDocument {
                     id: doc_id,
                     content,
@@ -504,46 +1053,2364 @@ impl Pipeline {
                     file_path: path,
                     track_id: track_id.to_string(),
                     created_at: now.clone(),
+                    metadata,
                 });
             }
         }
 
         self.status_service.enqueue_pending(pending).await?;
+        for (doc_id, calls) in chunking_calls {
+            self.record_calls(&doc_id, None, UsageKind::SemanticChunking, &calls)
+                .await;
+        }
+        self.storages
+            .doc_fingerprints
+            .upsert(new_fingerprints)
+            .await?;
+        for (doc_id, original_id) in linked {
+            self.link_version(&doc_id, &original_id).await?;
+        }
+        for (original_id, duplicate) in skipped {
+            self.record_skipped_duplicate(&original_id, duplicate)
+                .await?;
+        }
         self.persist_all().await?;
-        Ok(())
+
+        for (doc_id, mapping, chunks) in mapped {
+            self.map_csv_or_fail(&doc_id, mapping, &chunks).await?;
+        }
+        Ok(outcomes)
     }
 
-    pub async fn persist_all(&self) -> StorageResult<()> {
-        self.storages.full_docs.sync_if_dirty().await?;
-        self.storages.text_chunks.sync_if_dirty().await?;
-        self.storages.full_entities.sync_if_dirty().await?;
-        self.storages.full_relations.sync_if_dirty().await?;
-        self.storages.llm_response_cache.sync_if_dirty().await?;
-        self.storages.doc_status.sync_if_dirty().await?;
+    /// Applies `mapping` to the chunks of `doc_id`, marking the document
+    /// failed when the table can't be mapped.
+    async fn map_csv_or_fail(
+        &self,
+        doc_id: &str,
+        mapping: &CsvMapping,
+        chunks: &[Chunk],
+    ) -> Result<()> {
+        if let Err(err) = self.apply_csv_mapping(doc_id, mapping, chunks).await {
+            error!(error = %err, doc_id = %doc_id, mapping = %mapping.name, "failed to apply csv mapping");
+            if let Some(status) = self.storages.doc_status.get_by_id(doc_id).await? {
+                self.status_service
+                    .mark_failed(doc_id, &status, &err)
+                    .await?;
+            }
+        }
         Ok(())
     }
-}
 
-fn generate_track_id(prefix: &str) -> String {
-    format!("{}-{}", prefix, Uuid::new_v4())
-}
+    /// Replaces the content of an existing document with a new version.
+    /// Chunks whose text is unchanged keep their extraction results, only new
+    /// chunks are queued for extraction, and entities and relations that were
+    /// sourced solely from removed chunks are deleted. The document keeps its
+    /// id and gains an entry in `metadata.versions`. Documents still queued
+    /// or being extracted are left alone: their results would land on the
+    /// new version and mark it processed before its own chunks are.
+    pub async fn reingest_document(
+        &self,
+        existing: DocProcessingStatus,
+        input: DocumentInput,
+        track_id: &str,
+    ) -> Result<Reingestion> {
+        let doc_id = existing
+            .id
+            .clone()
+            .ok_or_else(|| anyhow!("existing document has no id"))?;
+        if in_progress(&existing) {
+            info!(doc_id = %doc_id, status = ?existing.status, "document still in progress, new version not ingested");
+            return Ok(Reingestion::InProgress);
+        }
+        // like new documents, a version a hook fails on is recorded as failed
+        // and reported rather than returned as an error
+        let content = match self
+            .prepare_content(&input.file_path, input.content.clone())
+            .await
+        {
+            Ok(content) => content,
+            Err(err) => {
+                error!(error = %err, file = %input.file_path, "failed to prepare document");
+                self.error_reporter
+                    .record(
+                        Path::new(&input.file_path),
+                        track_id,
+                        "before_chunking",
+                        &err,
+                    )
+                    .await?;
+                return Ok(Reingestion::Failed);
+            }
+        };
+        if content.is_empty() {
+            return Ok(Reingestion::Empty);
+        }
+        let new_content_id = content_id(&content);
+        if current_content_id(&existing).as_deref() == Some(new_content_id.as_str()) {
+            info!(doc_id = %doc_id, "document content unchanged, nothing to re-ingest");
+            return Ok(Reingestion::Unchanged);
+        }
 
-fn sanitize_text(input: &str) -> String {
-    input.replace('\r', "").trim().to_string()
-}
+        let path = input.file_path.clone();
+        let (chunks, chunking_calls) = self.chunk_document(&path, &content).await?;
+        let previous = self.doc_chunk_ids(&doc_id, &existing).await?;
+        let diff = ChunkDiff::new(&previous, &chunks);
+
+        // reused chunks keep their status and extraction, only their position moves
+        let reused_records = self.storages.text_chunks.get_by_ids(&diff.reused).await?;
+        let by_id: HashMap<&str, &Chunk> = chunks.iter().map(|c| (c.id.as_str(), c)).collect();
+        let mut reused_updates = HashMap::new();
+        for (chunk_id, record) in diff.reused.iter().zip(reused_records) {
+            if let (Some(mut record), Some(chunk)) = (record, by_id.get(chunk_id.as_str())) {
+                record["chunk_order_index"] = json!(chunk.order);
+                record["heading"] = json!(chunk.heading);
+                record["span"] = json!(chunk.span);
+                reused_updates.insert(chunk_id.clone(), record);
+            }
+        }
+        if !reused_updates.is_empty() {
+            self.storages.text_chunks.upsert(reused_updates).await?;
+        }
 
-fn summarize_content(content: &str) -> String {
-    const MAX_LEN: usize = 200;
-    let trimmed = content.trim();
-    if trimmed.len() <= MAX_LEN {
-        trimmed.to_string()
-    } else {
-        format!("{}…", &trimmed[..MAX_LEN])
-    }
-}
+        let added: HashSet<&str> = diff.added.iter().map(String::as_str).collect();
+        let added_chunks: Vec<Chunk> = chunks
+            .iter()
+            .filter(|chunk| added.contains(chunk.id.as_str()))
+            .cloned()
+            .collect();
+        let mapping = self.csv_mapping_for(&path, &content);
+        let chunk_status = if mapping.is_some() {
+            "Success"
+        } else {
+            "Pending"
+        };
+        self.store_chunks(&added_chunks, &doc_id, &path, chunk_status)
+            .await?;
 
-#[derive(Clone)]
-struct DocumentInput {
-    content: String,
-    file_path: String,
+        self.retire_chunks(&doc_id, &diff.removed).await?;
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut metadata = existing.metadata.clone();
+        if version_count(metadata.as_ref()) == 0 {
+            metadata = Some(push_version(
+                metadata,
+                json!({
+                    "version": 1,
+                    "content_id": current_content_id(&existing),
+                    "created_at": existing.created_at,
+                    "track_id": existing.track_id,
+                    "chunks": previous.len(),
+                }),
+            ));
+        }
+        let version = version_count(metadata.as_ref()) + 1;
+        metadata = Some(push_version(
+            metadata,
+            json!({
+                "version": version,
+                "content_id": new_content_id,
+                "created_at": now,
+                "track_id": track_id,
+                "chunks": chunks.len(),
+                "reused_chunks": diff.reused.len(),
+                "added_chunks": diff.added.len(),
+                "removed_chunks": diff.removed.len(),
+            }),
+        ));
+        let mut metadata = with_metadata_field(metadata, "content_id", json!(new_content_id));
+        if let Some(Value::Object(fields)) = input.metadata.map(normalize_metadata)
+            && let Some(target) = metadata.as_object_mut()
+        {
+            target.extend(fields);
+        }
+        let doc_metadata = DocumentMetadata::from_stored(Some(&metadata));
+        if doc_metadata != existing.document_metadata() {
+            self.refresh_provenance(&doc_id, &doc_metadata).await?;
+        }
+
+        let signature = MinHasher::from_config(&self.config.near_duplicate).signature(&content);
+        self.storages
+            .doc_fingerprints
+            .upsert(HashMap::from([(
+                doc_id.clone(),
+                json!({ "signature": signature, "file_path": path }),
+            )]))
+            .await?;
+        // keep the rest of the record, but the summary was of the old content
+        let mut full_doc = self
+            .storages
+            .full_docs
+            .get_by_id(&doc_id)
+            .await?
+            .filter(Value::is_object)
+            .unwrap_or_else(|| json!({}));
+        full_doc["content"] = json!(content);
+        clear_summary(&mut full_doc);
+        self.storages
+            .full_docs
+            .upsert(HashMap::from([(doc_id.clone(), full_doc)]))
+            .await?;
+
+        let needs_extraction = mapping.is_none()
+            && self
+                .storages
+                .text_chunks
+                .get_by_ids(&diff.reused)
+                .await?
+                .iter()
+                .flatten()
+                .any(|record| record.get("status").and_then(Value::as_str) != Some("Success"));
+        let needs_processing = !diff.added.is_empty() || needs_extraction;
+        let status = if !needs_processing || mapping.is_some() {
+            DocStatus::PROCESSED
+        } else {
+            DocStatus::PENDING
+        };
+        let chunk_ids: Vec<String> = chunks.iter().map(|chunk| chunk.id.clone()).collect();
+        let updated = DocProcessingStatus {
+            id: Some(doc_id.clone()),
+            status,
+            content_summary: Some(summarize_content(&content)),
+            content_length: Some(content.chars().count() as i64),
+            created_at: existing.created_at.clone(),
+            updated_at: Some(now),
+            file_path: Some(path),
+            track_id: Some(track_id.to_string()),
+            chunks_list: Some(chunk_ids),
+            metadata: Some(metadata),
+            error_msg: None,
+        };
+        self.storages
+            .doc_status
+            .upsert(HashMap::from([(doc_id.clone(), updated)]))
+            .await?;
+        self.record_calls(&doc_id, None, UsageKind::SemanticChunking, &chunking_calls)
+            .await;
+        self.persist_all().await?;
+
+        if let Some(mapping) = mapping {
+            self.map_csv_or_fail(&doc_id, mapping, &added_chunks)
+                .await?;
+        } else if !needs_processing {
+            // pending documents are summarized before they are extracted
+            self.summarize_document(&doc_id).await;
+        }
+
+        info!(
+            doc_id = %doc_id,
+            version,
+            reused = diff.reused.len(),
+            added = diff.added.len(),
+            removed = diff.removed.len(),
+            "re-ingested new document version"
+        );
+        Ok(Reingestion::Updated(diff))
+    }
+
+    /// Rewrites the document metadata copied onto the entities and relations
+    /// of `doc_id` after the document's metadata changed.
+    async fn refresh_provenance(&self, doc_id: &str, metadata: &DocumentMetadata) -> Result<()> {
+        for storage in [&self.storages.full_entities, &self.storages.full_relations] {
+            let updates: HashMap<String, Value> = storage
+                .get_all()
+                .await?
+                .into_iter()
+                .filter(|(_, record)| record.get("doc_id").and_then(Value::as_str) == Some(doc_id))
+                .map(|(id, mut record)| {
+                    record["doc_metadata"] = json!(metadata);
+                    (id, record)
+                })
+                .collect();
+            storage.upsert(updates).await?;
+        }
+        Ok(())
+    }
+
+    /// Chunk ids of the current version of a document.
+    async fn doc_chunk_ids(
+        &self,
+        doc_id: &str,
+        status: &DocProcessingStatus,
+    ) -> Result<Vec<String>> {
+        if let Some(chunks) = status.chunks_list.as_ref().filter(|c| !c.is_empty()) {
+            return Ok(chunks.clone());
+        }
+        let mut records: Vec<(usize, String)> = self
+            .storages
+            .text_chunks
+            .get_all()
+            .await?
+            .into_iter()
+            .filter(|(_, record)| record.get("full_doc_id").and_then(Value::as_str) == Some(doc_id))
+            .map(|(chunk_id, record)| {
+                let order = record
+                    .get("chunk_order_index")
+                    .and_then(Value::as_u64)
+                    .unwrap_or_default() as usize;
+                (order, chunk_id)
+            })
+            .collect();
+        records.sort();
+        Ok(records.into_iter().map(|(_, chunk_id)| chunk_id).collect())
+    }
+
+    /// Deletes removed chunks of `doc_id` and the entities and relations that
+    /// were extracted only from them.
+    async fn retire_chunks(&self, doc_id: &str, removed: &[String]) -> Result<()> {
+        if removed.is_empty() {
+            return Ok(());
+        }
+        let removed_set: HashSet<String> = removed.iter().cloned().collect();
+
+        // chunk ids are content hashes, only drop records still owned by this doc
+        let owned: Vec<String> = removed
+            .iter()
+            .zip(self.storages.text_chunks.get_by_ids(removed).await?)
+            .filter(|(_, record)| {
+                record
+                    .as_ref()
+                    .and_then(|r| r.get("full_doc_id"))
+                    .and_then(Value::as_str)
+                    == Some(doc_id)
+            })
+            .map(|(chunk_id, _)| chunk_id.clone())
+            .collect();
+        self.storages.text_chunks.delete(&owned).await?;
+
+        let mut retired_entities = HashMap::new();
+        let mut entity_updates = HashMap::new();
+        for (entity_id, mut record) in self.storages.full_entities.get_all().await? {
+            if record.get("doc_id").and_then(Value::as_str) != Some(doc_id) {
+                continue;
+            }
+            let before = source_chunk_ids(&record);
+            if !before.iter().any(|id| removed_set.contains(id)) {
+                continue;
+            }
+            if retire_sources(&mut record, &removed_set) {
+                entity_updates.insert(entity_id, record);
+            } else {
+                retired_entities.insert(entity_id, record);
+            }
+        }
+
+        let mut retired_relations = Vec::new();
+        let mut relation_updates = HashMap::new();
+        for (relation_id, mut record) in self.storages.full_relations.get_all().await? {
+            if record.get("doc_id").and_then(Value::as_str) != Some(doc_id) {
+                continue;
+            }
+            let dangling = ["source_entity_id", "target_entity_id"].iter().any(|key| {
+                record
+                    .get(*key)
+                    .and_then(Value::as_str)
+                    .is_some_and(|id| retired_entities.contains_key(id))
+            });
+            let touched = source_chunk_ids(&record)
+                .iter()
+                .any(|id| removed_set.contains(id));
+            if dangling || (touched && !retire_sources(&mut record, &removed_set)) {
+                retired_relations.push(relation_id);
+            } else if touched {
+                relation_updates.insert(relation_id, record);
+            }
+        }
+
+        self.consolidate_descriptions(doc_id, &mut entity_updates, "entity_description")
+            .await;
+        self.consolidate_descriptions(doc_id, &mut relation_updates, "relationship_description")
+            .await;
+
+        let retired_entities: Vec<(String, Value)> = retired_entities.into_iter().collect();
+        let emptied = unlink_mentions(&self.storages.canonical_entities, &retired_entities).await?;
+        self.storages.entity_embeddings.delete(&emptied).await?;
+        let retired_ids: Vec<String> = retired_entities.into_iter().map(|(id, _)| id).collect();
+        self.storages.full_entities.delete(&retired_ids).await?;
+        self.storages.full_entities.upsert(entity_updates).await?;
+        self.storages
+            .full_relations
+            .delete(&retired_relations)
+            .await?;
+        self.storages
+            .full_relations
+            .upsert(relation_updates)
+            .await?;
+        info!(
+            doc_id = %doc_id,
+            chunks = owned.len(),
+            entities = retired_ids.len(),
+            relations = retired_relations.len(),
+            "retired removed chunks"
+        );
+        Ok(())
+    }
+
+    /// Indexes the stored signatures for near-duplicate lookups. Loading is
+    /// linear in the number of documents, once per batch; lookups only
+    /// compare documents that share a band.
+    async fn load_fingerprints(&self) -> Result<SignatureIndex> {
+        let mut index = SignatureIndex::new(&self.config.near_duplicate);
+        for (doc_id, value) in self.storages.doc_fingerprints.get_all().await? {
+            if let Some(signature) = value
+                .get("signature")
+                .and_then(|signature| serde_json::from_value(signature.clone()).ok())
+            {
+                index.insert(doc_id, signature);
+            }
+        }
+        Ok(index)
+    }
+
+    /// Records a document skipped as a near-duplicate under
+    /// `metadata.near_duplicates` of the document it matched.
+    async fn record_skipped_duplicate(&self, original_id: &str, duplicate: Value) -> Result<()> {
+        let Some(mut original) = self.storages.doc_status.get_by_id(original_id).await? else {
+            return Ok(());
+        };
+        let mut duplicates = original
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get("near_duplicates"))
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        duplicates.push(duplicate);
+        original.metadata = Some(with_metadata_field(
+            original.metadata.take(),
+            "near_duplicates",
+            Value::Array(duplicates),
+        ));
+        self.storages
+            .doc_status
+            .upsert(HashMap::from([(original_id.to_string(), original)]))
+            .await?;
+        Ok(())
+    }
+
+    /// Marks `doc_id` as processed without chunks and records it as a newer
+    /// version of `original_id` on both documents.
+    async fn link_version(&self, doc_id: &str, original_id: &str) -> Result<()> {
+        if let Some(status) = self.storages.doc_status.get_by_id(doc_id).await? {
+            self.status_service
+                .mark_processed(doc_id, &status, &[])
+                .await?;
+        }
+        if let Some(mut original) = self.storages.doc_status.get_by_id(original_id).await? {
+            let mut versions = original
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.get("linked_versions"))
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default();
+            versions.push(json!(doc_id));
+            original.metadata = Some(with_metadata_field(
+                original.metadata.take(),
+                "linked_versions",
+                Value::Array(versions),
+            ));
+            self.storages
+                .doc_status
+                .upsert(HashMap::from([(original_id.to_string(), original)]))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn apply_csv_mapping(
+        &self,
+        doc_id: &str,
+        mapping: &CsvMapping,
+        chunks: &[Chunk],
+    ) -> Result<()> {
+        for chunk in chunks {
+            let extraction = mapping.extract(&chunk.content)?;
+            self.store_extraction(doc_id, &chunk.id, chunk.order, extraction)
+                .await?;
+        }
+
+        self.finish_document(doc_id).await;
+
+        let chunk_ids: Vec<String> = chunks.iter().map(|chunk| chunk.id.clone()).collect();
+        if let Some(status) = self.storages.doc_status.get_by_id(doc_id).await? {
+            self.status_service
+                .mark_processed(doc_id, &status, &chunk_ids)
+                .await?;
+        }
+        info!(doc_id = %doc_id, mapping = %mapping.name, chunks = chunk_ids.len(), "imported csv through column mapping");
+        Ok(())
+    }
+
+    /// Upserts the entities and relations extracted from a single chunk.
+    /// Chunks of a document that already failed are not stored.
+    pub async fn store_extraction(
+        &self,
+        doc_id: &str,
+        chunk_id: &str,
+        chunk_order_index: usize,
+        extraction: EntitiesRelationships,
+    ) -> Result<()> {
+        let status = self.storages.doc_status.get_by_id(doc_id).await?;
+        if status
+            .as_ref()
+            .is_some_and(|status| status.status == DocStatus::FAILED)
+        {
+            info!(doc_id = %doc_id, chunk_id = %chunk_id, "skipped extraction of a failed document");
+            return Ok(());
+        }
+        // a newer version of the document may have removed the chunk
+        if status
+            .as_ref()
+            .and_then(|status| status.chunks_list.as_ref())
+            .is_some_and(|chunks| !chunks.is_empty() && !chunks.iter().any(|id| id == chunk_id))
+        {
+            info!(doc_id = %doc_id, chunk_id = %chunk_id, "skipped extraction of a retired chunk");
+            return Ok(());
+        }
+        // relations may name entities extracted from other chunks of the
+        // document, which are already stored
+        let stored_entities = match &status {
+            Some(status) => self.stored_doc_entities(doc_id, status).await?,
+            None => Vec::new(),
+        };
+        let doc_metadata = status
+            .map(|status| status.document_metadata())
+            .unwrap_or_default();
+        let extraction = self
+            .run_after_extraction(doc_id, chunk_id, extraction)
+            .await?;
+        // quotes are verified against the stored chunk text
+        let chunk_record = self.storages.text_chunks.get_by_id(chunk_id).await?;
+        let chunk_text = chunk_record
+            .as_ref()
+            .map(|chunk| field_str(chunk, "content"))
+            .unwrap_or_default();
+        let chunk_span: Option<Span> = chunk_record
+            .as_ref()
+            .and_then(|chunk| chunk.get("span"))
+            .and_then(|span| serde_json::from_value(span.clone()).ok());
+        let mut records =
+            ExtractedRecords::new(doc_id, doc_metadata, self.config.relation_repair.clone());
+        for (entity_id, entity) in &stored_entities {
+            records.add_stored_entity(entity_id, entity);
+        }
+        let repair_counts = self.add_extraction(
+            &mut records,
+            &SourceChunk {
+                id: chunk_id,
+                order: chunk_order_index,
+                content: &chunk_text,
+                span: chunk_span,
+            },
+            &extraction,
+        );
+        let entity_ids = records.entity_ids_of(chunk_id);
+        self.store_records(records).await?;
+        if let Some(mut chunk) = chunk_record {
+            chunk["relation_repair"] = json!(repair_counts);
+            chunk["entity_ids"] = json!(entity_ids);
+            self.storages
+                .text_chunks
+                .upsert(HashMap::from([(chunk_id.to_string(), chunk)]))
+                .await?;
+        }
+
+        self.persist_all().await?;
+        Ok(())
+    }
+
+    /// Entities stored from the chunks of `doc_id`, found through the entity
+    /// ids each chunk record lists rather than by scanning every entity.
+    async fn stored_doc_entities(
+        &self,
+        doc_id: &str,
+        status: &DocProcessingStatus,
+    ) -> Result<Vec<(String, Value)>> {
+        let chunk_ids = self.doc_chunk_ids(doc_id, status).await?;
+        let mut entity_ids: Vec<String> = Vec::new();
+        for chunk in self
+            .storages
+            .text_chunks
+            .get_by_ids(&chunk_ids)
+            .await?
+            .into_iter()
+            .flatten()
+        {
+            let ids = chunk.get("entity_ids").and_then(Value::as_array);
+            for id in ids.into_iter().flatten().filter_map(Value::as_str) {
+                if !entity_ids.iter().any(|known| known == id) {
+                    entity_ids.push(id.to_string());
+                }
+            }
+        }
+        let entities = self.storages.full_entities.get_by_ids(&entity_ids).await?;
+        // chunk ids are content hashes, so a chunk may be shared with another
+        // document
+        Ok(entity_ids
+            .into_iter()
+            .zip(entities)
+            .filter_map(|(id, entity)| Some((id, entity?)))
+            .filter(|(_, entity)| field_str(entity, "doc_id") == doc_id)
+            .collect())
+    }
+
+    /// Adds the entities and relationships extracted from one chunk to
+    /// `records`. An entity is identified by its document, name and type and
+    /// a relation by its document, the entities its endpoints resolve to, its
+    /// predicate and its modality, so a fact extracted from several chunks,
+    /// or under several spellings, is one record listing each chunk as a
+    /// source.
+    fn add_extraction(
+        &self,
+        records: &mut ExtractedRecords,
+        chunk: &SourceChunk<'_>,
+        extraction: &EntitiesRelationships,
+    ) -> RepairCounts {
+        let doc_id = records.doc_id.clone();
+        for entity in &extraction.entities {
+            let entity_type = self.config.ontology.resolve_type(&entity.entity_type);
+            let entity_id = compute_mdhash_id(
+                &format!("{}:{}:{}", doc_id, entity.entity_name, entity_type),
+                "entity-",
+            );
+            records
+                .endpoints
+                .add_entity(&entity.entity_name, &entity_id);
+            records
+                .entity_types
+                .insert(entity_id.clone(), entity_type.to_string());
+            let record = records.entities.entry(entity_id).or_insert_with(|| {
+                json!({
+                    "entity_name": entity.entity_name,
+                    "entity_type": entity_type,
+                    "entity_description": entity.entity_description,
+                    "canonical_id": canonical_entity_id(&entity.entity_name, entity_type),
+                    "doc_id": doc_id,
+                    "doc_metadata": records.doc_metadata,
+                    "chunk_id": chunk.id,
+                    "source_chunk_ids": [],
+                    "chunk_order_index": chunk.order,
+                })
+            });
+            // a placeholder created by an earlier chunk is now extracted
+            if let Some(fields) = record.as_object_mut() {
+                fields.remove("placeholder");
+            }
+            add_source_chunk(record, chunk.id);
+            push_description(
+                record,
+                "entity_description",
+                chunk.id,
+                &entity.entity_description,
+            );
+            if let Some(evidence) =
+                Evidence::locate(chunk.id, chunk.content, chunk.span, &entity.evidence)
+            {
+                push_evidence(record, evidence);
+            }
+        }
+
+        let mut repair_counts = RepairCounts::default();
+        for relationship in &extraction.relationships {
+            let Some((source_id, target_id)) = records.endpoints.resolve_relation(
+                &doc_id,
+                &relationship.source_entity,
+                &relationship.target_entity,
+                &mut repair_counts,
+            ) else {
+                warn!(
+                    doc_id = %doc_id,
+                    chunk_id = %chunk.id,
+                    source = %relationship.source_entity,
+                    target = %relationship.target_entity,
+                    "dropped relationship referencing unknown entity"
+                );
+                continue;
+            };
+            let predicate = self.checked_predicate(
+                relationship.predicate.as_deref(),
+                &records.entity_types,
+                &source_id,
+                &target_id,
+            );
+            // "inhibits" and "does not bind" between the same entities are
+            // different facts
+            let relation_id = compute_mdhash_id(
+                &format!(
+                    "{doc_id}:{source_id}:{target_id}:{}:{}",
+                    predicate.as_deref().unwrap_or_default(),
+                    relationship.modality.as_str()
+                ),
+                "rel-",
+            );
+            let record = records.relations.entry(relation_id).or_insert_with(|| {
+                json!({
+                    "source_entity_id": source_id,
+                    "target_entity_id": target_id,
+                    "relationship_keywords": relationship.relationship_keywords,
+                    "relationship_description": relationship.relationship_description,
+                    "confidence": relationship.confidence.map(|c| c.clamp(0.0, 1.0)),
+                    "modality": relationship.modality,
+                    "hedge_cues": relationship.hedge_cues,
+                    "predicate": predicate,
+                    "doc_id": doc_id,
+                    "doc_metadata": records.doc_metadata,
+                    "chunk_id": chunk.id,
+                    "source_chunk_ids": [],
+                })
+            });
+            add_source_chunk(record, chunk.id);
+            push_description(
+                record,
+                "relationship_description",
+                chunk.id,
+                &relationship.relationship_description,
+            );
+            if let Some(evidence) =
+                Evidence::locate(chunk.id, chunk.content, chunk.span, &relationship.evidence)
+            {
+                push_evidence(record, evidence);
+            }
+        }
+
+        for placeholder in records.endpoints.take_placeholders() {
+            let record = records
+                .entities
+                .entry(placeholder.entity_id.clone())
+                .or_insert_with(|| {
+                    placeholder_record(
+                        &placeholder,
+                        &doc_id,
+                        &records.doc_metadata,
+                        chunk.id,
+                        chunk.order,
+                    )
+                });
+            add_source_chunk(record, chunk.id);
+        }
+        repair_counts
+    }
+
+    /// Merges `records` with their stored versions and stores them.
+    async fn store_records(&self, records: ExtractedRecords) -> Result<()> {
+        let ExtractedRecords {
+            doc_id,
+            mut entities,
+            mut relations,
+            ..
+        } = records;
+        // the same entity or relation seen in other chunks keeps those sources
+        // and descriptions, so re-ingestion only retires it once every source
+        // chunk is gone
+        self.merge_existing(
+            &self.storages.full_entities,
+            &mut entities,
+            "entity_description",
+        )
+        .await?;
+        self.merge_existing(
+            &self.storages.full_relations,
+            &mut relations,
+            "relationship_description",
+        )
+        .await?;
+
+        // descriptions over budget are summarized by `finish_document`
+        if !entities.is_empty() {
+            for record in entities.values_mut() {
+                self.descriptions.join(record, "entity_description");
+            }
+            self.run_before_upsert(&doc_id, RecordKind::Entities, &mut entities)
+                .await?;
+        }
+        if !relations.is_empty() {
+            for record in relations.values_mut() {
+                self.descriptions.join(record, "relationship_description");
+            }
+            self.run_before_upsert(&doc_id, RecordKind::Relations, &mut relations)
+                .await?;
+        }
+
+        // every hook has run, so one failing stores nothing of the records
+        if !entities.is_empty() {
+            self.store_mentions(&doc_id, entities).await?;
+        }
+        if !relations.is_empty() {
+            self.storages.full_relations.upsert(relations).await?;
+        }
+        Ok(())
+    }
+
+    /// Normalizes entity mentions against the configured dictionaries, links
+    /// them to their canonical entities and stores them.
+    async fn store_mentions(
+        &self,
+        doc_id: &str,
+        mut records: HashMap<String, Value>,
+    ) -> Result<()> {
+        if !self.config.normalizer.is_empty() {
+            for record in records.values_mut() {
+                self.config.normalizer.normalize(record);
+            }
+        }
+        let mentions: Vec<Mention> = records
+            .iter()
+            .map(|(mention_id, record)| Mention {
+                mention_id: mention_id.clone(),
+                entity_name: field_str(record, "entity_name"),
+                entity_type: field_str(record, "entity_type"),
+                entity_description: field_str(record, "entity_description"),
+                doc_id: doc_id.to_string(),
+                normalization: record_normalization(record),
+            })
+            .collect();
+        let outcome = link_mentions(&self.storages.canonical_entities, &mentions).await?;
+        for (mention_id, canonical_id) in outcome.canonical_ids {
+            if let Some(record) = records.get_mut(&mention_id) {
+                record["canonical_id"] = json!(canonical_id);
+            }
+        }
+        self.storages.full_entities.upsert(records).await?;
+        Ok(())
+    }
+
+    /// Summarizes the entity and relation descriptions of a document that
+    /// outgrew the token budget and resolves the canonical entities it
+    /// introduced. Runs once every chunk of the document is stored, so these
+    /// model calls are made once per document rather than per chunk result.
+    /// Failures are logged; the extracted records are kept either way.
+    pub async fn finish_document(&self, doc_id: &str) {
+        if let Err(err) = self.try_finish_document(doc_id).await {
+            warn!(error = %err, doc_id = %doc_id, "failed to finish document");
+        }
+    }
+
+    async fn try_finish_document(&self, doc_id: &str) -> Result<()> {
+        let mut canonical_ids = HashSet::new();
+        for (storage, field) in [
+            (&self.storages.full_entities, "entity_description"),
+            (&self.storages.full_relations, "relationship_description"),
+        ] {
+            let mut records: HashMap<String, Value> = storage
+                .get_all()
+                .await?
+                .into_iter()
+                .filter(|(_, record)| field_str(record, "doc_id") == doc_id)
+                .collect();
+            canonical_ids.extend(
+                records
+                    .values()
+                    .filter_map(|record| record.get("canonical_id")?.as_str())
+                    .map(str::to_string),
+            );
+            let stored = records.clone();
+            self.consolidate_descriptions(doc_id, &mut records, field)
+                .await;
+            records.retain(|id, record| stored.get(id) != Some(&*record));
+
+            // summarizing awaited the model, during which a merge or another
+            // chunk may have changed the records; only descriptions are
+            // written back onto what is stored now
+            let ids: Vec<String> = records.keys().cloned().collect();
+            let current = storage.get_by_ids(&ids).await?;
+            let updates: HashMap<String, Value> = ids
+                .into_iter()
+                .zip(current)
+                .filter_map(|(id, current)| {
+                    let mut current = current?;
+                    apply_consolidated(&records[&id], &mut current, field).then_some((id, current))
+                })
+                .collect();
+            storage.upsert(updates).await?;
+        }
+
+        // canonical entities without an embedding were never resolved
+        if let Some(resolver) = &self.entity_resolver {
+            let unresolved: Vec<String> = self
+                .storages
+                .entity_embeddings
+                .filter_keys(&canonical_ids)
+                .await?
+                .into_iter()
+                .collect();
+            if !unresolved.is_empty() {
+                match resolver.resolve(&unresolved).await {
+                    Ok((_, calls)) => {
+                        self.record_calls(doc_id, None, UsageKind::EntityResolution, &calls)
+                            .await
+                    }
+                    Err(err) => warn!(error = %err, doc_id = %doc_id, "entity resolution failed"),
+                }
+            }
+        }
+        self.persist_all().await?;
+        Ok(())
+    }
+
+    async fn merge_existing(
+        &self,
+        storage: &JsonKvStorage,
+        records: &mut HashMap<String, Value>,
+        description_field: &str,
+    ) -> Result<()> {
+        let ids: Vec<String> = records.keys().cloned().collect();
+        for (id, existing) in ids.iter().zip(storage.get_by_ids(&ids).await?) {
+            let (Some(existing), Some(record)) = (existing, records.get_mut(id)) else {
+                continue;
+            };
+            let mut sources = source_chunk_ids(&existing);
+            for chunk_id in source_chunk_ids(record) {
+                if !sources.contains(&chunk_id) {
+                    sources.push(chunk_id);
+                }
+            }
+            record["source_chunk_ids"] = json!(sources);
+            merge_descriptions(&existing, record, description_field);
+            merge_evidence(&existing, record);
+        }
+        Ok(())
+    }
+
+    /// Records the LLM calls made to extract a chunk against its document
+    /// and upload batch. Failing to record them doesn't fail the chunk.
+    pub async fn record_extraction_usage(
+        &self,
+        doc_id: &str,
+        chunk_id: &str,
+        extraction: &ChunkExtraction,
+    ) -> UsageTotals {
+        let track_id = self.track_id(doc_id).await;
+        self.usage
+            .record_extraction(doc_id, chunk_id, track_id, extraction)
+            .await
+            .unwrap_or_else(|err| {
+                warn!(doc_id, chunk_id, error = %err, "failed to record LLM usage");
+                UsageTotals::default()
+            })
+    }
+
+    /// Records model calls made for `doc_id` other than successful chunk
+    /// extractions, such as summaries, embeddings and failed extraction
+    /// attempts. Failing to record them doesn't fail the caller.
+    pub async fn record_calls(
+        &self,
+        doc_id: &str,
+        chunk_id: Option<&str>,
+        kind: UsageKind,
+        calls: &[ModelCall],
+    ) {
+        if calls.is_empty() {
+            return;
+        }
+        let track_id = self.track_id(doc_id).await;
+        for call in calls {
+            if let Err(err) = self
+                .usage
+                .record_call(doc_id, chunk_id, track_id.clone(), kind, call)
+                .await
+            {
+                warn!(doc_id, error = %err, "failed to record LLM usage");
+            }
+        }
+    }
+
+    async fn track_id(&self, doc_id: &str) -> Option<String> {
+        match self.storages.doc_status.get_by_id(doc_id).await {
+            Ok(status) => status.and_then(|status| status.track_id),
+            Err(_) => None,
+        }
+    }
+
+    /// The extracted predicate, if the ontology defines it for the types of
+    /// the relation's endpoints in `entity_types`; otherwise the relation is
+    /// stored untyped. Placeholders have no type there and are unconstrained.
+    fn checked_predicate(
+        &self,
+        predicate: Option<&str>,
+        entity_types: &HashMap<String, String>,
+        source_id: &str,
+        target_id: &str,
+    ) -> Option<String> {
+        let predicate = predicate.filter(|predicate| !predicate.trim().is_empty())?;
+        let entity_type = |id: &str| entity_types.get(id).map(String::as_str);
+        match self.config.ontology.resolve_predicate(
+            predicate,
+            entity_type(source_id),
+            entity_type(target_id),
+        ) {
+            Ok(predicate) => Some(predicate.to_string()),
+            Err(err) => {
+                warn!(error = %err, source_id, target_id, "dropped relationship predicate");
+                None
+            }
+        }
+    }
+
+    /// Sets the description of each record from the descriptions collected
+    /// over all of its chunks, summarizing the ones over budget.
+    async fn consolidate_descriptions(
+        &self,
+        doc_id: &str,
+        records: &mut HashMap<String, Value>,
+        field: &str,
+    ) {
+        let mut calls = Vec::new();
+        for (id, record) in records.iter_mut() {
+            let subject = self.description_subject(record).await;
+            match self.descriptions.consolidate(&subject, record, field).await {
+                Ok(call) => calls.extend(call),
+                Err(err) => {
+                    warn!(error = %err, id = %id, "description summarization failed");
+                    calls.extend(ModelCall::from_error(&self.config.llm_model, &err));
+                }
+            }
+        }
+        self.record_calls(doc_id, None, UsageKind::DescriptionSummary, &calls)
+            .await;
+    }
+
+    /// What a record describes: the entity name, or "source -> target" for
+    /// relations, whose entities are already stored.
+    async fn description_subject(&self, record: &Value) -> String {
+        if let Some(name) = record.get("entity_name").and_then(Value::as_str) {
+            return name.to_string();
+        }
+        let mut names = Vec::new();
+        for key in ["source_entity_id", "target_entity_id"] {
+            let id = field_str(record, key);
+            let name = match self.storages.full_entities.get_by_id(&id).await {
+                Ok(Some(entity)) => field_str(&entity, "entity_name"),
+                _ => id,
+            };
+            names.push(name);
+        }
+        names.join(" -> ")
+    }
+
+    pub async fn persist_all(&self) -> StorageResult<()> {
+        self.storages.full_docs.sync_if_dirty().await?;
+        self.storages.text_chunks.sync_if_dirty().await?;
+        self.storages.full_entities.sync_if_dirty().await?;
+        self.storages.full_relations.sync_if_dirty().await?;
+        self.storages.llm_response_cache.sync_if_dirty().await?;
+        self.storages.llm_usage.sync_if_dirty().await?;
+        self.storages.doc_fingerprints.sync_if_dirty().await?;
+        self.storages.canonical_entities.sync_if_dirty().await?;
+        self.storages.entity_embeddings.sync_if_dirty().await?;
+        self.storages.entity_merges.sync_if_dirty().await?;
+        self.storages.doc_status.sync_if_dirty().await?;
+        Ok(())
+    }
+}
+
+/// Entity and relation records built from the extractions of a document's
+/// chunks, by LLM extraction and CSV mappings alike.
+struct ExtractedRecords {
+    doc_id: String,
+    doc_metadata: DocumentMetadata,
+    entities: HashMap<String, Value>,
+    relations: HashMap<String, Value>,
+    endpoints: EndpointResolver,
+    /// Ontology types of the extracted entities relations may refer to.
+    entity_types: HashMap<String, String>,
+}
+
+impl ExtractedRecords {
+    fn new(doc_id: &str, doc_metadata: DocumentMetadata, repair: RelationRepairConfig) -> Self {
+        Self {
+            doc_id: doc_id.to_string(),
+            doc_metadata,
+            entities: HashMap::new(),
+            relations: HashMap::new(),
+            endpoints: EndpointResolver::new(repair),
+            entity_types: HashMap::new(),
+        }
+    }
+
+    /// Ids of the entities sourced from `chunk_id`, sorted. Stored on the
+    /// chunk record so later chunks of the document can look them up.
+    fn entity_ids_of(&self, chunk_id: &str) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .entities
+            .iter()
+            .filter(|(_, entity)| source_chunk_ids(entity).iter().any(|id| id == chunk_id))
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Lets relations refer to an entity stored from another chunk of the
+    /// document.
+    fn add_stored_entity(&mut self, entity_id: &str, entity: &Value) {
+        self.endpoints
+            .add_entity(&field_str(entity, "entity_name"), entity_id);
+        if !entity["placeholder"].as_bool().unwrap_or(false) {
+            self.entity_types
+                .insert(entity_id.to_string(), field_str(entity, "entity_type"));
+        }
+    }
+}
+
+/// The chunk an extraction came from.
+struct SourceChunk<'a> {
+    id: &'a str,
+    order: usize,
+    content: &'a str,
+    span: Option<Span>,
+}
+
+fn add_source_chunk(record: &mut Value, chunk_id: &str) {
+    let mut sources = source_chunk_ids(record);
+    if !sources.iter().any(|id| id == chunk_id) {
+        sources.push(chunk_id.to_string());
+    }
+    record["source_chunk_ids"] = json!(sources);
+}
+
+/// Rebuilds a chunk from its stored record. Records missing a field the
+/// chunk needs are skipped.
+pub(crate) fn stored_chunk(chunk_id: String, record: &Value) -> Option<Chunk> {
+    let content = record.get("content")?.as_str()?.to_owned();
+    let order = record.get("chunk_order_index")?.as_u64()? as usize;
+    let token_count = record
+        .get("token")
+        .or_else(|| record.get("tokens"))?
+        .as_i64()?;
+    let heading = record
+        .get("heading")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let span = record
+        .get("span")
+        .and_then(|span| serde_json::from_value(span.clone()).ok());
+    Some(Chunk {
+        id: chunk_id,
+        content,
+        order,
+        token_count,
+        heading,
+        span,
+    })
+}
+
+/// Document id for `content`: a hash of the sanitized text.
+pub fn content_id(content: &str) -> String {
+    compute_mdhash_id(&sanitize_text(content), "doc-")
+}
+
+/// Hash of the current version of a stored document. Documents that were
+/// never re-ingested are identified by their content hash.
+/// Whether a document is queued or being extracted.
+pub(crate) fn in_progress(status: &DocProcessingStatus) -> bool {
+    matches!(status.status, DocStatus::PENDING | DocStatus::PROCESSING)
+}
+
+fn current_content_id(status: &DocProcessingStatus) -> Option<String> {
+    status
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get("content_id"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .or_else(|| status.id.clone())
+}
+
+/// Normalizes the bibliographic fields of caller-supplied metadata in place.
+/// Routes validate metadata before it gets here, so invalid fields are kept
+/// as given rather than rejected.
+fn normalize_metadata(metadata: Value) -> Value {
+    match DocumentMetadata::from_value(&metadata) {
+        Ok(fields) => fields.merge_into(Some(metadata)),
+        Err(_) => metadata,
+    }
+}
+
+/// Entity record for a relationship endpoint no extracted entity matched.
+fn placeholder_record(
+    placeholder: &Placeholder,
+    doc_id: &str,
+    doc_metadata: &DocumentMetadata,
+    chunk_id: &str,
+    chunk_order_index: usize,
+) -> Value {
+    json!({
+        "entity_name": placeholder.entity_name,
+        "entity_type": OTHER_ENTITY_TYPE,
+        "entity_description": "",
+        "placeholder": true,
+        "canonical_id": canonical_entity_id(&placeholder.entity_name, OTHER_ENTITY_TYPE),
+        "doc_id": doc_id,
+        "doc_metadata": doc_metadata,
+        "chunk_id": chunk_id,
+        "source_chunk_ids": [chunk_id],
+        "chunk_order_index": chunk_order_index,
+    })
+}
+
+fn field_str(record: &Value, key: &str) -> String {
+    record
+        .get(key)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Sets `key` on a metadata object, creating the object when needed.
+fn with_metadata_field(metadata: Option<Value>, key: &str, value: Value) -> Value {
+    let mut metadata = match metadata {
+        Some(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    metadata.insert(key.to_string(), value);
+    Value::Object(metadata)
+}
+
+pub(crate) fn generate_track_id(prefix: &str) -> String {
+    format!("{}-{}", prefix, Uuid::new_v4())
+}
+
+pub(crate) fn file_extension(file_path: &str) -> Option<String> {
+    std::path::Path::new(file_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(normalize_extension)
+}
+
+fn sanitize_text(input: &str) -> String {
+    input.replace('\r', "").trim().to_string()
+}
+
+fn summarize_content(content: &str) -> String {
+    const MAX_CHARS: usize = 200;
+    let trimmed = content.trim();
+    match trimmed.char_indices().nth(MAX_CHARS) {
+        Some((end, _)) => format!("{}…", &trimmed[..end]),
+        None => trimmed.to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DocumentInput {
+    pub content: String,
+    pub file_path: String,
+    /// Arbitrary caller-supplied fields stored on the document status.
+    pub metadata: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnqueueStatus {
+    New,
+    Duplicate,
+    /// Skipped because it is too similar to an existing document.
+    NearDuplicate,
+    /// Stored as a version of a similar document, without extraction.
+    Linked,
+    /// Replaced the content of the document stored under the same file path.
+    Updated,
+    /// Nothing was left after cleaning the content.
+    Empty,
+    /// A hook failed on the content; the error is recorded as a failed
+    /// document status.
+    Failed,
+    /// Not enqueued: the document stored under the same file path is still
+    /// queued or being extracted.
+    InProgress,
+}
+
+/// What became of a new version of a stored document.
+#[derive(Debug)]
+pub enum Reingestion {
+    /// Stored as the current version, with the chunks that changed.
+    Updated(ChunkDiff),
+    /// The content is unchanged, so nothing was re-ingested.
+    Unchanged,
+    /// The stored version is still queued or being extracted.
+    InProgress,
+    /// A hook failed on the new content; the error is recorded.
+    Failed,
+    /// Nothing was left after cleaning the new content.
+    Empty,
+}
+
+impl Reingestion {
+    pub fn status(&self) -> EnqueueStatus {
+        match self {
+            Self::Updated(_) => EnqueueStatus::Updated,
+            Self::Unchanged => EnqueueStatus::Duplicate,
+            Self::InProgress => EnqueueStatus::InProgress,
+            Self::Failed => EnqueueStatus::Failed,
+            Self::Empty => EnqueueStatus::Empty,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnqueuedDocument {
+    pub doc_id: Option<String>,
+    pub file_path: String,
+    pub status: EnqueueStatus,
+    pub near_duplicate_of: Option<String>,
+    pub similarity: Option<f32>,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        ai::schemas::{DocumentAbstract, ExtractedEntity, ExtractedRelationship, Modality},
+        storage::{JsonDocStatusConfig, JsonDocStatusStorage, JsonKvStorageConfig},
+    };
+
+    use super::super::{chunker::TokenizerChunker, extractor::Utf8DocumentExtractor};
+
+    /// Extracts every capitalized word of a chunk as an entity and relates
+    /// each one to the next.
+    pub(crate) struct CapitalizedWords;
+
+    #[async_trait]
+    impl EntityRelationshipExtractor for CapitalizedWords {
+        async fn extract_entities_and_relationships(
+            &self,
+            chunk: &Chunk,
+        ) -> Result<ChunkExtraction> {
+            let mut names: Vec<&str> = Vec::new();
+            for word in chunk.content.split_whitespace() {
+                let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+                if word.starts_with(char::is_uppercase) && !names.contains(&word) {
+                    names.push(word);
+                }
+            }
+            let entities = names
+                .iter()
+                .map(|name| ExtractedEntity {
+                    entity_name: name.to_string(),
+                    entity_type: "Gene".to_string(),
+                    entity_description: format!("{name} as seen in the text."),
+                    evidence: name.to_string(),
+                })
+                .collect();
+            let relationships = names
+                .windows(2)
+                .map(|pair| ExtractedRelationship {
+                    source_entity: pair[0].to_string(),
+                    target_entity: pair[1].to_string(),
+                    relationship_keywords: Vec::new(),
+                    relationship_description: format!("{} precedes {}.", pair[0], pair[1]),
+                    evidence: String::new(),
+                    confidence: None,
+                    modality: Modality::Asserted,
+                    hedge_cues: Vec::new(),
+                    predicate: None,
+                })
+                .collect();
+            Ok(ChunkExtraction {
+                result: EntitiesRelationships {
+                    entities,
+                    relationships,
+                },
+                ..ChunkExtraction::default()
+            })
+        }
+    }
+
+    /// A pipeline over fresh storages in `dir` that extracts with
+    /// [`CapitalizedWords`] and never calls a model.
+    pub(crate) async fn test_pipeline(dir: &TempDir, config: PipelineConfig) -> Pipeline {
+        let kv = |namespace: &str| {
+            Arc::new(JsonKvStorage::new(JsonKvStorageConfig {
+                working_dir: dir.path().into(),
+                namespace: namespace.into(),
+                workspace: None,
+            }))
+        };
+        let storages = Arc::new(AppStorages {
+            full_docs: kv("full_docs"),
+            text_chunks: kv("text_chunks"),
+            full_entities: kv("full_entities"),
+            full_relations: kv("full_relations"),
+            llm_response_cache: kv("llm_response_cache"),
+            llm_usage: kv("llm_usage"),
+            doc_fingerprints: kv("doc_fingerprints"),
+            canonical_entities: kv("canonical_entities"),
+            entity_embeddings: kv("entity_embeddings"),
+            entity_merges: kv("entity_merges"),
+            doc_status: Arc::new(JsonDocStatusStorage::new(JsonDocStatusConfig {
+                working_dir: dir.path().into(),
+                namespace: "doc_status".into(),
+                workspace: None,
+            })),
+        });
+        for storage in [
+            &storages.full_docs,
+            &storages.text_chunks,
+            &storages.full_entities,
+            &storages.full_relations,
+            &storages.llm_response_cache,
+            &storages.llm_usage,
+            &storages.doc_fingerprints,
+            &storages.canonical_entities,
+            &storages.entity_embeddings,
+            &storages.entity_merges,
+        ] {
+            storage.initialize().await.unwrap();
+        }
+        storages.doc_status.initialize().await.unwrap();
+
+        let doc_manager = DocumentManager::new(dir.path().join("input"), None, &[".txt", ".csv"])
+            .await
+            .unwrap();
+        let tokenizer: Arc<dyn Tokenizer> = Arc::new(TiktokenTokenizer::new().unwrap());
+        Pipeline::with_dependencies(
+            storages.clone(),
+            doc_manager.clone(),
+            config,
+            Arc::new(TokenizerChunker::new(tokenizer)),
+            Arc::new(Utf8DocumentExtractor::new(doc_manager.file_repo())),
+            Arc::new(CapitalizedWords),
+            DocStatusService::new(storages.doc_status.clone(), storages.docs_storage()),
+            ErrorReporter::new(storages.doc_status.clone()),
+        )
+    }
+
+    fn entity(name: &str) -> ExtractedEntity {
+        ExtractedEntity {
+            entity_name: name.to_string(),
+            entity_type: "Compound".to_string(),
+            entity_description: format!("{name} as seen in the text."),
+            evidence: String::new(),
+        }
+    }
+
+    fn relationship(source: &str, target: &str) -> ExtractedRelationship {
+        ExtractedRelationship {
+            source_entity: source.to_string(),
+            target_entity: target.to_string(),
+            relationship_keywords: Vec::new(),
+            relationship_description: format!("{source} relates to {target}."),
+            evidence: String::new(),
+            confidence: None,
+            modality: Modality::Asserted,
+            hedge_cues: Vec::new(),
+            predicate: None,
+        }
+    }
+
+    /// Enqueues `content` as one document split into a chunk per paragraph,
+    /// returning the document id and its chunk ids in order.
+    async fn enqueue_paragraphs(pipeline: &Pipeline, content: &str) -> (String, Vec<String>) {
+        let doc_id = pipeline
+            .enqueue_documents(
+                vec![DocumentInput {
+                    content: content.to_string(),
+                    file_path: "paper.txt".to_string(),
+                    metadata: None,
+                }],
+                "track-1",
+            )
+            .await
+            .unwrap()
+            .remove(0)
+            .doc_id
+            .unwrap();
+        let mut chunks: Vec<(u64, String)> = pipeline
+            .storages
+            .text_chunks
+            .get_all()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|(_, chunk)| chunk["full_doc_id"] == doc_id.as_str())
+            .map(|(chunk_id, chunk)| (chunk["chunk_order_index"].as_u64().unwrap(), chunk_id))
+            .collect();
+        chunks.sort();
+        (doc_id, chunks.into_iter().map(|(_, id)| id).collect())
+    }
+
+    fn paragraph_config() -> PipelineConfig {
+        PipelineConfig {
+            split_by_character: Some("\n\n".to_string()),
+            split_by_character_only: true,
+            ..PipelineConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn relations_resolve_to_entities_of_other_chunks_under_any_spelling() {
+        let dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, paragraph_config()).await;
+        let (doc_id, chunk_ids) = enqueue_paragraphs(
+            &pipeline,
+            "mTOR inhibitors such as rapamycin.\n\nMTOR-inhibitors act like rapamycin.",
+        )
+        .await;
+        assert_eq!(chunk_ids.len(), 2);
+
+        pipeline
+            .store_extraction(
+                &doc_id,
+                &chunk_ids[0],
+                0,
+                EntitiesRelationships {
+                    entities: vec![entity("mTOR Inhibitor"), entity("Rapamycin")],
+                    relationships: vec![relationship("mTOR Inhibitor", "Rapamycin")],
+                },
+            )
+            .await
+            .unwrap();
+        // the second chunk names the same edge differently and extracts
+        // neither of its entities
+        pipeline
+            .store_extraction(
+                &doc_id,
+                &chunk_ids[1],
+                1,
+                EntitiesRelationships {
+                    entities: Vec::new(),
+                    relationships: vec![relationship("MTOR-inhibitors", "rapamycin")],
+                },
+            )
+            .await
+            .unwrap();
+
+        let entities = pipeline.storages.full_entities.get_all().await.unwrap();
+        assert_eq!(entities.len(), 2);
+        assert!(
+            entities
+                .values()
+                .all(|entity| entity.get("placeholder").is_none())
+        );
+        let relations = pipeline.storages.full_relations.get_all().await.unwrap();
+        assert_eq!(relations.len(), 1);
+        let relation = relations.values().next().unwrap();
+        assert_eq!(relation["source_chunk_ids"], json!(chunk_ids));
+    }
+
+    #[tokio::test]
+    async fn relations_between_the_same_entities_differ_by_predicate_and_modality() {
+        let dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, paragraph_config()).await;
+        let (doc_id, chunk_ids) = enqueue_paragraphs(
+            &pipeline,
+            "Rapamycin inhibits MTOR.\n\nRapamycin activates MTOR but does not inhibit it.",
+        )
+        .await;
+        let mtor = ExtractedEntity {
+            entity_type: "Protein".to_string(),
+            ..entity("MTOR")
+        };
+        let typed = |predicate: &str, modality: Modality| ExtractedRelationship {
+            predicate: Some(predicate.to_string()),
+            modality,
+            ..relationship("Rapamycin", "MTOR")
+        };
+        for (order, relationships) in [
+            vec![typed("inhibits", Modality::Asserted)],
+            vec![
+                typed("activates", Modality::Asserted),
+                typed("inhibits", Modality::Negated),
+                typed("inhibits", Modality::Asserted),
+            ],
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            pipeline
+                .store_extraction(
+                    &doc_id,
+                    &chunk_ids[order],
+                    order,
+                    EntitiesRelationships {
+                        entities: vec![entity("Rapamycin"), mtor.clone()],
+                        relationships,
+                    },
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut relations: Vec<(String, String, usize)> = pipeline
+            .storages
+            .full_relations
+            .get_all()
+            .await
+            .unwrap()
+            .into_values()
+            .map(|relation| {
+                (
+                    relation["predicate"].as_str().unwrap().to_string(),
+                    relation["modality"].as_str().unwrap().to_string(),
+                    relation["source_chunk_ids"].as_array().unwrap().len(),
+                )
+            })
+            .collect();
+        relations.sort();
+        assert_eq!(
+            relations,
+            [
+                ("activates".to_string(), "asserted".to_string(), 1),
+                ("inhibits".to_string(), "asserted".to_string(), 2),
+                ("inhibits".to_string(), "negated".to_string(), 1),
+            ]
+        );
+    }
+
+    /// Rejects relations extracted from chunks that mention a retraction.
+    struct RetractedRelations {
+        retracted_chunk: String,
+    }
+
+    #[async_trait]
+    impl PipelineHook for RetractedRelations {
+        fn name(&self) -> &str {
+            "retracted_relations"
+        }
+
+        async fn before_upsert(
+            &self,
+            _doc_id: &str,
+            kind: RecordKind,
+            records: &mut HashMap<String, Value>,
+        ) -> Result<()> {
+            let retracted = records
+                .values()
+                .any(|record| source_chunk_ids(record).contains(&self.retracted_chunk));
+            if kind == RecordKind::Relations && retracted {
+                return Err(anyhow!("relations come from a retracted paragraph"));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn a_hook_failing_on_a_later_chunk_stops_storing_the_document() {
+        let dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, paragraph_config()).await;
+        let (doc_id, chunk_ids) = enqueue_paragraphs(
+            &pipeline,
+            "Rapamycin inhibits Mtor.\n\nRetracted: Metformin activates Ampk.",
+        )
+        .await;
+        let pipeline = pipeline.with_hook(Arc::new(RetractedRelations {
+            retracted_chunk: chunk_ids[1].clone(),
+        }));
+        let extraction = |source: &str, target: &str| EntitiesRelationships {
+            entities: vec![entity(source), entity(target)],
+            relationships: vec![relationship(source, target)],
+        };
+
+        pipeline
+            .store_extraction(&doc_id, &chunk_ids[0], 0, extraction("Rapamycin", "Mtor"))
+            .await
+            .unwrap();
+        let err = pipeline
+            .store_extraction(&doc_id, &chunk_ids[1], 1, extraction("Metformin", "Ampk"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("retracted_relations"));
+        let entity_names = || async {
+            let mut names: Vec<String> = pipeline
+                .storages
+                .full_entities
+                .get_all()
+                .await
+                .unwrap()
+                .into_values()
+                .map(|entity| field_str(&entity, "entity_name"))
+                .collect();
+            names.sort();
+            names
+        };
+        // the relations hook failed before the chunk's entities were stored
+        assert_eq!(entity_names().await, ["Mtor", "Rapamycin"]);
+
+        // as the scheduler does, then a late result for the failed document
+        let status = pipeline
+            .storages
+            .doc_status
+            .get_by_id(&doc_id)
+            .await
+            .unwrap()
+            .unwrap();
+        pipeline
+            .status_service
+            .mark_failed(&doc_id, &status, &err)
+            .await
+            .unwrap();
+        pipeline
+            .store_extraction(
+                &doc_id,
+                &chunk_ids[1],
+                1,
+                extraction("Spermidine", "Autophagy"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(entity_names().await, ["Mtor", "Rapamycin"]);
+        assert_eq!(
+            pipeline
+                .storages
+                .full_relations
+                .get_all()
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    /// Counts its calls, each taking long enough for callers to overlap.
+    #[derive(Default)]
+    struct SlowSummarizer {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl DocumentSummarizer for SlowSummarizer {
+        async fn summarize(
+            &self,
+            _content: &str,
+            _max_keywords: usize,
+        ) -> Result<DocumentAbstract> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(DocumentAbstract {
+                summary: "Rapamycin inhibits MTOR.".to_string(),
+                keywords: vec!["rapamycin".to_string()],
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_summarize_a_document_once() {
+        let dir = TempDir::new().unwrap();
+        let config = PipelineConfig {
+            document_summary: DocumentSummaryConfig {
+                enabled: true,
+                ..DocumentSummaryConfig::default()
+            },
+            ..PipelineConfig::default()
+        };
+        let summarizer = Arc::new(SlowSummarizer::default());
+        let pipeline = test_pipeline(&dir, config)
+            .await
+            .with_document_summarizer(summarizer.clone());
+        let (doc_id, _) = enqueue_paragraphs(&pipeline, "Rapamycin inhibits MTOR.").await;
+
+        tokio::join!(
+            pipeline.summarize_document(&doc_id),
+            pipeline.summarize_document(&doc_id),
+        );
+
+        assert_eq!(summarizer.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            pipeline.document_context(&doc_id).await.unwrap(),
+            "Summary: Rapamycin inhibits MTOR.\nKeywords: rapamycin"
+        );
+        assert!(pipeline.summary_locks.lock().await.is_empty());
+    }
+
+    #[derive(Default)]
+    struct CountingDescriptions {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl DescriptionSummarizer for CountingDescriptions {
+        async fn summarize(
+            &self,
+            subject: &str,
+            descriptions: &[String],
+            _max_tokens: usize,
+        ) -> Result<String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("{subject}: {} descriptions", descriptions.len()))
+        }
+    }
+
+    #[tokio::test]
+    async fn descriptions_are_summarized_once_the_document_is_stored() {
+        let dir = TempDir::new().unwrap();
+        let config = PipelineConfig {
+            descriptions: DescriptionConfig { token_budget: 8 },
+            ..paragraph_config()
+        };
+        let summarizer = Arc::new(CountingDescriptions::default());
+        let pipeline = test_pipeline(&dir, config)
+            .await
+            .with_description_summarizer(summarizer.clone());
+        let (doc_id, chunk_ids) = enqueue_paragraphs(
+            &pipeline,
+            "Rapamycin inhibits MTOR.\n\nRapamycin extends lifespan in mice.",
+        )
+        .await;
+        for (order, (chunk_id, description)) in chunk_ids
+            .iter()
+            .zip([
+                "Inhibits MTOR in yeast and mice",
+                "Extends lifespan when fed late in life",
+            ])
+            .enumerate()
+        {
+            let extraction = EntitiesRelationships {
+                entities: vec![ExtractedEntity {
+                    entity_description: description.to_string(),
+                    ..entity("Rapamycin")
+                }],
+                relationships: Vec::new(),
+            };
+            pipeline
+                .store_extraction(&doc_id, chunk_id, order, extraction)
+                .await
+                .unwrap();
+        }
+        let rapamycin = || async {
+            pipeline
+                .storages
+                .full_entities
+                .get_all()
+                .await
+                .unwrap()
+                .into_values()
+                .next()
+                .unwrap()
+        };
+        // chunk results only join descriptions
+        assert_eq!(summarizer.calls.load(Ordering::SeqCst), 0);
+        assert_eq!(
+            rapamycin().await["entity_description"],
+            "Inhibits MTOR in yeast and mice\nExtends lifespan when fed late in life"
+        );
+
+        pipeline.finish_document(&doc_id).await;
+        pipeline.finish_document(&doc_id).await;
+        assert_eq!(summarizer.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            rapamycin().await["entity_description"],
+            "Rapamycin: 2 descriptions"
+        );
+    }
+
+    /// Moves every mention to another canonical entity while summarizing,
+    /// as a merge finishing meanwhile would.
+    struct MergingDescriptions {
+        storages: Arc<AppStorages>,
+    }
+
+    #[async_trait]
+    impl DescriptionSummarizer for MergingDescriptions {
+        async fn summarize(
+            &self,
+            subject: &str,
+            descriptions: &[String],
+            _max_tokens: usize,
+        ) -> Result<String> {
+            let mut mentions = self.storages.full_entities.get_all().await?;
+            for mention in mentions.values_mut() {
+                mention["canonical_id"] = json!("canonical-merged");
+            }
+            self.storages.full_entities.upsert(mentions).await?;
+            Ok(format!("{subject}: {} descriptions", descriptions.len()))
+        }
+    }
+
+    #[tokio::test]
+    async fn summaries_keep_changes_made_to_the_records_meanwhile() {
+        let dir = TempDir::new().unwrap();
+        let config = PipelineConfig {
+            descriptions: DescriptionConfig { token_budget: 8 },
+            ..paragraph_config()
+        };
+        let pipeline = test_pipeline(&dir, config).await;
+        let pipeline = pipeline.with_description_summarizer(Arc::new(MergingDescriptions {
+            storages: pipeline.storages.clone(),
+        }));
+        let (doc_id, chunk_ids) = enqueue_paragraphs(
+            &pipeline,
+            "Rapamycin inhibits MTOR.\n\nRapamycin extends lifespan in mice.",
+        )
+        .await;
+        for (order, (chunk_id, description)) in chunk_ids
+            .iter()
+            .zip([
+                "Inhibits MTOR in yeast and mice",
+                "Extends lifespan when fed late in life",
+            ])
+            .enumerate()
+        {
+            let extraction = EntitiesRelationships {
+                entities: vec![ExtractedEntity {
+                    entity_description: description.to_string(),
+                    ..entity("Rapamycin")
+                }],
+                relationships: Vec::new(),
+            };
+            pipeline
+                .store_extraction(&doc_id, chunk_id, order, extraction)
+                .await
+                .unwrap();
+        }
+
+        pipeline.finish_document(&doc_id).await;
+        let rapamycin = pipeline
+            .storages
+            .full_entities
+            .get_all()
+            .await
+            .unwrap()
+            .into_values()
+            .next()
+            .unwrap();
+        assert_eq!(rapamycin["entity_description"], "Rapamycin: 2 descriptions");
+        assert_eq!(rapamycin["canonical_id"], "canonical-merged");
+    }
+
+    #[tokio::test]
+    async fn text_without_a_path_gets_one_per_document() {
+        let dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, PipelineConfig::default()).await;
+        let text = |content: &str| DocumentInput {
+            content: content.to_string(),
+            file_path: String::new(),
+            metadata: None,
+        };
+
+        let outcomes = pipeline
+            .enqueue_documents(
+                vec![
+                    text("Rapamycin inhibits MTOR."),
+                    text("Metformin activates AMPK."),
+                ],
+                "insert-test",
+            )
+            .await
+            .unwrap();
+
+        for outcome in &outcomes {
+            let doc_id = outcome.doc_id.as_deref().unwrap();
+            assert_eq!(outcome.file_path, format!("text/{doc_id}"));
+            let stored = pipeline
+                .find_existing_document(&outcome.file_path)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(stored.id.as_deref(), Some(doc_id));
+        }
+    }
+
+    /// Drops draft markers, and fails on text that is still a PDF.
+    struct DraftMarkers;
+
+    #[async_trait]
+    impl PipelineHook for DraftMarkers {
+        fn name(&self) -> &str {
+            "draft_markers"
+        }
+
+        async fn before_chunking(&self, _file_path: &str, content: String) -> Result<String> {
+            if content.starts_with("%PDF") {
+                return Err(anyhow!("content is not text"));
+            }
+            Ok(content.replace("[draft] ", ""))
+        }
+    }
+
+    #[tokio::test]
+    async fn hooks_shape_the_content_that_is_identified_and_fail_only_their_document() {
+        let dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, PipelineConfig::default())
+            .await
+            .with_hook(Arc::new(DraftMarkers));
+        let doc = |content: &str, file_path: &str| DocumentInput {
+            content: content.to_string(),
+            file_path: file_path.to_string(),
+            metadata: None,
+        };
+
+        let outcomes = pipeline
+            .enqueue_documents(
+                vec![
+                    doc("%PDF-1.7 binary", "scan.txt"),
+                    doc("[draft] Rapamycin inhibits MTOR.", "paper.txt"),
+                ],
+                "track-1",
+            )
+            .await
+            .unwrap();
+        assert_eq!(outcomes[0].status, EnqueueStatus::Failed);
+        assert_eq!(outcomes[1].status, EnqueueStatus::New);
+        let failed = pipeline
+            .find_existing_document("scan.txt")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.status, DocStatus::FAILED);
+
+        let unchanged = pipeline
+            .find_unchanged_document("paper.txt", "Rapamycin inhibits MTOR.")
+            .await
+            .unwrap();
+        assert_eq!(unchanged.unwrap().id, outcomes[1].doc_id);
+        assert!(
+            pipeline
+                .find_unchanged_document("paper.txt", "Rapamycin inhibits AMPK.")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn reingesting_a_changed_paragraph_only_extracts_that_paragraph() {
+        let dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, paragraph_config()).await;
+        let doc = |content: &str| DocumentInput {
+            content: content.to_string(),
+            file_path: "paper.txt".to_string(),
+            metadata: None,
+        };
+        pipeline
+            .enqueue_documents(
+                vec![doc(
+                    "Rapamycin inhibits Mtor.\n\nMetformin activates Ampk.\n\nSirtuins need Nad.",
+                )],
+                "track-1",
+            )
+            .await
+            .unwrap();
+        pipeline.process_queue().await.unwrap();
+        let existing = pipeline
+            .find_existing_document("paper.txt")
+            .await
+            .unwrap()
+            .unwrap();
+        let entity_names = || async {
+            let mut names: Vec<String> = pipeline
+                .storages
+                .full_entities
+                .get_all()
+                .await
+                .unwrap()
+                .into_values()
+                .map(|entity| entity["entity_name"].as_str().unwrap().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert!(entity_names().await.contains(&"Metformin".to_string()));
+        let doc_id = existing.id.clone().unwrap();
+        let full_docs = &pipeline.storages.full_docs;
+        let mut full_doc = full_docs.get_by_id(&doc_id).await.unwrap().unwrap();
+        full_doc["abstract"] = json!("Rapamycin and metformin target nutrient sensing.");
+        full_doc["keywords"] = json!(["rapamycin", "metformin"]);
+        full_doc["source_url"] = json!("https://example.org/paper");
+        full_docs
+            .upsert(HashMap::from([(doc_id.clone(), full_doc)]))
+            .await
+            .unwrap();
+
+        let diff = pipeline
+            .reingest_document(
+                existing,
+                doc("Rapamycin inhibits Mtor.\n\nSpermidine induces Autophagy.\n\nSirtuins need Nad."),
+                "track-2",
+            )
+            .await
+            .unwrap();
+        let Reingestion::Updated(diff) = diff else {
+            panic!("expected a new version, got {diff:?}");
+        };
+        assert_eq!(diff.reused.len(), 2);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.removed.len(), 1);
+
+        let full_doc = full_docs.get_by_id(&doc_id).await.unwrap().unwrap();
+        assert!(full_doc["content"].as_str().unwrap().contains("Spermidine"));
+        assert_eq!(full_doc["source_url"], "https://example.org/paper");
+        assert!(full_doc.get("abstract").is_none());
+        assert!(full_doc.get("keywords").is_none());
+
+        let chunks = &pipeline.storages.text_chunks;
+        for chunk_id in &diff.reused {
+            let chunk = chunks.get_by_id(chunk_id).await.unwrap().unwrap();
+            assert_eq!(chunk["status"], "Success");
+            assert!(chunk.get("extraction_passes").is_some());
+        }
+        let added = chunks.get_by_id(&diff.added[0]).await.unwrap().unwrap();
+        assert_eq!(added["status"], "Pending");
+        assert_eq!(
+            entity_names().await,
+            ["Mtor", "Nad", "Rapamycin", "Sirtuins"]
+        );
+
+        // a result for the removed chunk arriving late stays retired
+        let late = EntitiesRelationships {
+            entities: vec![entity("Metformin")],
+            relationships: Vec::new(),
+        };
+        pipeline
+            .store_extraction(&doc_id, &diff.removed[0], 1, late)
+            .await
+            .unwrap();
+        assert_eq!(
+            entity_names().await,
+            ["Mtor", "Nad", "Rapamycin", "Sirtuins"]
+        );
+
+        let updated = pipeline
+            .find_existing_document("paper.txt")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated.status, DocStatus::PENDING);
+        assert_eq!(version_count(updated.metadata.as_ref()), 2);
+    }
+
+    #[tokio::test]
+    async fn new_versions_a_hook_fails_on_are_recorded_and_moved_out_of_the_input() {
+        let dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, paragraph_config())
+            .await
+            .with_hook(Arc::new(DraftMarkers));
+        let input_dir = pipeline.document_manager().input_dir();
+        let file_path = input_dir.join("paper.txt");
+        tokio::fs::write(&file_path, "Rapamycin inhibits Mtor.")
+            .await
+            .unwrap();
+        let first = pipeline
+            .enqueue_file(file_path.clone(), "track-1", None)
+            .await
+            .unwrap();
+        assert_eq!(first.status, EnqueueStatus::New);
+        pipeline.process_queue().await.unwrap();
+
+        tokio::fs::write(&file_path, "%PDF-1.7 binary")
+            .await
+            .unwrap();
+        let outcome = pipeline
+            .enqueue_file(file_path.clone(), "track-2", None)
+            .await
+            .unwrap();
+        assert_eq!(outcome.status, EnqueueStatus::Failed);
+        assert!(!file_path.exists());
+
+        // the stored document is still the one found under its path
+        let stored = pipeline
+            .find_existing_document("paper.txt")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.id, first.doc_id);
+        assert_eq!(stored.status, DocStatus::PROCESSED);
+        let failures = pipeline
+            .storages
+            .doc_status
+            .docs_by_status(&DocStatus::FAILED)
+            .await
+            .unwrap();
+        assert_eq!(failures.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn documents_in_progress_are_not_reingested() {
+        let dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, paragraph_config()).await;
+        let doc = |content: &str| DocumentInput {
+            content: content.to_string(),
+            file_path: "paper.txt".to_string(),
+            metadata: None,
+        };
+        pipeline
+            .enqueue_documents(vec![doc("Rapamycin inhibits Mtor.")], "track-1")
+            .await
+            .unwrap();
+        let existing = pipeline
+            .find_existing_document("paper.txt")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let reingestion = pipeline
+            .reingest_document(
+                existing.clone(),
+                doc("Metformin activates Ampk."),
+                "track-2",
+            )
+            .await
+            .unwrap();
+        assert_eq!(reingestion.status(), EnqueueStatus::InProgress);
+        let stored = pipeline
+            .find_existing_document("paper.txt")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.status, DocStatus::PENDING);
+        assert_eq!(stored.chunks_list, existing.chunks_list);
+        assert_eq!(version_count(stored.metadata.as_ref()), 0);
+    }
+
+    #[tokio::test]
+    async fn submitted_texts_under_a_known_path_are_new_versions() {
+        let dir = TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, paragraph_config()).await;
+        let doc = |content: &str, file_path: &str| DocumentInput {
+            content: content.to_string(),
+            file_path: file_path.to_string(),
+            metadata: None,
+        };
+        let first = pipeline
+            .submit_documents(
+                vec![doc("Rapamycin inhibits Mtor.", "paper.txt")],
+                "track-1",
+            )
+            .await
+            .unwrap();
+        assert_eq!(first[0].status, EnqueueStatus::New);
+        pipeline.process_queue().await.unwrap();
+
+        let outcomes = pipeline
+            .submit_documents(
+                vec![
+                    doc("Spermidine induces Autophagy.", ""),
+                    doc("Metformin activates Ampk.", "paper.txt"),
+                    doc("Sirtuins need Nad.", "notes.txt"),
+                ],
+                "track-2",
+            )
+            .await
+            .unwrap();
+        let statuses: Vec<EnqueueStatus> = outcomes.iter().map(|o| o.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                EnqueueStatus::New,
+                EnqueueStatus::Updated,
+                EnqueueStatus::New
+            ]
+        );
+        assert_eq!(outcomes[1].doc_id, first[0].doc_id);
+
+        let stored = pipeline
+            .find_existing_document("paper.txt")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.id, first[0].doc_id);
+        assert_eq!(version_count(stored.metadata.as_ref()), 2);
+    }
+
+    const ABSTRACT: &str = "Rapamycin extends lifespan in genetically heterogeneous mice. \
+        Treatment started late in life increased median and maximal lifespan in both sexes, \
+        suggesting that MTOR signalling regulates ageing in mammals.";
+
+    /// Enqueues `ABSTRACT` and then a copy with one more sentence under
+    /// `policy`, returning the pipeline, the original's id and the outcome
+    /// of the copy.
+    async fn enqueue_near_duplicate(
+        dir: &TempDir,
+        policy: NearDuplicatePolicy,
+    ) -> (Pipeline, String, EnqueuedDocument) {
+        let config = PipelineConfig {
+            near_duplicate: NearDuplicateConfig {
+                policy,
+                ..NearDuplicateConfig::default()
+            },
+            ..PipelineConfig::default()
+        };
+        let pipeline = test_pipeline(dir, config).await;
+        let doc = |content: String, file_path: &str| DocumentInput {
+            content,
+            file_path: file_path.to_string(),
+            metadata: None,
+        };
+        let original = pipeline
+            .enqueue_documents(vec![doc(ABSTRACT.to_string(), "paper.txt")], "track-1")
+            .await
+            .unwrap()
+            .remove(0);
+        let copy = pipeline
+            .enqueue_documents(
+                vec![doc(format!("{ABSTRACT} Preprint."), "preprint.txt")],
+                "track-2",
+            )
+            .await
+            .unwrap()
+            .remove(0);
+        (pipeline, original.doc_id.unwrap(), copy)
+    }
+
+    #[tokio::test]
+    async fn skipped_near_duplicates_are_recorded_on_the_original() {
+        let dir = TempDir::new().unwrap();
+        let (pipeline, original_id, copy) =
+            enqueue_near_duplicate(&dir, NearDuplicatePolicy::Skip).await;
+
+        assert_eq!(copy.status, EnqueueStatus::NearDuplicate);
+        assert_eq!(
+            copy.near_duplicate_of.as_deref(),
+            Some(original_id.as_str())
+        );
+        let copy_id = copy.doc_id.unwrap();
+        let doc_status = &pipeline.storages.doc_status;
+        assert!(doc_status.get_by_id(&copy_id).await.unwrap().is_none());
+
+        let original = doc_status.get_by_id(&original_id).await.unwrap().unwrap();
+        let duplicates = &original.metadata.unwrap()["near_duplicates"];
+        assert_eq!(duplicates[0]["doc_id"], copy_id);
+        assert_eq!(duplicates[0]["file_path"], "preprint.txt");
+        assert_eq!(duplicates[0]["track_id"], "track-2");
+    }
+
+    #[tokio::test]
+    async fn linked_near_duplicates_are_stored_as_versions_without_chunks() {
+        let dir = TempDir::new().unwrap();
+        let (pipeline, original_id, copy) =
+            enqueue_near_duplicate(&dir, NearDuplicatePolicy::Link).await;
+
+        assert_eq!(copy.status, EnqueueStatus::Linked);
+        let copy_id = copy.doc_id.unwrap();
+        let doc_status = &pipeline.storages.doc_status;
+        let linked = doc_status.get_by_id(&copy_id).await.unwrap().unwrap();
+        assert_eq!(linked.status, DocStatus::PROCESSED);
+        assert!(linked.chunks_list.unwrap_or_default().is_empty());
+        assert_eq!(
+            linked.metadata.unwrap()["near_duplicate"]["doc_id"],
+            original_id.as_str()
+        );
+
+        let original = doc_status.get_by_id(&original_id).await.unwrap().unwrap();
+        assert_eq!(
+            original.metadata.unwrap()["linked_versions"],
+            json!([copy_id])
+        );
+    }
+
+    #[tokio::test]
+    async fn ingested_near_duplicates_are_queued_with_the_match_recorded() {
+        let dir = TempDir::new().unwrap();
+        let (pipeline, original_id, copy) =
+            enqueue_near_duplicate(&dir, NearDuplicatePolicy::Ingest).await;
+
+        assert_eq!(copy.status, EnqueueStatus::New);
+        let copy = pipeline
+            .storages
+            .doc_status
+            .get_by_id(&copy.doc_id.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(copy.status, DocStatus::PENDING);
+        let near_duplicate = &copy.metadata.unwrap()["near_duplicate"];
+        assert_eq!(near_duplicate["doc_id"], original_id.as_str());
+        assert_eq!(near_duplicate["policy"], "ingest");
+    }
 }
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    canonical::normalize_entity_name, ontology::OTHER_ENTITY_TYPE, utils::compute_mdhash_id,
+};
+
+/// Shortest name key considered for fuzzy matching; shorter names such as
+/// gene symbols differ by a single character too often.
+const MIN_FUZZY_KEY_LEN: usize = 4;
+
+/// How relationship endpoints that don't exactly name an extracted entity
+/// are matched to one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelationRepairConfig {
+    /// Minimum normalized Damerau-Levenshtein similarity of two names for a
+    /// fuzzy match.
+    pub fuzzy_threshold: f64,
+    /// Create a placeholder entity for an endpoint nothing matches instead
+    /// of dropping the relation.
+    pub create_placeholders: bool,
+}
+
+impl Default for RelationRepairConfig {
+    fn default() -> Self {
+        Self {
+            fuzzy_threshold: 0.85,
+            create_placeholders: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointMatch {
+    Exact,
+    /// Same name after normalizing case, punctuation and plurals.
+    Normalized,
+    /// An abbreviation of the entity name, or the other way round.
+    Abbreviation,
+    Fuzzy,
+}
+
+/// What happened to the relations of one chunk whose endpoints did not
+/// exactly match an extracted entity. Stored on the chunk record.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepairCounts {
+    /// Relations kept by matching an endpoint by normalized, abbreviated or
+    /// fuzzy name.
+    pub repaired: u32,
+    /// Relations kept by creating a placeholder endpoint.
+    pub placeholders: u32,
+    pub dropped: u32,
+}
+
+/// Entity created for a relationship endpoint that matched nothing. It is
+/// stored with the `Other` type and marked as a placeholder.
+#[derive(Debug, Clone)]
+pub struct Placeholder {
+    pub entity_id: String,
+    pub entity_name: String,
+}
+
+pub fn placeholder_entity_id(doc_id: &str, name: &str) -> String {
+    compute_mdhash_id(&format!("{doc_id}:{name}:{OTHER_ENTITY_TYPE}"), "entity-")
+}
+
+/// Matches relationship endpoints against the entities extracted so far.
+pub struct EndpointResolver {
+    config: RelationRepairConfig,
+    exact: HashMap<String, String>,
+    /// Normalized name keys; `None` marks keys shared by different entities.
+    normalized: HashMap<String, Option<String>>,
+    abbreviations: HashMap<String, Option<String>>,
+    placeholders: Vec<Placeholder>,
+}
+
+impl EndpointResolver {
+    pub fn new(config: RelationRepairConfig) -> Self {
+        Self {
+            config,
+            exact: HashMap::new(),
+            normalized: HashMap::new(),
+            abbreviations: HashMap::new(),
+            placeholders: Vec::new(),
+        }
+    }
+
+    pub fn add_entity(&mut self, name: &str, entity_id: &str) {
+        self.exact.insert(name.to_string(), entity_id.to_string());
+        insert_unique(&mut self.normalized, name_key(name), entity_id);
+        for abbreviation in abbreviation_keys(name) {
+            insert_unique(&mut self.abbreviations, abbreviation, entity_id);
+        }
+    }
+
+    /// Entity id an endpoint name refers to, if any extracted entity matches.
+    pub fn resolve(&self, name: &str) -> Option<(String, EndpointMatch)> {
+        if let Some(id) = self.exact.get(name) {
+            return Some((id.clone(), EndpointMatch::Exact));
+        }
+        let key = name_key(name);
+        if key.is_empty() {
+            return None;
+        }
+        if let Some(Some(id)) = self.normalized.get(&key) {
+            return Some((id.clone(), EndpointMatch::Normalized));
+        }
+        if let Some(Some(id)) = self.abbreviations.get(&key) {
+            return Some((id.clone(), EndpointMatch::Abbreviation));
+        }
+        for abbreviation in abbreviation_keys(name) {
+            if let Some(Some(id)) = self.normalized.get(&abbreviation) {
+                return Some((id.clone(), EndpointMatch::Abbreviation));
+            }
+        }
+        self.fuzzy(&key)
+            .map(|id| (id.to_string(), EndpointMatch::Fuzzy))
+    }
+
+    /// Best fuzzy match for a name key, unless two entities match equally.
+    fn fuzzy(&self, key: &str) -> Option<&str> {
+        if key.chars().count() < MIN_FUZZY_KEY_LEN {
+            return None;
+        }
+        let mut best: Option<(f64, &str)> = None;
+        let mut tied = false;
+        for (candidate, id) in &self.normalized {
+            let Some(id) = id else {
+                continue;
+            };
+            if candidate.chars().count() < MIN_FUZZY_KEY_LEN {
+                continue;
+            }
+            let score = strsim::normalized_damerau_levenshtein(key, candidate);
+            if score < self.config.fuzzy_threshold {
+                continue;
+            }
+            match best {
+                Some((best_score, best_id)) if score == best_score => tied |= best_id != id,
+                Some((best_score, _)) if score < best_score => {}
+                _ => {
+                    best = Some((score, id));
+                    tied = false;
+                }
+            }
+        }
+        best.filter(|_| !tied).map(|(_, id)| id)
+    }
+
+    /// Resolves both endpoints of a relationship and counts the outcome.
+    /// Returns `None` when the relation has to be dropped.
+    pub fn resolve_relation(
+        &mut self,
+        doc_id: &str,
+        source: &str,
+        target: &str,
+        counts: &mut RepairCounts,
+    ) -> Option<(String, String)> {
+        let resolved = [self.resolve(source), self.resolve(target)];
+        let unmatched = resolved.iter().any(Option::is_none);
+        if unmatched
+            && (!self.config.create_placeholders
+                || source.trim().is_empty()
+                || target.trim().is_empty())
+        {
+            counts.dropped += 1;
+            return None;
+        }
+
+        let repaired = resolved
+            .iter()
+            .flatten()
+            .any(|(_, matched)| *matched != EndpointMatch::Exact);
+        let [source_id, target_id] = [(source, &resolved[0]), (target, &resolved[1])].map(
+            |(name, resolved)| match resolved {
+                Some((id, _)) => id.clone(),
+                None => self.add_placeholder(doc_id, name),
+            },
+        );
+        if unmatched {
+            counts.placeholders += 1;
+        } else if repaired {
+            counts.repaired += 1;
+        }
+        Some((source_id, target_id))
+    }
+
+    fn add_placeholder(&mut self, doc_id: &str, name: &str) -> String {
+        let name = name.trim();
+        let entity_id = placeholder_entity_id(doc_id, name);
+        self.add_entity(name, &entity_id);
+        self.placeholders.push(Placeholder {
+            entity_id: entity_id.clone(),
+            entity_name: name.to_string(),
+        });
+        entity_id
+    }
+
+    /// Placeholders created since the last call.
+    pub fn take_placeholders(&mut self) -> Vec<Placeholder> {
+        std::mem::take(&mut self.placeholders)
+    }
+}
+
+fn insert_unique(keys: &mut HashMap<String, Option<String>>, key: String, entity_id: &str) {
+    if key.is_empty() {
+        return;
+    }
+    keys.entry(key)
+        .and_modify(|existing| {
+            if existing.as_deref() != Some(entity_id) {
+                *existing = None;
+            }
+        })
+        .or_insert_with(|| Some(entity_id.to_string()));
+}
+
+/// Lowercase alphanumeric form of a name with a trailing plural dropped, so
+/// "mTOR inhibitors", "MTOR-inhibitor" and "mtor inhibitor" share a key.
+fn name_key(name: &str) -> String {
+    let compact: String = normalize_entity_name(name)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+    singular(&compact).to_string()
+}
+
+fn singular(key: &str) -> &str {
+    if key.chars().count() <= 3 || key.ends_with("ss") || key.ends_with("us") {
+        return key;
+    }
+    key.strip_suffix('s').unwrap_or(key)
+}
+
+/// Keys a name may be abbreviated to or from: the text in parentheses, the
+/// name without it, and the initials of a name of several words, as in
+/// "Nicotinamide Adenine Dinucleotide (NAD+)".
+fn abbreviation_keys(name: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut base = name.to_string();
+    if let (Some(open), Some(close)) = (name.find('('), name.rfind(')'))
+        && open < close
+    {
+        keys.push(name_key(&name[open + 1..close]));
+        base = format!("{}{}", &name[..open], &name[close + 1..]);
+        keys.push(name_key(&base));
+    }
+    let words: Vec<&str> = base
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.len() >= 2 {
+        keys.push(
+            words
+                .iter()
+                .filter_map(|word| word.chars().next())
+                .flat_map(char::to_lowercase)
+                .collect(),
+        );
+    }
+    keys.retain(|key| !key.is_empty() && key != &name_key(name));
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver(config: RelationRepairConfig) -> EndpointResolver {
+        let mut resolver = EndpointResolver::new(config);
+        resolver.add_entity("Rapamycin", "e-rapamycin");
+        resolver.add_entity("mTOR Inhibitor", "e-inhibitor");
+        resolver.add_entity("Nicotinamide Adenine Dinucleotide (NAD+)", "e-nad");
+        resolver.add_entity("Caloric Restriction", "e-cr");
+        resolver
+    }
+
+    #[test]
+    fn endpoints_match_by_normalized_abbreviated_and_fuzzy_name() {
+        let resolver = resolver(RelationRepairConfig::default());
+        let matched = |name: &str| resolver.resolve(name);
+
+        assert_eq!(
+            matched("Rapamycin"),
+            Some(("e-rapamycin".to_string(), EndpointMatch::Exact))
+        );
+        assert_eq!(
+            matched("MTOR-inhibitors"),
+            Some(("e-inhibitor".to_string(), EndpointMatch::Normalized))
+        );
+        assert_eq!(
+            matched("NAD+"),
+            Some(("e-nad".to_string(), EndpointMatch::Abbreviation))
+        );
+        assert_eq!(
+            matched("CR"),
+            Some(("e-cr".to_string(), EndpointMatch::Abbreviation))
+        );
+        assert_eq!(
+            matched("Rapamycine"),
+            Some(("e-rapamycin".to_string(), EndpointMatch::Fuzzy))
+        );
+        assert_eq!(matched("Metformin"), None);
+    }
+
+    #[test]
+    fn unmatched_endpoints_become_placeholders_or_are_dropped() {
+        let mut counts = RepairCounts::default();
+        let mut with_placeholders = resolver(RelationRepairConfig::default());
+        let (_, target) = with_placeholders
+            .resolve_relation("doc-1", "rapamycin", "Metformin", &mut counts)
+            .unwrap();
+        assert_eq!(target, placeholder_entity_id("doc-1", "Metformin"));
+        assert_eq!(with_placeholders.take_placeholders().len(), 1);
+        // the placeholder now matches later relations exactly
+        with_placeholders
+            .resolve_relation("doc-1", "Rapamycin", "Metformin", &mut counts)
+            .unwrap();
+
+        let mut dropping = resolver(RelationRepairConfig {
+            create_placeholders: false,
+            ..RelationRepairConfig::default()
+        });
+        assert!(
+            dropping
+                .resolve_relation("doc-1", "Rapamycin", "Metformin", &mut counts)
+                .is_none()
+        );
+        assert_eq!(
+            counts,
+            RepairCounts {
+                repaired: 0,
+                placeholders: 1,
+                dropped: 1,
+            }
+        );
+    }
+}
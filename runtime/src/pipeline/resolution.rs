@@ -0,0 +1,518 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::info;
+
+use crate::{
+    ai::{
+        embeddings::{Embedder, cosine_similarity},
+        responses::ModelCall,
+    },
+    storage::KvStorage,
+};
+
+use super::{canonical::string_set, pipeline::AppStorages, utils::compute_mdhash_id};
+
+/// Embedding-based entity resolution run after new canonical entities are
+/// created. Candidates are only looked for within the same entity type.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResolutionConfig {
+    pub enabled: bool,
+    /// Cosine similarity at or above which entities are merged without review.
+    pub auto_merge_threshold: f32,
+    /// Cosine similarity at or above which a candidate is queued for review.
+    pub review_threshold: f32,
+    /// Candidates considered per new entity, best first.
+    pub max_candidates: usize,
+}
+
+impl Default for ResolutionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            auto_merge_threshold: 0.95,
+            review_threshold: 0.85,
+            max_candidates: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStatus {
+    Pending,
+    Merged,
+    Rejected,
+}
+
+/// A merge candidate and, once decided, the decision. Stored in
+/// `entity_merges` so every merge, automatic or reviewed, can be audited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeRecord {
+    /// Canonical entity that is merged away.
+    pub source_id: String,
+    /// Canonical entity that survives.
+    pub target_id: String,
+    pub source_name: String,
+    pub target_name: String,
+    pub entity_type: String,
+    pub similarity: f32,
+    pub status: MergeStatus,
+    /// `auto` or `review`.
+    #[serde(default)]
+    pub decided_by: Option<String>,
+    #[serde(default)]
+    pub decided_at: Option<String>,
+}
+
+pub fn merge_id(source_id: &str, target_id: &str) -> String {
+    compute_mdhash_id(&format!("{source_id}:{target_id}"), "merge-")
+}
+
+pub struct EntityResolver {
+    storages: Arc<AppStorages>,
+    embedder: Arc<dyn Embedder>,
+    config: ResolutionConfig,
+}
+
+impl EntityResolver {
+    pub fn new(
+        storages: Arc<AppStorages>,
+        embedder: Arc<dyn Embedder>,
+        config: ResolutionConfig,
+    ) -> Self {
+        Self {
+            storages,
+            embedder,
+            config,
+        }
+    }
+
+    /// Embeds newly created canonical entities and compares them with the
+    /// existing ones of the same type. The best candidate above the auto-merge
+    /// threshold is merged into; other candidates above the review threshold
+    /// are queued. Returns the merge records written and the embedding calls
+    /// made.
+    pub async fn resolve(
+        &self,
+        canonical_ids: &[String],
+    ) -> Result<(Vec<MergeRecord>, Vec<ModelCall>)> {
+        let records = self
+            .storages
+            .canonical_entities
+            .get_by_ids(canonical_ids)
+            .await?;
+        let new_entities: Vec<(String, Value)> = canonical_ids
+            .iter()
+            .cloned()
+            .zip(records)
+            .filter_map(|(id, record)| record.map(|record| (id, record)))
+            .filter(|(_, record)| record.get("merged_into").is_none())
+            .collect();
+        if new_entities.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        let texts: Vec<String> = new_entities
+            .iter()
+            .map(|(_, record)| embedding_text(record))
+            .collect();
+        let (vectors, calls) = self.embedder.embed_with_usage(&texts).await?;
+
+        let mut known: Vec<(String, String, Vec<f32>)> = self
+            .storages
+            .entity_embeddings
+            .get_all()
+            .await?
+            .into_iter()
+            .filter_map(|(id, record)| {
+                let entity_type = str_field(&record, "entity_type");
+                let vector = serde_json::from_value(record.get("vector")?.clone()).ok()?;
+                Some((id, entity_type, vector))
+            })
+            .collect();
+
+        let mut decisions = Vec::new();
+        for ((canonical_id, record), vector) in new_entities.into_iter().zip(vectors) {
+            let entity_type = str_field(&record, "entity_type");
+            let mut candidates: Vec<(&str, f32)> = known
+                .iter()
+                .filter(|(id, known_type, _)| {
+                    id != &canonical_id && known_type.eq_ignore_ascii_case(&entity_type)
+                })
+                .map(|(id, _, known_vector)| {
+                    (id.as_str(), cosine_similarity(&vector, known_vector))
+                })
+                .filter(|(_, similarity)| *similarity >= self.config.review_threshold)
+                .collect();
+            candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+            candidates.truncate(self.config.max_candidates);
+
+            let mut merged_into = None;
+            for (target_id, similarity) in candidates {
+                let target_name = self
+                    .storages
+                    .canonical_entities
+                    .get_by_id(target_id)
+                    .await?
+                    .map(|target| str_field(&target, "entity_name"))
+                    .unwrap_or_default();
+                let mut decision = MergeRecord {
+                    source_id: canonical_id.clone(),
+                    target_id: target_id.to_string(),
+                    source_name: str_field(&record, "entity_name"),
+                    target_name,
+                    entity_type: entity_type.clone(),
+                    similarity,
+                    status: MergeStatus::Pending,
+                    decided_by: None,
+                    decided_at: None,
+                };
+                if merged_into.is_none() && similarity >= self.config.auto_merge_threshold {
+                    self.merge(&canonical_id, target_id).await?;
+                    decision.status = MergeStatus::Merged;
+                    decision.decided_by = Some("auto".to_string());
+                    decision.decided_at = Some(chrono::Utc::now().to_rfc3339());
+                    merged_into = Some(target_id.to_string());
+                } else if merged_into.is_some() {
+                    // the entity no longer exists on its own
+                    continue;
+                }
+                self.record(&decision).await?;
+                decisions.push(decision);
+            }
+
+            if merged_into.is_none() {
+                self.storages
+                    .entity_embeddings
+                    .upsert(HashMap::from([(
+                        canonical_id.clone(),
+                        json!({ "entity_type": entity_type, "vector": vector }),
+                    )]))
+                    .await?;
+                known.push((canonical_id, entity_type, vector));
+            }
+        }
+        Ok((decisions, calls))
+    }
+
+    /// Lists merge records, optionally only those with `status`.
+    pub async fn merges(&self, status: Option<MergeStatus>) -> Result<Vec<(String, MergeRecord)>> {
+        let mut merges: Vec<(String, MergeRecord)> = self
+            .storages
+            .entity_merges
+            .get_all()
+            .await?
+            .into_iter()
+            .filter_map(|(id, value)| Some((id, serde_json::from_value(value).ok()?)))
+            .filter(|(_, record): &(String, MergeRecord)| {
+                status.is_none_or(|status| record.status == status)
+            })
+            .collect();
+        merges.sort_by(|a, b| b.1.similarity.total_cmp(&a.1.similarity));
+        Ok(merges)
+    }
+
+    /// Applies a reviewer's decision on a pending candidate.
+    pub async fn decide(&self, id: &str, approve: bool) -> Result<MergeRecord, DecideError> {
+        let mut record: MergeRecord = self
+            .storages
+            .entity_merges
+            .get_by_id(id)
+            .await?
+            .map(serde_json::from_value)
+            .transpose()
+            .context("invalid merge record")?
+            .ok_or_else(|| DecideError::NotFound(format!("merge candidate '{id}' not found")))?;
+        if record.status != MergeStatus::Pending {
+            return Err(DecideError::Conflict(format!(
+                "merge candidate '{id}' was already decided ({:?})",
+                record.status
+            )));
+        }
+        if approve {
+            self.merge(&record.source_id, &record.target_id).await?;
+        }
+        record.status = if approve {
+            MergeStatus::Merged
+        } else {
+            MergeStatus::Rejected
+        };
+        record.decided_by = Some("review".to_string());
+        record.decided_at = Some(chrono::Utc::now().to_rfc3339());
+        self.record(&record).await?;
+        Ok(record)
+    }
+
+    /// Merges canonical entity `source_id` into `target_id`. Mentions move to
+    /// the target and the source is left as a redirect, so later mentions of
+    /// the same name land on the target too.
+    async fn merge(&self, source_id: &str, target_id: &str) -> Result<(), DecideError> {
+        let canonical = &self.storages.canonical_entities;
+        let ids = [source_id.to_string(), target_id.to_string()];
+        let [Some(source), Some(mut target)]: [Option<Value>; 2] = canonical
+            .get_by_ids(&ids)
+            .await?
+            .try_into()
+            .map_err(|_| anyhow!("unexpected canonical lookup result"))?
+        else {
+            return Err(DecideError::Conflict(
+                "canonical entity no longer exists".to_string(),
+            ));
+        };
+        for (id, record) in [(source_id, &source), (target_id, &target)] {
+            if let Some(into) = record.get("merged_into").and_then(Value::as_str) {
+                return Err(DecideError::Conflict(format!(
+                    "'{id}' was already merged into '{into}'"
+                )));
+            }
+        }
+
+        let moved = string_set(&source, "mention_ids");
+        let mut mention_ids = string_set(&target, "mention_ids");
+        mention_ids.extend(moved.iter().cloned());
+        let mut doc_ids = string_set(&target, "doc_ids");
+        doc_ids.extend(string_set(&source, "doc_ids"));
+        let mut aliases = string_set(&target, "aliases");
+        aliases.insert(str_field(&source, "entity_name"));
+        aliases.extend(string_set(&source, "aliases"));
+        target["mention_count"] = json!(mention_ids.len());
+        target["mention_ids"] = json!(mention_ids);
+        target["doc_ids"] = json!(doc_ids);
+        target["aliases"] = json!(aliases);
+
+        let redirect = json!({
+            "entity_name": source.get("entity_name"),
+            "entity_type": source.get("entity_type"),
+            "merged_into": target_id,
+        });
+        canonical
+            .upsert(HashMap::from([
+                (target_id.to_string(), target),
+                (source_id.to_string(), redirect),
+            ]))
+            .await?;
+
+        let moved: Vec<String> = moved.into_iter().collect();
+        let mentions = self.storages.full_entities.get_by_ids(&moved).await?;
+        let updates: HashMap<String, Value> = moved
+            .into_iter()
+            .zip(mentions)
+            .filter_map(|(id, mention)| {
+                let mut mention = mention?;
+                mention["canonical_id"] = json!(target_id);
+                Some((id, mention))
+            })
+            .collect();
+        self.storages.full_entities.upsert(updates).await?;
+        self.storages
+            .entity_embeddings
+            .delete(&[source_id.to_string()])
+            .await?;
+        info!(source = %source_id, target = %target_id, "merged canonical entities");
+        Ok(())
+    }
+
+    async fn record(&self, record: &MergeRecord) -> Result<()> {
+        self.storages
+            .entity_merges
+            .upsert(HashMap::from([(
+                merge_id(&record.source_id, &record.target_id),
+                serde_json::to_value(record)?,
+            )]))
+            .await
+    }
+}
+
+/// Why a merge could not be applied.
+#[derive(Debug)]
+pub enum DecideError {
+    /// There is no merge candidate with the given id.
+    NotFound(String),
+    /// The candidate was already decided, or one of its entities was merged
+    /// or removed since it was queued.
+    Conflict(String),
+    Storage(anyhow::Error),
+}
+
+impl fmt::Display for DecideError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(message) | Self::Conflict(message) => f.write_str(message),
+            Self::Storage(err) => write!(f, "{err:#}"),
+        }
+    }
+}
+
+impl std::error::Error for DecideError {}
+
+impl From<anyhow::Error> for DecideError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::Storage(err)
+    }
+}
+
+/// Text embedded for an entity: its name, plus its description when known.
+fn embedding_text(record: &Value) -> String {
+    let name = str_field(record, "entity_name");
+    match record
+        .get("entity_description")
+        .and_then(Value::as_str)
+        .filter(|description| !description.trim().is_empty())
+    {
+        Some(description) => format!("{name}: {description}"),
+        None => name,
+    }
+}
+
+fn str_field(record: &Value, key: &str) -> String {
+    record
+        .get(key)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        pipeline::canonical::{Mention, canonical_entity_id, link_mentions},
+        storage::{JsonDocStatusConfig, JsonDocStatusStorage, JsonKvStorage, JsonKvStorageConfig},
+    };
+
+    /// Embeds "Alzheimer's disease" and "AD" to nearly the same vector.
+    struct FakeEmbedder;
+
+    #[async_trait]
+    impl Embedder for FakeEmbedder {
+        async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+            Ok(inputs
+                .iter()
+                .map(|text| match text.as_str() {
+                    "Alzheimer's disease" => vec![1.0, 0.0, 0.0],
+                    "AD" => vec![0.99, 0.05, 0.0],
+                    "Dementia" => vec![0.9, 0.4, 0.0],
+                    _ => vec![0.0, 0.0, 1.0],
+                })
+                .collect())
+        }
+    }
+
+    fn storages(dir: &TempDir) -> Arc<AppStorages> {
+        let kv = |namespace: &str| {
+            Arc::new(JsonKvStorage::new(JsonKvStorageConfig {
+                working_dir: dir.path().into(),
+                namespace: namespace.into(),
+                workspace: None,
+            }))
+        };
+        Arc::new(AppStorages {
+            full_docs: kv("full_docs"),
+            text_chunks: kv("text_chunks"),
+            full_entities: kv("full_entities"),
+            full_relations: kv("full_relations"),
+            llm_response_cache: kv("llm_response_cache"),
+            llm_usage: kv("llm_usage"),
+            doc_fingerprints: kv("doc_fingerprints"),
+            canonical_entities: kv("canonical_entities"),
+            entity_embeddings: kv("entity_embeddings"),
+            entity_merges: kv("entity_merges"),
+            doc_status: Arc::new(JsonDocStatusStorage::new(JsonDocStatusConfig {
+                working_dir: dir.path().into(),
+                namespace: "doc_status".into(),
+                workspace: None,
+            })),
+        })
+    }
+
+    async fn add(storages: &AppStorages, name: &str, mention_id: &str) -> String {
+        let outcome = link_mentions(
+            &storages.canonical_entities,
+            &[Mention {
+                mention_id: mention_id.to_string(),
+                entity_name: name.to_string(),
+                entity_type: "Disease".to_string(),
+                entity_description: String::new(),
+                doc_id: "doc-1".to_string(),
+                normalization: None,
+            }],
+        )
+        .await
+        .unwrap();
+        storages
+            .full_entities
+            .upsert(HashMap::from([(
+                mention_id.to_string(),
+                json!({ "entity_name": name, "canonical_id": outcome.canonical_ids[mention_id] }),
+            )]))
+            .await
+            .unwrap();
+        outcome.canonical_ids[mention_id].clone()
+    }
+
+    #[tokio::test]
+    async fn close_entities_merge_and_borderline_ones_wait_for_review() {
+        let dir = TempDir::new().unwrap();
+        let storages = storages(&dir);
+        let resolver = EntityResolver::new(
+            storages.clone(),
+            Arc::new(FakeEmbedder),
+            ResolutionConfig {
+                enabled: true,
+                ..ResolutionConfig::default()
+            },
+        );
+
+        let alzheimers = add(&storages, "Alzheimer's disease", "m1").await;
+        resolver
+            .resolve(std::slice::from_ref(&alzheimers))
+            .await
+            .unwrap();
+
+        let ad = add(&storages, "AD", "m2").await;
+        let (decisions, _) = resolver.resolve(std::slice::from_ref(&ad)).await.unwrap();
+        assert_eq!(decisions.len(), 1);
+        assert_eq!(decisions[0].status, MergeStatus::Merged);
+        let mention = storages
+            .full_entities
+            .get_by_id("m2")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(mention["canonical_id"], json!(alzheimers));
+
+        // later mentions of the merged name follow the redirect
+        add(&storages, "ad", "m3").await;
+        let target = storages
+            .canonical_entities
+            .get_by_id(&alzheimers)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(target["mention_count"], 3);
+        assert_ne!(canonical_entity_id("AD", "Disease"), alzheimers);
+
+        let dementia = add(&storages, "Dementia", "m4").await;
+        let (decisions, _) = resolver.resolve(&[dementia]).await.unwrap();
+        assert_eq!(decisions[0].status, MergeStatus::Pending);
+
+        let pending = resolver.merges(Some(MergeStatus::Pending)).await.unwrap();
+        let decided = resolver.decide(&pending[0].0, false).await.unwrap();
+        assert_eq!(decided.status, MergeStatus::Rejected);
+        assert!(matches!(
+            resolver.decide(&pending[0].0, true).await,
+            Err(DecideError::Conflict(_))
+        ));
+        assert!(matches!(
+            resolver.decide("merge-unknown", true).await,
+            Err(DecideError::NotFound(_))
+        ));
+    }
+}
@@ -1,14 +1,17 @@
 use super::{
-    chunker::{Chunk, ChunkConfig},
-    pipeline::{AppStorages, Pipeline},
+    chunker::Chunk,
+    pipeline::{AppStorages, Pipeline, stored_chunk},
+    usage::UsageKind,
     utils::compute_mdhash_id,
 };
 use crate::{
-    ai::schemas::EntitiesRelationships, pipeline::utils::chunk_to_chunk_state, storage::KvStorage,
+    ai::{responses::ModelCall, schemas::EntitiesRelationships},
+    pipeline::utils::chunk_to_chunk_state,
+    storage::{DocStatus, KvStorage},
 };
 use anyhow::{Ok, Result, anyhow};
 use chrono::{DateTime, Utc};
-use serde_json::{self as serde_json, Value, json};
+use serde_json::{Value, json};
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     result::Result::{Err as StdErr, Ok as StdOk},
@@ -26,6 +29,30 @@ use tracing::{debug, error, warn};
 
 use crate::AppState;
 
+/// Sizing and timing knobs for the [`Scheduler`].
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    pub max_inflight: u8,
+    pub queue_capacity: u32,
+    /// Number of concurrent extraction workers.
+    pub workers: usize,
+    pub tick_interval: Duration,
+    /// Retries per chunk before it is marked as failed.
+    pub chunk_max_retries: u8,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            max_inflight: 10,
+            queue_capacity: 5,
+            workers: 20,
+            tick_interval: Duration::from_secs(10),
+            chunk_max_retries: 20,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Scheduler {
     pub queue: Arc<Mutex<Queue>>,
@@ -34,12 +61,12 @@ pub struct Scheduler {
     result_rx: Arc<Mutex<UnboundedReceiver<JobResult>>>,
     pipeline: Arc<Pipeline>,
     storage: Arc<AppStorages>,
+    config: SchedulerConfig,
 }
 
 impl Scheduler {
     pub fn new(
-        max_inflight: u8,
-        capacity: u32,
+        config: SchedulerConfig,
         work_tx: UnboundedSender<JobDispatch>,
         result_rx: Arc<Mutex<UnboundedReceiver<JobResult>>>,
         pipeline: Arc<Pipeline>,
@@ -47,21 +74,23 @@ impl Scheduler {
         work_rx: Arc<Mutex<UnboundedReceiver<JobDispatch>>>,
         result_tx: UnboundedSender<JobResult>,
     ) -> Self {
-        let queue = Arc::new(Mutex::new(Queue::new(capacity)));
+        let queue = Arc::new(Mutex::new(Queue::new(config.queue_capacity)));
+        let workers = config.workers;
 
         let scheduler = Scheduler {
             queue,
-            dispatcher: Dispatcher::new(work_tx, max_inflight),
+            dispatcher: Dispatcher::new(work_tx, config.max_inflight),
             result_rx,
             pipeline: pipeline.clone(),
             storage,
+            config,
         };
         Worker::spawn_pool(
             pipeline.clone(),
             Arc::new(scheduler.clone()),
             work_rx,
             result_tx,
-            20,
+            workers,
         ); // spawn `n` concurrent ER extraction workers
         // tokio::spawn(async move { worker.handle().await });
         scheduler
@@ -125,6 +154,31 @@ impl Scheduler {
                     }
                 }
             };
+            // a failing hook fails the document, not the scheduler
+            if let Err(err) = self
+                .pipeline
+                .store_extraction(
+                    &job_result.doc_id,
+                    &job_result.chunk_id,
+                    job_result.chunk_order_index,
+                    er,
+                )
+                .await
+            {
+                error!(error = %err, doc_id = %job_result.doc_id, chunk_id = %job_result.chunk_id, "failed to store extraction");
+                if let Some(doc) = self
+                    .pipeline
+                    .storages
+                    .doc_status
+                    .get_by_id(&job_result.doc_id)
+                    .await?
+                {
+                    self.pipeline
+                        .status_service
+                        .mark_failed(&job_result.doc_id, &doc, &err)
+                        .await?;
+                }
+            }
             {
                 let mut guard = self.queue.lock().await;
                 let job_id = if let Some(job) = guard.jobs_map.get(&job_result.job_id) {
@@ -151,99 +205,29 @@ impl Scheduler {
                             .iter()
                             .map(|chunk| chunk.chunk_id.clone())
                             .collect::<Vec<_>>();
+                        // documents failed by a hook stay failed, and a new
+                        // version queued since waits for its own chunks
                         if let Some(doc) = self
                             .pipeline
                             .storages
                             .doc_status
                             .get_by_id(&job_result.doc_id)
                             .await?
+                            .filter(|doc| doc.status == DocStatus::PROCESSING)
                         {
                             self.pipeline
                                 .status_service
                                 .mark_processed(&job_result.doc_id, &doc, &chunk_ids)
                                 .await?;
+                            // summaries and resolution call models, which
+                            // would hold up the results of other documents
+                            let pipeline = self.pipeline.clone();
+                            let doc_id = job_result.doc_id.clone();
+                            tokio::spawn(async move { pipeline.finish_document(&doc_id).await });
                         }
                     }
                 }
             }
-            let entities = er.entities;
-            let relationships = er.relationships;
-            let mut entities_to_upsert: HashMap<String, Value> = HashMap::new();
-            let mut relationships_to_upsert: HashMap<String, Value> = HashMap::new();
-            let mut entity_name_to_entity_id_mapping: HashMap<String, String> = HashMap::new();
-            for entity in entities {
-                let entity_id = compute_mdhash_id(
-                    &format!(
-                        "{}:{}:{}",
-                        &job_result.doc_id,
-                        &entity.entity_name,
-                        &entity.entity_type.as_str()
-                    ),
-                    "entity-",
-                );
-
-                entities_to_upsert.insert(
-                    entity_id.clone(),
-                    json!({
-                        "entity_name": entity.entity_name,
-                        "entity_type": entity.entity_type,
-                        "entity_description": entity.entity_description,
-                        "doc_id": job_result.doc_id,
-                        "chunk_id": job_result.chunk_id,
-                        "chunk_order_index": job_result.chunk_order_index
-                    }),
-                );
-                entity_name_to_entity_id_mapping.insert(entity.entity_name, entity_id);
-            }
-
-            for relationship in relationships {
-                let relation_id = compute_mdhash_id(
-                    &format!(
-                        "{}:{}:{}",
-                        &job_result.doc_id,
-                        &relationship.source_entity,
-                        &relationship.target_entity
-                    ),
-                    "rel-",
-                );
-                let maybe_source_entity_id =
-                    entity_name_to_entity_id_mapping.get(&relationship.source_entity);
-                let maybe_target_entity_id =
-                    entity_name_to_entity_id_mapping.get(&relationship.target_entity);
-                if let (Some(source_entity_id), Some(target_entity_id)) =
-                    (maybe_source_entity_id, maybe_target_entity_id)
-                {
-                    relationships_to_upsert.insert(
-                        relation_id,
-                        json!({
-                            "source_entity_id": source_entity_id,
-                            "target_entity_id": target_entity_id,
-                            "relationship_keywords": relationship.relationship_keywords,
-                            "relationship_description": relationship.relationship_description,
-                            "doc_id": job_result.doc_id,
-                            "chunk_id": job_result.chunk_id,
-                        }),
-                    );
-                }
-            }
-
-            if !entities_to_upsert.is_empty() {
-                self.pipeline
-                    .storages
-                    .full_entities
-                    .upsert(entities_to_upsert)
-                    .await?;
-            }
-
-            if !relationships_to_upsert.is_empty() {
-                self.pipeline
-                    .storages
-                    .full_relations
-                    .upsert(relationships_to_upsert)
-                    .await?;
-            }
-
-            self.pipeline.persist_all().await?;
         }
         Ok(())
     }
@@ -253,7 +237,8 @@ impl Scheduler {
             let mut guard = self.queue.lock().await;
             if let Some(job) = guard.peek() {
                 let chunks = self.get_pending_chunks_for_doc(&job.doc_id).await?;
-                let chunks_state = chunk_to_chunk_state(chunks, job.doc_id.clone());
+                let chunks_state =
+                    chunk_to_chunk_state(chunks, job.doc_id.clone(), self.config.chunk_max_retries);
                 job.chunks = chunks_state;
             }
             guard.peek().cloned()
@@ -265,7 +250,8 @@ impl Scheduler {
                 .iter()
                 .map(|chunk| chunk.id.clone())
                 .collect::<Vec<String>>();
-            let chunks_state = chunk_to_chunk_state(chunks, job.doc_id.clone());
+            let chunks_state =
+                chunk_to_chunk_state(chunks, job.doc_id.clone(), self.config.chunk_max_retries);
             debug!("Made {} chunk(s)", chunks_state.len());
             {
                 let mut guard = self.queue.lock().await;
@@ -283,19 +269,28 @@ impl Scheduler {
                         .await?;
                 }
             }
-            for chunk in job.chunks.iter().cloned() {
-                self.dispatcher.work_tx.send(JobDispatch {
-                    job_id: job.job_id.clone(),
-                    chunk,
-                })?;
-            }
+            // the abstract is context for every chunk, so it is written
+            // first, off the scheduler loop
+            let pipeline = self.pipeline.clone();
+            let work_tx = self.dispatcher.work_tx.clone();
+            tokio::spawn(async move {
+                pipeline.summarize_document(&job.doc_id).await;
+                for chunk in job.chunks {
+                    if let StdErr(err) = work_tx.send(JobDispatch {
+                        job_id: job.job_id.clone(),
+                        chunk,
+                    }) {
+                        error!(error = %err, doc_id = %job.doc_id, "failed to dispatch chunk");
+                    }
+                }
+            });
 
             // if let Err(_) = self.dispatcher.work_tx.send(job).await {}
         } else {
             debug!("no job queued")
         }
 
-        sleep(Duration::new(10, 0)).await;
+        sleep(self.config.tick_interval).await;
         Ok(())
     }
 
@@ -316,50 +311,11 @@ impl Scheduler {
             .collect();
         let vec = pending_chunks
             .into_iter()
-            .filter_map(|(chunk_id, value)| {
-                let content = value.get("content")?.as_str()?.to_owned();
-                let order = value.get("chunk_order_index")?.as_u64()? as usize;
-                let token_count_field = value.get("token").or_else(|| value.get("tokens"))?;
-                let token_count = token_count_field.as_i64()?;
-                Some(Chunk {
-                    id: chunk_id,
-                    content,
-                    order,
-                    token_count,
-                })
-            })
+            .filter_map(|(chunk_id, value)| stored_chunk(chunk_id, &value))
             .collect::<Vec<_>>();
 
         Ok(vec)
     }
-
-    async fn make_chunks(&self, job: &Job) -> Result<Vec<Chunk>> {
-        debug!("Making chunks for {}", job.doc_id);
-        let content_value = self
-            .pipeline
-            .storages
-            .full_docs
-            .get_by_id(&job.doc_id)
-            .await?
-            .ok_or_else(|| anyhow!("document missing"))?;
-
-        debug!("Got content value {}", job.job_id);
-        let content = content_value
-            .get("content")
-            .and_then(Value::as_str)
-            .ok_or_else(|| anyhow!("document content field missing"))?;
-
-        debug!("Got content {}", job.job_id);
-        let chunk_config = ChunkConfig {
-            max_tokens: self.pipeline.config.chunk_size,
-            overlap_tokens: self.pipeline.config.chunk_overlap,
-            split_by_character: self.pipeline.config.split_by_character.clone(),
-            split_by_character_only: self.pipeline.config.split_by_character_only,
-        };
-        let chunks = self.pipeline.chunker.chunk(content, &chunk_config)?;
-        debug!("Exiting make_chunks {}", job.job_id);
-        Ok(chunks)
-    }
 }
 
 #[derive(Clone)]
@@ -430,21 +386,30 @@ impl Worker {
                                 }
                             };
 
+                            let context =
+                                pipeline.document_context(&job_dispatch.chunk.doc_id).await;
                             let result = pipeline
                                 .entity_relationship_extractor
-                                .extract_entities_and_relationships(&Chunk {
-                                    id: job_dispatch.chunk.chunk_id.clone(),
-                                    content: job_dispatch.chunk.content.clone(),
-                                    order: 0,
-                                    token_count: 0,
-                                })
+                                .extract_with_context(
+                                    &Chunk {
+                                        id: job_dispatch.chunk.chunk_id.clone(),
+                                        content: job_dispatch.chunk.content.clone(),
+                                        order: 0,
+                                        token_count: 0,
+                                        heading: job_dispatch.chunk.heading.clone(),
+                                        span: None,
+                                    },
+                                    context.as_deref(),
+                                )
                                 .await;
                             match result {
-                                StdOk(entity_relationships) => {
+                                StdOk(extraction) => {
+                                    let entity_relationships = extraction.result.clone();
                                     debug!(
-                                        "Extracted {} entities and {} relationships",
+                                        "Extracted {} entities and {} relationships in {} passes",
                                         entity_relationships.entities.len(),
-                                        entity_relationships.relationships.len()
+                                        entity_relationships.relationships.len(),
+                                        extraction.passes.len()
                                     );
                                     if let StdOk(Some(mut chunk_record)) = pipeline
                                         .storages
@@ -453,6 +418,17 @@ impl Worker {
                                         .await
                                     {
                                         chunk_record["status"] = Value::String("Success".into());
+                                        chunk_record["extraction_passes"] =
+                                            json!(extraction.passes);
+                                        chunk_record["usage"] = json!(
+                                            pipeline
+                                                .record_extraction_usage(
+                                                    &job_dispatch.chunk.doc_id,
+                                                    &job_dispatch.chunk.chunk_id,
+                                                    &extraction,
+                                                )
+                                                .await
+                                        );
                                         let mut update = HashMap::new();
                                         update.insert(
                                             job_dispatch.chunk.chunk_id.clone(),
@@ -483,6 +459,19 @@ impl Worker {
                                     for (depth, err) in err.chain().skip(1).enumerate() {
                                         error!(depth=%depth, error=%err, chunk_id=%job_dispatch.chunk.chunk_id, "caused by");
                                     }
+                                    // a failed attempt is billed whether or not it is retried
+                                    if let Some(call) =
+                                        ModelCall::from_error(&pipeline.config.llm_model, &err)
+                                    {
+                                        pipeline
+                                            .record_calls(
+                                                &job_dispatch.chunk.doc_id,
+                                                Some(&job_dispatch.chunk.chunk_id),
+                                                UsageKind::Extraction,
+                                                &[call],
+                                            )
+                                            .await;
+                                    }
 
                                     if let StdOk(Some(mut chunk_record)) = pipeline
                                         .storages
@@ -719,6 +708,7 @@ pub struct ChunkState {
     pub chunk_status: ChunkStatus,
     pub chunk_order_index: usize,
     pub content: String,
+    pub heading: Option<String>,
     pub error: Option<String>,
     pub output: Option<EntitiesRelationships>,
     pub max_retries: u8,
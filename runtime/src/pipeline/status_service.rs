@@ -17,6 +17,7 @@ pub struct PendingDocument {
     pub file_path: String,
     pub track_id: String,
     pub created_at: String,
+    pub metadata: Option<serde_json::Value>,
 }
 
 pub struct DocStatusService {
@@ -61,7 +62,7 @@ impl DocStatusService {
                     file_path: Some(doc.file_path),
                     track_id: Some(doc.track_id),
                     chunks_list: Some(vec![]),
-                    metadata: None,
+                    metadata: doc.metadata,
                     error_msg: None,
                 },
             );
@@ -90,7 +91,7 @@ impl DocStatusService {
                 updated_at: Some(now),
                 file_path: status.file_path.clone(),
                 track_id: status.track_id.clone(),
-                chunks_list: Some(chunk_ids.to_vec()),
+                chunks_list: Some(merge_chunk_ids(status, chunk_ids)),
                 metadata: status.metadata.clone(),
                 error_msg: None,
             },
@@ -118,7 +119,7 @@ impl DocStatusService {
                 updated_at: Some(now),
                 file_path: status.file_path.clone(),
                 track_id: status.track_id.clone(),
-                chunks_list: Some(chunk_ids.to_vec()),
+                chunks_list: Some(merge_chunk_ids(status, chunk_ids)),
                 metadata: status.metadata.clone(),
                 error_msg: None,
             },
@@ -155,3 +156,15 @@ impl DocStatusService {
         self.doc_status.upsert(payload).await
     }
 }
+
+/// Keeps the chunks already recorded for the document, so processing only the
+/// new chunks of a re-ingested version doesn't truncate the list.
+fn merge_chunk_ids(status: &DocProcessingStatus, chunk_ids: &[String]) -> Vec<String> {
+    let mut merged = status.chunks_list.clone().unwrap_or_default();
+    for chunk_id in chunk_ids {
+        if !merged.contains(chunk_id) {
+            merged.push(chunk_id.clone());
+        }
+    }
+    merged
+}
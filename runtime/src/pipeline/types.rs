@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+use crate::{ai::schemas::Modality, storage::DocumentMetadata};
+
+use super::{descriptions::SourcedDescription, evidence::Evidence, normalization::DictionaryEntry};
+
 #[derive(Default, Clone, Debug, Deserialize, TS, Serialize)]
 #[ts(export)]
 pub struct EntityNode {
@@ -8,8 +12,32 @@ pub struct EntityNode {
     pub chunk_order_index: u32,
     pub doc_id: String,
     pub entity_description: String,
+    /// Descriptions from every chunk the entity was extracted from;
+    /// `entity_description` joins or summarizes them.
+    #[serde(default)]
+    pub descriptions: Vec<SourcedDescription>,
+    /// Verified quotes supporting the entity, with their offsets.
+    #[serde(default)]
+    pub evidence: Vec<Evidence>,
     pub entity_name: String,
     pub entity_type: String,
+    /// Metadata of the source document, for filtering the graph.
+    #[serde(default)]
+    pub doc_metadata: DocumentMetadata,
+    /// Entity this mention belongs to across documents.
+    #[serde(default)]
+    pub canonical_id: Option<String>,
+    /// Number of mentions merged into this node when the graph is built over
+    /// canonical entities; 0 on stored mentions.
+    #[serde(default)]
+    pub mention_count: u32,
+    /// Created for a relationship endpoint that matched no extracted entity.
+    #[serde(default)]
+    pub placeholder: bool,
+    /// Dictionary entry the name was normalized to, with its synonyms.
+    #[serde(default)]
+    #[ts(optional)]
+    pub normalization: Option<DictionaryEntry>,
 }
 
 #[derive(Default, Clone, Debug, Deserialize, TS, Serialize)]
@@ -18,7 +46,131 @@ pub struct RelationEdge {
     pub chunk_id: String,
     pub doc_id: String,
     pub relationship_description: String,
+    #[serde(default)]
+    pub descriptions: Vec<SourcedDescription>,
+    #[serde(default)]
+    pub evidence: Vec<Evidence>,
     pub relationship_keywords: Vec<String>,
     pub source_entity_id: String,
     pub target_entity_id: String,
+    #[serde(default)]
+    pub doc_metadata: DocumentMetadata,
+    /// How strongly the source text supports the relation, from 0 to 1.
+    /// Relations extracted before confidence was recorded have none.
+    #[serde(default)]
+    pub confidence: Option<f32>,
+    #[serde(default)]
+    pub modality: Modality,
+    #[serde(default)]
+    pub hedge_cues: Vec<String>,
+    /// Predicate from the ontology's vocabulary, e.g. `inhibits`.
+    #[serde(default)]
+    pub predicate: Option<String>,
+}
+
+/// Restricts graph edges by extraction quality, predicate and endpoint
+/// types. Deserializes from query strings such as
+/// `?min_confidence=0.7&exclude_negated=true` or
+/// `?predicate=inhibits&source_type=Compound&target_type=Protein`.
+#[derive(Default, Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct EdgeFilter {
+    /// Relations without a recorded confidence are kept.
+    pub min_confidence: Option<f32>,
+    pub exclude_hedged: bool,
+    pub exclude_negated: bool,
+    /// Comma-separated predicates; untyped relations are dropped when set.
+    pub predicate: Option<String>,
+    pub source_type: Option<String>,
+    pub target_type: Option<String>,
+}
+
+impl EdgeFilter {
+    pub fn matches(&self, relation: &RelationEdge) -> bool {
+        if let (Some(min), Some(confidence)) = (self.min_confidence, relation.confidence)
+            && confidence < min
+        {
+            return false;
+        }
+        if let Some(predicates) = &self.predicate
+            && !predicates.split(',').any(|predicate| {
+                relation
+                    .predicate
+                    .as_deref()
+                    .is_some_and(|stored| stored.eq_ignore_ascii_case(predicate.trim()))
+            })
+        {
+            return false;
+        }
+        match relation.modality {
+            Modality::Asserted => true,
+            Modality::Hedged => !self.exclude_hedged,
+            Modality::Negated => !self.exclude_negated,
+        }
+    }
+
+    /// Checks the types of a relation's endpoints, when the filter names any.
+    pub fn matches_endpoints(
+        &self,
+        source: Option<&EntityNode>,
+        target: Option<&EntityNode>,
+    ) -> bool {
+        let has_type = |wanted: &Option<String>, entity: Option<&EntityNode>| match wanted {
+            Some(wanted) => {
+                entity.is_some_and(|entity| entity.entity_type.eq_ignore_ascii_case(wanted.trim()))
+            }
+            None => true,
+        };
+        has_type(&self.source_type, source) && has_type(&self.target_type, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edge_filter_drops_weak_and_negated_relations() {
+        let relation = |confidence: Option<f32>, modality: Modality| RelationEdge {
+            confidence,
+            modality,
+            ..RelationEdge::default()
+        };
+        let filter = EdgeFilter {
+            min_confidence: Some(0.6),
+            exclude_negated: true,
+            ..EdgeFilter::default()
+        };
+
+        assert!(filter.matches(&relation(Some(0.9), Modality::Hedged)));
+        assert!(filter.matches(&relation(None, Modality::Asserted)));
+        assert!(!filter.matches(&relation(Some(0.3), Modality::Asserted)));
+        assert!(!filter.matches(&relation(Some(0.9), Modality::Negated)));
+        assert!(EdgeFilter::default().matches(&relation(Some(0.1), Modality::Negated)));
+    }
+
+    #[test]
+    fn edge_filter_selects_predicates_between_entity_types() {
+        let relation = |predicate: Option<&str>| RelationEdge {
+            predicate: predicate.map(str::to_string),
+            ..RelationEdge::default()
+        };
+        let entity = |entity_type: &str| EntityNode {
+            entity_type: entity_type.to_string(),
+            ..EntityNode::default()
+        };
+        let filter = EdgeFilter {
+            predicate: Some("inhibits, activates".to_string()),
+            source_type: Some("Compound".to_string()),
+            target_type: Some("Protein".to_string()),
+            ..EdgeFilter::default()
+        };
+
+        assert!(filter.matches(&relation(Some("activates"))));
+        assert!(!filter.matches(&relation(Some("treats"))));
+        assert!(!filter.matches(&relation(None)));
+        assert!(filter.matches_endpoints(Some(&entity("Compound")), Some(&entity("protein"))));
+        assert!(!filter.matches_endpoints(Some(&entity("Gene")), Some(&entity("Protein"))));
+        assert!(!filter.matches_endpoints(None, Some(&entity("Protein"))));
+    }
 }
@@ -0,0 +1,381 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::{Client, StatusCode, Url, header, redirect::Policy};
+
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct UrlFetchConfig {
+    pub timeout: Duration,
+    pub max_bytes: usize,
+    /// Allow loopback, private and link-local targets. Off by default so the
+    /// server can't be used to probe its own network.
+    pub allow_private_addresses: bool,
+}
+
+impl Default for UrlFetchConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_bytes: 20 * 1024 * 1024,
+            allow_private_addresses: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FetchedDocument {
+    /// Final URL after redirects.
+    pub url: String,
+    pub content_type: String,
+    pub content: String,
+}
+
+/// Downloads documents for URL ingestion, enforcing content type, size,
+/// timeout and address restrictions on every redirect hop.
+#[derive(Debug, Clone)]
+pub struct UrlFetcher {
+    config: UrlFetchConfig,
+}
+
+impl UrlFetcher {
+    pub fn new(config: UrlFetchConfig) -> Self {
+        Self { config }
+    }
+
+    pub async fn fetch(&self, raw_url: &str) -> Result<FetchedDocument> {
+        let mut url = Url::parse(raw_url.trim()).context("invalid URL")?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            let addr = self.resolve(&url).await?;
+            let host = url
+                .host_str()
+                .ok_or_else(|| anyhow!("URL has no host"))?
+                .to_string();
+            // pin the connection to the address we just checked so a second
+            // DNS lookup can't swap in a private one
+            let client = Client::builder()
+                .redirect(Policy::none())
+                .timeout(self.config.timeout)
+                .connect_timeout(self.config.timeout.min(Duration::from_secs(10)))
+                .resolve(&host, addr)
+                .build()
+                .context("failed to build HTTP client")?;
+
+            let resp = client
+                .get(url.clone())
+                .header(header::ACCEPT, "text/*, application/json;q=0.9")
+                .send()
+                .await
+                .with_context(|| format!("request to {url} failed"))?;
+
+            if resp.status().is_redirection() {
+                let location = resp
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                    .ok_or_else(|| anyhow!("redirect from {url} has no location"))?;
+                url = url
+                    .join(location)
+                    .with_context(|| format!("invalid redirect location '{location}'"))?;
+                continue;
+            }
+            if resp.status() != StatusCode::OK {
+                return Err(anyhow!("{url} responded with {}", resp.status()));
+            }
+
+            let content_type = resp
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| {
+                    value
+                        .split(';')
+                        .next()
+                        .unwrap_or_default()
+                        .trim()
+                        .to_ascii_lowercase()
+                })
+                .unwrap_or_default();
+            let kind = ContentKind::from_mime(&content_type)
+                .ok_or_else(|| anyhow!("unsupported content type '{content_type}'"))?;
+
+            if let Some(length) = resp.content_length()
+                && length > self.config.max_bytes as u64
+            {
+                return Err(anyhow!(
+                    "document is {length} bytes, the limit is {}",
+                    self.config.max_bytes
+                ));
+            }
+
+            let body = self.read_body(resp).await?;
+            let text = String::from_utf8_lossy(&body).into_owned();
+            let content = match kind {
+                ContentKind::Html => html_to_text(&text),
+                ContentKind::Text => text,
+            };
+
+            return Ok(FetchedDocument {
+                url: url.to_string(),
+                content_type,
+                content,
+            });
+        }
+
+        Err(anyhow!("too many redirects"))
+    }
+
+    async fn resolve(&self, url: &Url) -> Result<SocketAddr> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!("unsupported URL scheme '{}'", url.scheme()));
+        }
+        let host = url.host_str().ok_or_else(|| anyhow!("URL has no host"))?;
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| anyhow!("URL has no port"))?;
+
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .with_context(|| format!("failed to resolve {host}"))?
+            .collect();
+        if addrs.is_empty() {
+            return Err(anyhow!("{host} did not resolve to any address"));
+        }
+
+        if !self.config.allow_private_addresses
+            && let Some(blocked) = addrs.iter().find(|addr| !is_public_ip(addr.ip()))
+        {
+            return Err(anyhow!(
+                "{host} resolves to non-public address {}",
+                blocked.ip()
+            ));
+        }
+        Ok(addrs[0])
+    }
+
+    async fn read_body(&self, mut resp: reqwest::Response) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        while let Some(chunk) = resp.chunk().await.context("failed to read response")? {
+            if body.len() + chunk.len() > self.config.max_bytes {
+                return Err(anyhow!(
+                    "document exceeds the {} byte limit",
+                    self.config.max_bytes
+                ));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+}
+
+enum ContentKind {
+    Text,
+    Html,
+}
+
+impl ContentKind {
+    fn from_mime(mime: &str) -> Option<Self> {
+        match mime {
+            "text/html" | "application/xhtml+xml" => Some(Self::Html),
+            "text/plain" | "text/markdown" | "text/x-markdown" | "text/csv"
+            | "application/json" => Some(Self::Text),
+            _ => None,
+        }
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                // carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // unique local fc00::/7 and link-local fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Very small HTML to text conversion: drops scripts, styles and tags,
+/// keeps block boundaries as line breaks and decodes common entities.
+fn html_to_text(html: &str) -> String {
+    const BLOCK_TAGS: &[&str] = &[
+        "p",
+        "div",
+        "br",
+        "li",
+        "tr",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "section",
+        "article",
+        "table",
+        "ul",
+        "ol",
+        "blockquote",
+        "pre",
+    ];
+
+    let mut text = String::with_capacity(html.len() / 2);
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = after[..end].trim_start_matches('/').trim();
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        rest = &after[end + 1..];
+
+        if (name == "script" || name == "style") && !after.starts_with('/') {
+            let closing = format!("</{name}");
+            rest = match rest.to_ascii_lowercase().find(&closing) {
+                Some(pos) => rest[pos..].find('>').map_or("", |gt| &rest[pos + gt + 1..]),
+                None => "",
+            };
+        } else if BLOCK_TAGS.contains(&name.as_str()) {
+            text.push('\n');
+        }
+    }
+    text.push_str(rest);
+
+    let decoded = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    decoded
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, http::header::CONTENT_TYPE, response::Redirect, routing::get};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    async fn serve() -> SocketAddr {
+        let app = Router::new()
+            .route(
+                "/notes.txt",
+                get(|| async { ([(CONTENT_TYPE, "text/plain")], "rapamycin extends lifespan") }),
+            )
+            .route(
+                "/page",
+                get(|| async {
+                    (
+                        [(CONTENT_TYPE, "text/html; charset=utf-8")],
+                        "<html><script>x()</script><p>Hello &amp; welcome</p></html>",
+                    )
+                }),
+            )
+            .route(
+                "/image.png",
+                get(|| async { ([(CONTENT_TYPE, "image/png")], vec![0u8; 16]) }),
+            )
+            .route(
+                "/big.txt",
+                get(|| async { ([(CONTENT_TYPE, "text/plain")], "a".repeat(4096)) }),
+            )
+            .route(
+                "/moved",
+                get(|| async { Redirect::temporary("/notes.txt") }),
+            );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    fn fetcher(allow_private_addresses: bool) -> UrlFetcher {
+        UrlFetcher::new(UrlFetchConfig {
+            max_bytes: 1024,
+            allow_private_addresses,
+            ..UrlFetchConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn fetches_text_and_html_and_follows_redirects() {
+        let addr = serve().await;
+        let fetcher = fetcher(true);
+
+        let doc = fetcher
+            .fetch(&format!("http://{addr}/moved"))
+            .await
+            .unwrap();
+        assert_eq!(doc.content, "rapamycin extends lifespan");
+        assert!(doc.url.ends_with("/notes.txt"));
+
+        let page = fetcher.fetch(&format!("http://{addr}/page")).await.unwrap();
+        assert_eq!(page.content, "Hello & welcome");
+    }
+
+    #[tokio::test]
+    async fn rejects_private_addresses_types_and_oversized_bodies() {
+        let addr = serve().await;
+
+        let err = fetcher(false)
+            .fetch(&format!("http://{addr}/notes.txt"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("non-public address"));
+
+        let fetcher = fetcher(true);
+        assert!(
+            fetcher
+                .fetch(&format!("http://{addr}/image.png"))
+                .await
+                .is_err()
+        );
+        assert!(
+            fetcher
+                .fetch(&format!("http://{addr}/big.txt"))
+                .await
+                .is_err()
+        );
+        assert!(fetcher.fetch("file:///etc/passwd").await.is_err());
+    }
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::{
+    ai::responses::{ModelCall, TokenUsage},
+    storage::{JsonKvStorage, KvStorage},
+};
+
+use super::extractor::ChunkExtraction;
+
+/// USD prices per million tokens, by model.
+///
+/// ```yaml
+/// pricing:
+///   models:
+///     gpt-5-mini: { input_per_million: 0.25, output_per_million: 2.0 }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PricingConfig {
+    pub models: HashMap<String, ModelPricing>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelPricing {
+    pub input_per_million: f64,
+    /// Also applies to reasoning tokens, which are billed as output.
+    pub output_per_million: f64,
+}
+
+impl PricingConfig {
+    /// Cost of `usage` in USD, or `None` when the model has no price. A
+    /// model without an exact entry uses the longest configured prefix, so
+    /// dated snapshots like `gpt-5-mini-2025-08-07` get the `gpt-5-mini` price.
+    pub fn price(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
+        let pricing = self.models.get(model).or_else(|| {
+            self.models
+                .iter()
+                .filter(|(name, _)| model.starts_with(name.as_str()))
+                .max_by_key(|(name, _)| name.len())
+                .map(|(_, pricing)| pricing)
+        })?;
+        // output_tokens already include the reasoning tokens
+        Some(
+            (usage.input_tokens as f64 * pricing.input_per_million
+                + usage.output_tokens as f64 * pricing.output_per_million)
+                / 1_000_000.0,
+        )
+    }
+}
+
+/// What a model call was made for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum UsageKind {
+    #[default]
+    Extraction,
+    DescriptionSummary,
+    DocumentSummary,
+    /// Embeddings of new entities compared with existing ones.
+    EntityResolution,
+    /// Sentence embeddings of the semantic chunking strategy.
+    SemanticChunking,
+}
+
+/// Tokens and cost of one LLM call, attributed to the document, upload
+/// batch and, for extraction, chunk it was made for.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct UsageRecord {
+    pub doc_id: String,
+    #[serde(default)]
+    #[ts(optional)]
+    pub chunk_id: Option<String>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub track_id: Option<String>,
+    /// Records written before other calls were recorded are extractions.
+    #[serde(default)]
+    pub kind: UsageKind,
+    pub model: String,
+    /// Extraction pass; 0 is the initial extraction, later ones gleaning.
+    #[serde(default)]
+    pub pass: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub reasoning_tokens: u64,
+    /// `None` when the model has no configured price.
+    #[serde(default)]
+    #[ts(optional)]
+    pub cost_usd: Option<f64>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct UsageTotals {
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub reasoning_tokens: u64,
+    /// Cost of the priced calls.
+    pub cost_usd: f64,
+    /// Calls to models without a configured price, missing from `cost_usd`.
+    pub unpriced_calls: u64,
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, record: &UsageRecord) {
+        self.calls += 1;
+        self.input_tokens += record.input_tokens;
+        self.output_tokens += record.output_tokens;
+        self.reasoning_tokens += record.reasoning_tokens;
+        match record.cost_usd {
+            Some(cost) => self.cost_usd += cost,
+            None => self.unpriced_calls += 1,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, TS)]
+#[ts(export)]
+pub struct UsageReport {
+    pub total: UsageTotals,
+    pub by_track: BTreeMap<String, UsageTotals>,
+    pub by_document: BTreeMap<String, UsageTotals>,
+    pub by_model: BTreeMap<String, UsageTotals>,
+}
+
+impl UsageReport {
+    pub fn from_records<'a>(records: impl IntoIterator<Item = &'a UsageRecord>) -> Self {
+        let mut report = Self::default();
+        for record in records {
+            report.total.add(record);
+            if let Some(track_id) = &record.track_id {
+                report
+                    .by_track
+                    .entry(track_id.clone())
+                    .or_default()
+                    .add(record);
+            }
+            report
+                .by_document
+                .entry(record.doc_id.clone())
+                .or_default()
+                .add(record);
+            report
+                .by_model
+                .entry(record.model.clone())
+                .or_default()
+                .add(record);
+        }
+        report
+    }
+}
+
+/// Records every LLM call in the `llm_usage` storage, one record per call.
+pub struct UsageLedger {
+    storage: Arc<JsonKvStorage>,
+    pricing: PricingConfig,
+}
+
+impl UsageLedger {
+    pub fn new(storage: Arc<JsonKvStorage>, pricing: PricingConfig) -> Self {
+        Self { storage, pricing }
+    }
+
+    /// Records the calls of every extraction pass over a chunk and returns
+    /// their totals.
+    pub async fn record_extraction(
+        &self,
+        doc_id: &str,
+        chunk_id: &str,
+        track_id: Option<String>,
+        extraction: &ChunkExtraction,
+    ) -> Result<UsageTotals> {
+        let mut totals = UsageTotals::default();
+        let mut records = HashMap::new();
+        for pass in &extraction.passes {
+            let call = ModelCall::new(
+                &extraction.model,
+                TokenUsage {
+                    input_tokens: pass.input_tokens,
+                    output_tokens: pass.output_tokens,
+                    reasoning_tokens: pass.reasoning_tokens,
+                },
+            );
+            let mut record = self.usage_record(
+                doc_id,
+                Some(chunk_id),
+                track_id.clone(),
+                UsageKind::Extraction,
+                &call,
+            );
+            record.pass = pass.pass;
+            totals.add(&record);
+            records.insert(format!("usage-{}", Uuid::new_v4()), json!(record));
+        }
+        if !records.is_empty() {
+            self.storage.upsert(records).await?;
+        }
+        Ok(totals)
+    }
+
+    /// Records a single call made for `doc_id`, and for `chunk_id` when it
+    /// concerned one chunk.
+    pub async fn record_call(
+        &self,
+        doc_id: &str,
+        chunk_id: Option<&str>,
+        track_id: Option<String>,
+        kind: UsageKind,
+        call: &ModelCall,
+    ) -> Result<UsageRecord> {
+        let record = self.usage_record(doc_id, chunk_id, track_id, kind, call);
+        self.storage
+            .upsert(HashMap::from([(
+                format!("usage-{}", Uuid::new_v4()),
+                json!(record),
+            )]))
+            .await?;
+        Ok(record)
+    }
+
+    fn usage_record(
+        &self,
+        doc_id: &str,
+        chunk_id: Option<&str>,
+        track_id: Option<String>,
+        kind: UsageKind,
+        call: &ModelCall,
+    ) -> UsageRecord {
+        UsageRecord {
+            doc_id: doc_id.to_string(),
+            chunk_id: chunk_id.map(str::to_string),
+            track_id,
+            kind,
+            model: call.model.clone(),
+            pass: 0,
+            input_tokens: call.usage.input_tokens,
+            output_tokens: call.usage.output_tokens,
+            reasoning_tokens: call.usage.reasoning_tokens,
+            cost_usd: self.pricing.price(&call.model, &call.usage),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub async fn records(&self) -> Result<Vec<UsageRecord>> {
+        Ok(self
+            .storage
+            .get_all()
+            .await?
+            .into_values()
+            .filter_map(|record| serde_json::from_value(record).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(track_id: &str, model: &str, cost_usd: Option<f64>) -> UsageRecord {
+        UsageRecord {
+            doc_id: "doc-1".to_string(),
+            chunk_id: Some("chunk-1".to_string()),
+            track_id: Some(track_id.to_string()),
+            kind: UsageKind::Extraction,
+            model: model.to_string(),
+            pass: 0,
+            input_tokens: 1_000,
+            output_tokens: 500,
+            reasoning_tokens: 300,
+            cost_usd,
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn calls_are_priced_by_model_prefix_and_totalled_per_track() {
+        let pricing = PricingConfig {
+            models: HashMap::from([
+                (
+                    "gpt-5".to_string(),
+                    ModelPricing {
+                        input_per_million: 1.25,
+                        output_per_million: 10.0,
+                    },
+                ),
+                (
+                    "gpt-5-mini".to_string(),
+                    ModelPricing {
+                        input_per_million: 0.25,
+                        output_per_million: 2.0,
+                    },
+                ),
+            ]),
+        };
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 500_000,
+            reasoning_tokens: 300_000,
+        };
+        assert_eq!(pricing.price("gpt-5-mini-2025-08-07", &usage), Some(1.25));
+        assert_eq!(pricing.price("gpt-5", &usage), Some(6.25));
+        assert_eq!(pricing.price("o3", &usage), None);
+
+        let records = [
+            record("batch-a", "gpt-5-mini", Some(0.5)),
+            record("batch-a", "gpt-5-mini", Some(0.25)),
+            record("batch-b", "o3", None),
+        ];
+        let report = UsageReport::from_records(&records);
+        assert_eq!(report.total.calls, 3);
+        assert_eq!(report.total.unpriced_calls, 1);
+        assert_eq!(report.by_track["batch-a"].cost_usd, 0.75);
+        assert_eq!(report.by_track["batch-a"].reasoning_tokens, 600);
+        assert_eq!(report.by_document["doc-1"].input_tokens, 3_000);
+        assert_eq!(report.by_model["o3"].calls, 1);
+    }
+
+    #[tokio::test]
+    async fn calls_other_than_extraction_are_recorded_by_kind() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(JsonKvStorage::new(crate::storage::JsonKvStorageConfig {
+            working_dir: dir.path().into(),
+            namespace: "llm_usage".into(),
+            workspace: None,
+        }));
+        storage.initialize().await.unwrap();
+        let pricing = PricingConfig {
+            models: HashMap::from([(
+                "text-embedding-3-small".to_string(),
+                ModelPricing {
+                    input_per_million: 0.02,
+                    output_per_million: 0.0,
+                },
+            )]),
+        };
+        let ledger = UsageLedger::new(storage, pricing);
+
+        let call = ModelCall::new(
+            "text-embedding-3-small",
+            TokenUsage {
+                input_tokens: 500_000,
+                ..TokenUsage::default()
+            },
+        );
+        ledger
+            .record_call(
+                "doc-1",
+                None,
+                Some("batch-a".to_string()),
+                UsageKind::EntityResolution,
+                &call,
+            )
+            .await
+            .unwrap();
+
+        let records = ledger.records().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].kind, UsageKind::EntityResolution);
+        assert_eq!(records[0].chunk_id, None);
+        assert_eq!(records[0].cost_usd, Some(0.01));
+        assert_eq!(
+            UsageReport::from_records(&records).by_track["batch-a"].calls,
+            1
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     pipeline::{
@@ -127,6 +127,189 @@ pub fn chunking_by_token_size<T: Tokenizer + ?Sized>(
     Ok(results)
 }
 
+/// Abbreviations that end with a period but do not end a sentence.
+const NON_TERMINAL_ABBREVIATIONS: &[&str] = &[
+    "al.", "approx.", "ca.", "cf.", "dr.", "e.g.", "eq.", "etc.", "fig.", "figs.", "i.e.", "mr.",
+    "mrs.", "ms.", "no.", "prof.", "ref.", "refs.", "sp.", "spp.", "st.", "vs.",
+];
+
+/// Splits text into sentences on `.`, `!` or `?` followed by whitespace, and on
+/// blank lines. Common abbreviations ("e.g.", "et al.", "Fig.") and single
+/// letter initials do not end a sentence. Returned slices are trimmed.
+pub fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0usize;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((idx, ch)) = chars.next() {
+        let boundary = match ch {
+            '\n' => matches!(chars.peek(), Some((_, '\n'))),
+            '.' | '!' | '?' => {
+                // keep closing quotes and brackets with the sentence
+                let mut end = idx + ch.len_utf8();
+                while let Some(&(next_idx, next)) = chars.peek() {
+                    if matches!(next, '"' | '\'' | ')' | ']' | '”' | '’') {
+                        end = next_idx + next.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let followed_by_space = chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+                followed_by_space && (ch != '.' || !is_abbreviation(&text[start..end]))
+            }
+            _ => false,
+        };
+
+        if boundary {
+            let end = chars
+                .peek()
+                .map(|(next_idx, _)| *next_idx)
+                .unwrap_or(text.len());
+            let sentence = text[start..end].trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence);
+            }
+            start = end;
+        }
+    }
+
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest);
+    }
+    sentences
+}
+
+fn is_abbreviation(sentence: &str) -> bool {
+    let last_word = sentence
+        .split_whitespace()
+        .last()
+        .unwrap_or_default()
+        .trim_start_matches(['(', '[', '"'])
+        .to_ascii_lowercase();
+    if NON_TERMINAL_ABBREVIATIONS.contains(&last_word.as_str()) {
+        return true;
+    }
+    // single letter initials such as "J. Smith"
+    let letters: Vec<char> = last_word.trim_end_matches('.').chars().collect();
+    letters.len() == 1 && letters[0].is_alphabetic()
+}
+
+/// Packs whole sentences of `text`, as split by [`split_sentences`], into
+/// chunks of at most `max_token_size` tokens.
+///
+/// A new chunk is also started before every index in `breakpoints`. Each new
+/// chunk repeats the trailing sentences of the previous one that fit within
+/// `overlap_token_size`. A single sentence longer than `max_token_size` is the
+/// only thing ever split, using token windows. Chunks are cut from `text` as
+/// they are, whitespace between sentences included, so they can be located
+/// in the document again.
+pub fn chunking_by_sentences<T: Tokenizer + ?Sized>(
+    tokenizer: &T,
+    text: &str,
+    sentences: &[&str],
+    breakpoints: &HashSet<usize>,
+    overlap_token_size: usize,
+    max_token_size: usize,
+) -> Result<Vec<TokenChunk>> {
+    if overlap_token_size >= max_token_size {
+        return Err(anyhow!(
+            "overlap_token_size ({overlap_token_size}) must be smaller than max_token_size ({max_token_size})"
+        ));
+    }
+
+    // byte range of every sentence in `text`, found in order
+    let mut cursor = 0usize;
+    let mut spans = Vec::with_capacity(sentences.len());
+    for sentence in sentences {
+        let start = text[cursor..]
+            .find(sentence)
+            .map(|offset| cursor + offset)
+            .ok_or_else(|| anyhow!("sentence not found in text: {sentence:?}"))?;
+        cursor = start + sentence.len();
+        spans.push(start..cursor);
+    }
+
+    let sentence_tokens: Vec<usize> = sentences
+        .iter()
+        .map(|sentence| tokenizer.encode(sentence).len())
+        .collect();
+
+    let mut results: Vec<TokenChunk> = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+    let mut current_tokens = 0usize;
+    // number of leading sentences in `current` carried over as overlap
+    let mut carried = 0usize;
+
+    let flush = |current: &[usize], results: &mut Vec<TokenChunk>| {
+        let (first, last) = (current[0], current[current.len() - 1]);
+        let content = text[spans[first].start..spans[last].end].to_string();
+        let tokens = tokenizer.encode(&content).len();
+        results.push(TokenChunk {
+            tokens,
+            content,
+            chunk_order_index: results.len(),
+        });
+    };
+
+    for (idx, &tokens) in sentence_tokens.iter().enumerate() {
+        let must_break = breakpoints.contains(&idx) || current_tokens + tokens > max_token_size;
+        if must_break && current.len() > carried {
+            flush(&current, &mut results);
+
+            let mut overlap = Vec::new();
+            let mut overlap_tokens = 0usize;
+            for &prev in current.iter().rev() {
+                if overlap_tokens + sentence_tokens[prev] + tokens > max_token_size
+                    || overlap_tokens + sentence_tokens[prev] > overlap_token_size
+                {
+                    break;
+                }
+                overlap_tokens += sentence_tokens[prev];
+                overlap.push(prev);
+            }
+            overlap.reverse();
+            carried = overlap.len();
+            current = overlap;
+            current_tokens = overlap_tokens;
+        }
+
+        if tokens > max_token_size {
+            // a single oversized sentence: fall back to token windows
+            if current.len() > carried {
+                flush(&current, &mut results);
+            }
+            for window in chunking_by_token_size(
+                tokenizer,
+                sentences[idx],
+                None,
+                false,
+                overlap_token_size,
+                max_token_size,
+            )? {
+                results.push(TokenChunk {
+                    chunk_order_index: results.len(),
+                    ..window
+                });
+            }
+            current.clear();
+            current_tokens = 0;
+            carried = 0;
+            continue;
+        }
+
+        current.push(idx);
+        current_tokens += tokens;
+    }
+
+    if current.len() > carried {
+        flush(&current, &mut results);
+    }
+
+    Ok(results)
+}
+
 pub fn compute_mdhash_id(content: &str, prefix: &str) -> String {
     use sha2::{Digest, Sha256};
     let mut hasher = Sha256::new();
@@ -170,7 +353,11 @@ pub async fn get_all_relationships(s: &JsonKvStorage) -> Result<HashMap<String,
     Ok(relations)
 }
 
-pub fn chunk_to_chunk_state(chunks: Vec<Chunk>, doc_id: String) -> Vec<ChunkState> {
+pub fn chunk_to_chunk_state(
+    chunks: Vec<Chunk>,
+    doc_id: String,
+    max_retries: u8,
+) -> Vec<ChunkState> {
     chunks
         .iter()
         .map(|chunk| ChunkState {
@@ -179,12 +366,84 @@ pub fn chunk_to_chunk_state(chunks: Vec<Chunk>, doc_id: String) -> Vec<ChunkStat
             chunk_status: ChunkStatus::Pending,
             chunk_order_index: chunk.order,
             content: chunk.content.clone(),
+            heading: chunk.heading.clone(),
             error: None,
             output: None,
-            max_retries: 20,
+            max_retries,
             current_retry: 0,
             created_at: Utc::now(),
             oai_resp_id: None,
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_sentences_respects_abbreviations() {
+        let text = "Rapamycin inhibits mTOR (Smith et al. 2020). It extends lifespan, e.g. in mice! Does it work in J. Doe's model?\n\nNew paragraph without stop";
+        let sentences = split_sentences(text);
+
+        assert_eq!(
+            sentences,
+            vec![
+                "Rapamycin inhibits mTOR (Smith et al. 2020).",
+                "It extends lifespan, e.g. in mice!",
+                "Does it work in J. Doe's model?",
+                "New paragraph without stop",
+            ]
+        );
+    }
+
+    #[test]
+    fn sentence_chunks_never_split_sentences_and_overlap() {
+        let tokenizer = TiktokenTokenizer::new().unwrap();
+        let text = (0..30)
+            .map(|idx| format!("Sentence number {idx} talks about protein {idx}."))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let sentences = split_sentences(&text);
+
+        let chunks =
+            chunking_by_sentences(&tokenizer, &text, &sentences, &HashSet::new(), 12, 40).unwrap();
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.tokens <= 40);
+            assert!(chunk.content.starts_with("Sentence number"));
+            assert!(chunk.content.ends_with('.'));
+        }
+        let last_of_first = split_sentences(&chunks[0].content).last().copied().unwrap();
+        assert!(chunks[1].content.starts_with(last_of_first));
+    }
+
+    #[test]
+    fn sentence_chunks_break_at_breakpoints() {
+        let tokenizer = TiktokenTokenizer::new().unwrap();
+        let text = "One.  Two.\nThree.";
+        let sentences = split_sentences(text);
+        let breakpoints = HashSet::from([2]);
+
+        let chunks =
+            chunking_by_sentences(&tokenizer, text, &sentences, &breakpoints, 0, 100).unwrap();
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].content, "One.  Two.");
+        assert_eq!(chunks[1].content, "Three.");
+    }
+
+    #[test]
+    fn sentence_chunks_keep_source_text_across_line_breaks() {
+        let tokenizer = TiktokenTokenizer::new().unwrap();
+        let text = "Rapamycin inhibits mTOR.\nIt extends lifespan  in mice.\n\nAMPK is activated.";
+        let sentences = split_sentences(text);
+
+        let chunks =
+            chunking_by_sentences(&tokenizer, text, &sentences, &HashSet::new(), 0, 100).unwrap();
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].content, text);
+    }
+}
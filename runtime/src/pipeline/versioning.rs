@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use super::{chunker::Chunk, descriptions::retire_descriptions, evidence::retire_evidence};
+
+/// How the chunks of a new document version relate to the previous one.
+/// Chunk ids are content hashes, so equal ids mean unchanged text.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ChunkDiff {
+    pub reused: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl ChunkDiff {
+    pub fn new(previous: &[String], current: &[Chunk]) -> Self {
+        let previous_ids: HashSet<&str> = previous.iter().map(String::as_str).collect();
+        let current_ids: HashSet<&str> = current.iter().map(|chunk| chunk.id.as_str()).collect();
+
+        let mut diff = ChunkDiff::default();
+        let mut seen = HashSet::new();
+        for chunk in current {
+            if !seen.insert(chunk.id.as_str()) {
+                continue;
+            }
+            if previous_ids.contains(chunk.id.as_str()) {
+                diff.reused.push(chunk.id.clone());
+            } else {
+                diff.added.push(chunk.id.clone());
+            }
+        }
+        diff.removed = previous
+            .iter()
+            .filter(|id| !current_ids.contains(id.as_str()))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+/// Chunk ids an entity or relation record was extracted from. Records
+/// written before `source_chunk_ids` existed only carry `chunk_id`.
+pub fn source_chunk_ids(record: &Value) -> Vec<String> {
+    match record.get("source_chunk_ids").and_then(Value::as_array) {
+        Some(ids) => ids
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        None => record
+            .get("chunk_id")
+            .and_then(Value::as_str)
+            .map(|id| vec![id.to_string()])
+            .unwrap_or_default(),
+    }
+}
+
+/// Drops `removed` chunk ids from a record's sources, along with the
+/// descriptions and evidence extracted from them. Returns false when no
+/// source is left and the record should be deleted.
+pub fn retire_sources(record: &mut Value, removed: &HashSet<String>) -> bool {
+    let remaining: Vec<String> = source_chunk_ids(record)
+        .into_iter()
+        .filter(|id| !removed.contains(id))
+        .collect();
+    if remaining.is_empty() {
+        return false;
+    }
+    if let Some(chunk_id) = record.get("chunk_id").and_then(Value::as_str)
+        && removed.contains(chunk_id)
+    {
+        record["chunk_id"] = json!(remaining[0]);
+    }
+    record["source_chunk_ids"] = json!(remaining);
+    retire_descriptions(record, removed);
+    retire_evidence(record, removed);
+    true
+}
+
+/// Appends an entry to the `versions` list in a document's metadata.
+pub fn push_version(metadata: Option<Value>, entry: Value) -> Value {
+    let mut metadata = match metadata {
+        Some(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    let versions = metadata
+        .entry("versions")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if let Some(versions) = versions.as_array_mut() {
+        versions.push(entry);
+    }
+    Value::Object(metadata)
+}
+
+/// Number of versions recorded so far.
+pub fn version_count(metadata: Option<&Value>) -> usize {
+    metadata
+        .and_then(|metadata| metadata.get("versions"))
+        .and_then(Value::as_array)
+        .map(Vec::len)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &str) -> Chunk {
+        Chunk {
+            id: id.to_string(),
+            content: String::new(),
+            token_count: 0,
+            order: 0,
+            heading: None,
+            span: None,
+        }
+    }
+
+    #[test]
+    fn diff_splits_reused_added_and_removed() {
+        let previous = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let diff = ChunkDiff::new(&previous, &[chunk("a"), chunk("d"), chunk("c")]);
+
+        assert_eq!(diff.reused, vec!["a", "c"]);
+        assert_eq!(diff.added, vec!["d"]);
+        assert_eq!(diff.removed, vec!["b"]);
+    }
+
+    #[test]
+    fn records_survive_while_any_source_remains() {
+        let removed: HashSet<String> = ["b".to_string()].into();
+
+        let mut shared = json!({ "chunk_id": "b", "source_chunk_ids": ["a", "b"] });
+        assert!(retire_sources(&mut shared, &removed));
+        assert_eq!(shared["chunk_id"], "a");
+        assert_eq!(shared["source_chunk_ids"], json!(["a"]));
+
+        let mut legacy = json!({ "chunk_id": "b" });
+        assert!(!retire_sources(&mut legacy, &removed));
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use tokio::{task::JoinHandle, time::Instant};
+use tracing::{debug, error, info, warn};
+
+use super::{
+    pipeline::{EnqueueStatus, Pipeline, generate_track_id},
+    scheduler::Scheduler,
+};
+
+#[derive(Debug, Clone)]
+pub struct WatcherConfig {
+    pub poll_interval: Duration,
+    /// How long a file's size and modification time must stay unchanged
+    /// before it is considered fully written.
+    pub debounce: Duration,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            debounce: Duration::from_secs(3),
+        }
+    }
+}
+
+/// Size and modification time of a file as seen by one poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FileSnapshot {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/// Tracks files across polls and reports those that stopped changing.
+#[derive(Debug, Default)]
+struct Debouncer {
+    observed: HashMap<PathBuf, (FileSnapshot, Instant)>,
+}
+
+impl Debouncer {
+    /// Feeds one directory listing and returns the files whose snapshot has
+    /// been stable for at least `debounce`. Returned files are forgotten, so
+    /// each one is reported once.
+    fn observe(
+        &mut self,
+        listing: Vec<(PathBuf, FileSnapshot)>,
+        now: Instant,
+        debounce: Duration,
+    ) -> Vec<PathBuf> {
+        let present: HashSet<&PathBuf> = listing.iter().map(|(path, _)| path).collect();
+        self.observed.retain(|path, _| present.contains(path));
+
+        let mut ready = Vec::new();
+        for (path, snapshot) in listing {
+            match self.observed.get(&path) {
+                Some((previous, since)) if *previous == snapshot => {
+                    if now.duration_since(*since) >= debounce {
+                        self.observed.remove(&path);
+                        ready.push(path);
+                    }
+                }
+                _ => {
+                    self.observed.insert(path, (snapshot, now));
+                }
+            }
+        }
+        ready.sort();
+        ready
+    }
+}
+
+/// Polls the document manager's input directory and ingests files that are
+/// dropped there directly, e.g. by rsync or a shared mount.
+pub struct InputWatcher {
+    pipeline: Arc<Pipeline>,
+    scheduler: Arc<Scheduler>,
+    config: WatcherConfig,
+    debouncer: Debouncer,
+    /// Duplicates already reported, so they are not logged on every poll.
+    skipped: HashSet<(PathBuf, FileSnapshot)>,
+}
+
+impl InputWatcher {
+    pub fn new(pipeline: Arc<Pipeline>, scheduler: Arc<Scheduler>, config: WatcherConfig) -> Self {
+        Self {
+            pipeline,
+            scheduler,
+            config,
+            debouncer: Debouncer::default(),
+            skipped: HashSet::new(),
+        }
+    }
+
+    pub fn spawn(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let input_dir = self.pipeline.document_manager().input_dir();
+            info!(dir = %input_dir.display(), "watching input directory");
+            loop {
+                if let Err(err) = self.poll().await {
+                    error!(error = %err, "input directory poll failed");
+                }
+                tokio::time::sleep(self.config.poll_interval).await;
+            }
+        })
+    }
+
+    async fn poll(&mut self) -> Result<()> {
+        let doc_manager = self.pipeline.document_manager();
+        let listing = list_candidates(&doc_manager.input_dir(), |name| {
+            doc_manager.is_supported_file(name)
+        })
+        .await?;
+        let snapshots: HashMap<PathBuf, FileSnapshot> = listing.iter().cloned().collect();
+        // files that were removed or rewritten since they were skipped are
+        // judged afresh
+        self.skipped
+            .retain(|(path, snapshot)| snapshots.get(path) == Some(snapshot));
+        let ready = self
+            .debouncer
+            .observe(listing, Instant::now(), self.config.debounce);
+
+        let mut enqueued = 0usize;
+        for path in ready {
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let snapshot = snapshots[&path];
+            if self.skipped.contains(&(path.clone(), snapshot)) {
+                continue;
+            }
+
+            match self.ingest(&path, file_name).await {
+                Ok(EnqueueStatus::New | EnqueueStatus::Updated) => enqueued += 1,
+                // the stored version is still being extracted; the file is
+                // picked up again once it settles on a later poll
+                Ok(EnqueueStatus::InProgress) => continue,
+                Ok(_) => {}
+                Err(err) => {
+                    error!(file = %file_name, error = %err, "failed to ingest watched file");
+                }
+            }
+            // unchanged files and files that failed stay in place; don't retry
+            // them every poll
+            if path.exists() {
+                self.skipped.insert((path, snapshot));
+            }
+        }
+
+        if enqueued > 0 {
+            self.pipeline
+                .enqueue_pending_docs(self.scheduler.clone())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Enqueues one settled file. Files unchanged in document storage are
+    /// reported as duplicates.
+    async fn ingest(&self, path: &Path, file_name: &str) -> Result<EnqueueStatus> {
+        let contents = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        if let Some(existing) = self
+            .pipeline
+            .find_unchanged_document(file_name, &String::from_utf8_lossy(&contents))
+            .await?
+        {
+            warn!(
+                file = %file_name,
+                status = ?existing.status,
+                "skipping watched file unchanged in document storage"
+            );
+            return Ok(EnqueueStatus::Duplicate);
+        }
+
+        let track_id = generate_track_id("watch");
+        let outcome = self
+            .pipeline
+            .enqueue_file(path.to_path_buf(), &track_id, None)
+            .await?;
+        info!(
+            file = %file_name,
+            track_id = %track_id,
+            status = ?outcome.status,
+            "enqueued watched file"
+        );
+        Ok(outcome.status)
+    }
+}
+
+/// Lists regular, supported, non-hidden files directly inside `dir`.
+/// Subdirectories such as `__enqueued__` are not descended into.
+async fn list_candidates(
+    dir: &Path,
+    is_supported: impl Fn(&str) -> bool,
+) -> Result<Vec<(PathBuf, FileSnapshot)>> {
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .with_context(|| format!("failed to read input directory {}", dir.display()))?;
+
+    let mut listing = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name.starts_with('.') || !is_supported(name) {
+            continue;
+        }
+        let metadata = match entry.metadata().await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => continue,
+            Err(err) => {
+                debug!(file = %name, error = %err, "failed to stat watched file");
+                continue;
+            }
+        };
+        listing.push((
+            entry.path(),
+            FileSnapshot {
+                len: metadata.len(),
+                modified: metadata.modified().ok(),
+            },
+        ));
+    }
+    Ok(listing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(len: u64) -> FileSnapshot {
+        FileSnapshot {
+            len,
+            modified: None,
+        }
+    }
+
+    #[test]
+    fn files_are_ready_once_stable() {
+        let mut debouncer = Debouncer::default();
+        let debounce = Duration::from_secs(3);
+        let start = Instant::now();
+        let path = PathBuf::from("paper.md");
+
+        assert!(
+            debouncer
+                .observe(vec![(path.clone(), snapshot(10))], start, debounce)
+                .is_empty()
+        );
+        // still being written: the size changed, so the clock restarts
+        let later = start + Duration::from_secs(4);
+        assert!(
+            debouncer
+                .observe(vec![(path.clone(), snapshot(20))], later, debounce)
+                .is_empty()
+        );
+        let ready = debouncer.observe(
+            vec![(path.clone(), snapshot(20))],
+            later + debounce,
+            debounce,
+        );
+        assert_eq!(ready, vec![path.clone()]);
+
+        // reported once, then tracked from scratch
+        assert!(
+            debouncer
+                .observe(vec![(path, snapshot(20))], later + debounce * 2, debounce)
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn listing_skips_hidden_unsupported_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        tokio::fs::write(dir.path().join("paper.md"), "# Title")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join(".paper.md.partial"), "")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.path().join("image.png"), "")
+            .await
+            .unwrap();
+        tokio::fs::create_dir(dir.path().join("__enqueued__"))
+            .await
+            .unwrap();
+
+        let listing = list_candidates(dir.path(), |name| {
+            name.ends_with(".md") || name.ends_with(".partial")
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(listing.len(), 1);
+        assert_eq!(listing[0].0, dir.path().join("paper.md"));
+    }
+}
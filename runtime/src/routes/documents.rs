@@ -1,17 +1,33 @@
-use std::sync::Arc;
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use axum::{
     Json, Router,
-    extract::{Multipart, State},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
     http::StatusCode,
+    response::{IntoResponse, Response},
     routing::{get, post},
 };
 use rand::{Rng, rng, seq::SliceRandom};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 use tokio::fs;
 use tracing::{debug, error, info, warn};
 
-use crate::{AppState, pipeline::scheduler::Job};
+use crate::{
+    AppState,
+    ai::schemas::DocumentAbstract,
+    pipeline::{
+        DocumentInput, EnqueueStatus, EnqueuedDocument, Pipeline, UsageReport, UsageTotals,
+        archive::{ArchiveEntry, ArchiveKind, ArchiveLimits, unpack_archive},
+        doc_summary::document_abstract,
+        pipeline::{generate_track_id, in_progress},
+        scheduler::Job,
+    },
+    storage::{DocFilter, DocumentMetadata, KvStorage},
+};
+
+/// Request body limit for archive uploads.
+const MAX_ARCHIVE_UPLOAD_BYTES: usize = 512 * 1024 * 1024;
 
 #[derive(Serialize)]
 struct InsertResponse {
@@ -20,6 +36,74 @@ struct InsertResponse {
     track_id: String,
 }
 
+#[derive(Serialize)]
+struct ArchiveUploadResponse {
+    status: String,
+    message: String,
+    track_id: String,
+    accepted: usize,
+    duplicated: usize,
+    rejected: usize,
+    files: Vec<ArchiveFileReport>,
+}
+
+#[derive(Serialize)]
+struct ArchiveFileReport {
+    path: String,
+    file_name: Option<String>,
+    status: String,
+    message: String,
+}
+
+/// Body of `POST /documents/text`: a single document or a list of them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextInsertRequest {
+    Many(Vec<TextDocument>),
+    One(TextDocument),
+}
+
+#[derive(Deserialize)]
+struct TextDocument {
+    content: String,
+    #[serde(default)]
+    file_path: Option<String>,
+    #[serde(default)]
+    metadata: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct TextInsertResponse {
+    status: String,
+    message: String,
+    track_id: String,
+    documents: Vec<EnqueuedDocument>,
+}
+
+#[derive(Deserialize)]
+struct UrlInsertRequest {
+    urls: Vec<String>,
+    /// Applied to every fetched document.
+    #[serde(default)]
+    metadata: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct UrlInsertResponse {
+    status: String,
+    message: String,
+    track_id: String,
+    documents: Vec<UrlReport>,
+}
+
+#[derive(Serialize)]
+struct UrlReport {
+    url: String,
+    doc_id: Option<String>,
+    status: String,
+    message: String,
+}
+
 #[derive(Serialize)]
 struct DocumentListResponse {
     total: usize,
@@ -37,21 +121,92 @@ struct DocumentSummary {
     updated_at: Option<String>,
     file_path: Option<String>,
     track_id: Option<String>,
+    metadata: DocumentMetadata,
+    /// Tokens and cost of extracting the document.
+    usage: UsageTotals,
+    /// Written by the LLM when `document_summary.enabled` is set.
+    #[serde(rename = "abstract")]
+    abstract_text: Option<String>,
+    keywords: Vec<String>,
+}
+
+/// Stored text of a document. Chunk spans and evidence offsets on graph
+/// elements are byte offsets into `content`.
+#[derive(Serialize)]
+struct DocumentContent {
+    id: String,
+    file_path: Option<String>,
+    content: String,
+    #[serde(rename = "abstract")]
+    abstract_text: Option<String>,
+    keywords: Vec<String>,
+    /// Abstract and keywords as given to the extraction model, for use as
+    /// context when answering questions about the document.
+    context: Option<String>,
 }
 
 pub fn document_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/documents/upload", post(upload_to_input_dir))
+        .route(
+            "/documents/upload_archive",
+            post(upload_archive).layer(DefaultBodyLimit::max(MAX_ARCHIVE_UPLOAD_BYTES)),
+        )
+        .route("/documents/text", post(insert_text))
+        .route("/documents/urls", post(insert_urls))
         .route("/documents", get(list_documents))
+        .route("/documents/{id}/content", get(document_content))
 }
 
+async fn document_content(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<DocumentContent>, (StatusCode, String)> {
+    let record = state
+        .storages
+        .full_docs
+        .get_by_id(&id)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to load document: {err}"),
+            )
+        })?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("document {id} not found")))?;
+    let file_path = state
+        .storages
+        .doc_status
+        .get_by_id(&id)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|status| status.file_path);
+    let summary = document_abstract(&record).unwrap_or_default();
+    Ok(Json(DocumentContent {
+        content: record
+            .get("content")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        abstract_text: (!summary.summary.is_empty()).then_some(summary.summary),
+        keywords: summary.keywords,
+        context: state.pipeline.summaries.extraction_context(&record),
+        file_path,
+        id,
+    }))
+}
+
+/// Lists documents, optionally filtered by metadata, e.g.
+/// `GET /documents?year_from=2020&tags=mouse-study`.
 async fn list_documents(
     State(state): State<Arc<AppState>>,
+    Query(filter): Query<DocFilter>,
 ) -> Result<Json<DocumentListResponse>, (StatusCode, String)> {
     let (records, total) = state
         .storages
         .doc_status
-        .docs_paginated(None, 1, 200, "updated_at", "desc")
+        .docs_paginated(None, Some(&filter), 1, 200, "updated_at", "desc")
         .await
         .map_err(|err| {
             (
@@ -59,6 +214,29 @@ async fn list_documents(
                 format!("failed to load documents: {err}"),
             )
         })?;
+    let usage = state.pipeline.usage.records().await.map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to load usage: {err}"),
+        )
+    })?;
+    let mut usage = UsageReport::from_records(&usage).by_document;
+    let ids: Vec<String> = records.iter().map(|(id, _)| id.clone()).collect();
+    let abstracts: HashMap<String, DocumentAbstract> = state
+        .storages
+        .full_docs
+        .get_by_ids(&ids)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to load documents: {err}"),
+            )
+        })?
+        .iter()
+        .zip(&ids)
+        .filter_map(|(record, id)| Some((id.clone(), document_abstract(record.as_ref()?)?)))
+        .collect();
 
     let documents = records
         .into_iter()
@@ -70,6 +248,7 @@ async fn list_documents(
                 .unwrap_or_else(|| "No summary available".to_string());
 
             DocumentSummary {
+                metadata: status.document_metadata(),
                 id: id.clone(),
                 summary,
                 status: map_status(&status.status),
@@ -83,6 +262,12 @@ async fn list_documents(
                 updated_at: status.updated_at.clone(),
                 file_path: status.file_path.clone(),
                 track_id: status.track_id.clone(),
+                usage: usage.remove(&id).unwrap_or_default(),
+                abstract_text: abstracts.get(&id).map(|summary| summary.summary.clone()),
+                keywords: abstracts
+                    .get(&id)
+                    .map(|summary| summary.keywords.clone())
+                    .unwrap_or_default(),
             }
         })
         .collect();
@@ -90,12 +275,21 @@ async fn list_documents(
     Ok(Json(DocumentListResponse { total, documents }))
 }
 
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct UploadQuery {
+    /// Only estimate chunks, tokens and extraction cost; nothing is stored.
+    dry_run: bool,
+}
+
 async fn upload_to_input_dir(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<UploadQuery>,
     mut multipart: Multipart,
-) -> Result<Json<InsertResponse>, (StatusCode, String)> {
+) -> Result<Response, (StatusCode, String)> {
     let mut file_bytes: Option<Vec<u8>> = None;
     let mut original_filename: Option<String> = None;
+    let mut metadata: Option<Value> = None;
 
     while let Some(field) = multipart.next_field().await.map_err(|err| {
         (
@@ -103,16 +297,34 @@ async fn upload_to_input_dir(
             format!("invalid multipart payload: {err}"),
         )
     })? {
-        if field.name() == Some("file") {
-            original_filename = field.file_name().map(|name| name.to_string());
-            let data = field.bytes().await.map_err(|err| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    format!("failed to read upload field: {err}"),
-                )
-            })?;
-            file_bytes = Some(data.to_vec());
-            break;
+        match field.name() {
+            Some("file") => {
+                original_filename = field.file_name().map(|name| name.to_string());
+                let data = field.bytes().await.map_err(|err| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        format!("failed to read upload field: {err}"),
+                    )
+                })?;
+                file_bytes = Some(data.to_vec());
+            }
+            Some("metadata") => {
+                let text = field.text().await.map_err(|err| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        format!("failed to read metadata field: {err}"),
+                    )
+                })?;
+                let value = serde_json::from_str(&text).map_err(|err| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        format!("metadata is not valid JSON: {err}"),
+                    )
+                })?;
+                metadata = validate_metadata(Some(value))
+                    .map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+            }
+            _ => {}
         }
     }
 
@@ -130,26 +342,13 @@ async fn upload_to_input_dir(
         )
     })?;
 
-    let doc_manager = state.pipeline.document_manager().clone();
-
-    let safe_filename = doc_manager
-        .sanitize_filename(&original_filename)
-        .map_err(|err| {
-            (
-                StatusCode::BAD_REQUEST,
-                format!("invalid filename '{}': {err}", original_filename),
-            )
-        })?;
-
-    if !doc_manager.is_supported_file(&safe_filename) {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            format!(
-                "unsupported file type. supported types: {:?}",
-                crate::SUPPORTED_EXTENSIONS
-            ),
-        ));
+    if query.dry_run {
+        let estimator = &state.estimator;
+        let mut report = estimator.report();
+        report.push(estimator.estimate(&original_filename, file_bytes).await);
+        return Ok(Json(report).into_response());
     }
+
     let scheduler = state.scheduler.clone();
     // let mut guard = scheduler.queue.lock().await;
 
@@ -163,10 +362,351 @@ async fn upload_to_input_dir(
     // }
     // drop(guard);
 
-    let existing_doc = state
-        .storages
-        .doc_status
-        .get_doc_by_file_path(&safe_filename)
+    let (safe_filename, file_path) =
+        match admit_file(&state.pipeline, &original_filename, &file_bytes).await? {
+            Admission::Accepted {
+                file_name,
+                file_path,
+            } => (file_name, file_path),
+            Admission::Duplicated(message) => {
+                return Ok(Json(InsertResponse {
+                    status: "duplicated".to_string(),
+                    message,
+                    track_id: String::new(),
+                })
+                .into_response());
+            }
+            Admission::Rejected(message) => return Err((StatusCode::BAD_REQUEST, message)),
+        };
+
+    let pipeline = state.pipeline.clone();
+    let track_id = generate_track_id("upload");
+    let outcome = pipeline
+        .enqueue_file(file_path.clone(), &track_id, metadata)
+        .await
+        .map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, format!("{err:#}")))?;
+    discard_if_in_progress(&outcome, &file_path).await;
+
+    let (status, message) = describe_enqueued(outcome.status);
+    match status.as_str() {
+        "accepted" => {}
+        "duplicated" => {
+            return Ok(Json(InsertResponse {
+                status,
+                message: format!("File '{safe_filename}': {message}"),
+                track_id,
+            })
+            .into_response());
+        }
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("File '{safe_filename}': {message}"),
+            ));
+        }
+    }
+
+    let background_pipeline = pipeline.clone();
+
+    tokio::spawn(async move {
+        if let Err(err) = background_pipeline.enqueue_pending_docs(scheduler).await {
+            warn!(error = %err, "background pipeline processing failed");
+        }
+    });
+
+    info!(filename = %safe_filename, track_id = %track_id, status = ?outcome.status, "file uploaded successfully");
+
+    Ok(Json(InsertResponse {
+        status: "success".to_string(),
+        message: format!(
+            "File '{}' uploaded successfully. Processing will continue in background.",
+            safe_filename
+        ),
+        track_id,
+    })
+    .into_response())
+}
+
+/// Enqueues documents posted as JSON, skipping the input directory.
+async fn insert_text(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<TextInsertRequest>,
+) -> Result<Json<TextInsertResponse>, (StatusCode, String)> {
+    let documents = match request {
+        TextInsertRequest::Many(documents) => documents,
+        TextInsertRequest::One(document) => vec![document],
+    };
+    if documents.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "at least one document is required".to_string(),
+        ));
+    }
+
+    let mut inputs = Vec::with_capacity(documents.len());
+    for (idx, document) in documents.into_iter().enumerate() {
+        let metadata = validate_metadata(document.metadata).map_err(|message| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("document {idx}: {message}"),
+            )
+        })?;
+        inputs.push(DocumentInput {
+            content: document.content,
+            file_path: document.file_path.unwrap_or_default(),
+            metadata,
+        });
+    }
+
+    let track_id = generate_track_id("insert");
+    let enqueued = state
+        .pipeline
+        .submit_documents(inputs, &track_id)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to enqueue documents: {err}"),
+            )
+        })?;
+
+    let queued = enqueued
+        .iter()
+        .filter(|doc| matches!(doc.status, EnqueueStatus::New | EnqueueStatus::Updated))
+        .count();
+    if queued > 0 {
+        let pipeline = state.pipeline.clone();
+        let scheduler = state.scheduler.clone();
+        tokio::spawn(async move {
+            if let Err(err) = pipeline.enqueue_pending_docs(scheduler).await {
+                warn!(error = %err, "background pipeline processing failed");
+            }
+        });
+    }
+
+    info!(track_id = %track_id, submitted = enqueued.len(), queued, "text documents submitted");
+
+    Ok(Json(TextInsertResponse {
+        status: if queued > 0 { "success" } else { "duplicated" }.to_string(),
+        message: format!(
+            "{queued} of {} document(s) enqueued. Processing will continue in background.",
+            enqueued.len()
+        ),
+        track_id,
+        documents: enqueued,
+    }))
+}
+
+/// Fetches each URL and enqueues its text. The submitted URL becomes the
+/// document's `file_path` and is recorded in its metadata.
+async fn insert_urls(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<UrlInsertRequest>,
+) -> Result<Json<UrlInsertResponse>, (StatusCode, String)> {
+    if request.urls.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "at least one url is required".to_string(),
+        ));
+    }
+
+    let metadata = validate_metadata(request.metadata)
+        .map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+    let track_id = generate_track_id("url");
+    let mut reports = Vec::with_capacity(request.urls.len());
+
+    for url in request.urls {
+        let url = url.trim().to_string();
+        let mut report = UrlReport {
+            url: url.clone(),
+            doc_id: None,
+            status: "rejected".to_string(),
+            message: String::new(),
+        };
+
+        let existing = state
+            .pipeline
+            .find_existing_document(&url)
+            .await
+            .map_err(|err| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to query document status: {err}"),
+                )
+            })?;
+        if let Some(existing) = existing {
+            report.doc_id = existing.id;
+            report.status = "duplicated".to_string();
+            report.message = format!(
+                "URL already exists in document storage (Status: {:?}).",
+                existing.status
+            );
+            reports.push(report);
+            continue;
+        }
+
+        let fetched = match state.url_fetcher.fetch(&url).await {
+            Ok(fetched) => fetched,
+            Err(err) => {
+                warn!(url = %url, error = %err, "failed to fetch url");
+                report.message = format!("{err:#}");
+                reports.push(report);
+                continue;
+            }
+        };
+
+        let input = DocumentInput {
+            content: fetched.content,
+            file_path: url.clone(),
+            metadata: Some(merge_objects(
+                metadata.clone(),
+                json!({
+                    "source_url": url,
+                    "final_url": fetched.url,
+                    "content_type": fetched.content_type,
+                    "fetched_at": chrono::Utc::now().to_rfc3339(),
+                }),
+            )),
+        };
+        let enqueued = state
+            .pipeline
+            .enqueue_documents(vec![input], &track_id)
+            .await
+            .map_err(|err| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to enqueue documents: {err}"),
+                )
+            })?;
+        if let Some(doc) = enqueued.into_iter().next() {
+            (report.status, report.message) = describe_enqueued(doc.status);
+            report.doc_id = doc.doc_id;
+        }
+        reports.push(report);
+    }
+
+    let accepted = reports.iter().filter(|r| r.status == "accepted").count();
+    if accepted > 0 {
+        let pipeline = state.pipeline.clone();
+        let scheduler = state.scheduler.clone();
+        tokio::spawn(async move {
+            if let Err(err) = pipeline.enqueue_pending_docs(scheduler).await {
+                warn!(error = %err, "background pipeline processing failed");
+            }
+        });
+    }
+
+    info!(track_id = %track_id, submitted = reports.len(), accepted, "urls submitted");
+
+    Ok(Json(UrlInsertResponse {
+        status: if accepted > 0 { "success" } else { "failure" }.to_string(),
+        message: format!("{accepted} of {} url(s) enqueued.", reports.len()),
+        track_id,
+        documents: reports,
+    }))
+}
+
+/// Removes an uploaded file that was left in the input directory because
+/// its document started processing after the upload was admitted, so the
+/// upload can be retried.
+async fn discard_if_in_progress(outcome: &EnqueuedDocument, file_path: &std::path::Path) {
+    if outcome.status != EnqueueStatus::InProgress {
+        return;
+    }
+    if let Err(err) = fs::remove_file(file_path).await {
+        warn!(error = %err, file = %file_path.display(), "failed to remove rejected upload");
+    }
+}
+
+/// Report status (`accepted`, `duplicated` or `rejected`) and message for
+/// how a document was enqueued.
+fn describe_enqueued(status: EnqueueStatus) -> (String, String) {
+    let (status, message) = match status {
+        EnqueueStatus::New => ("accepted", "Queued for processing."),
+        EnqueueStatus::Updated => (
+            "accepted",
+            "Queued as a new version of the document with the same name.",
+        ),
+        EnqueueStatus::Duplicate => (
+            "duplicated",
+            "Same content already exists in document storage.",
+        ),
+        EnqueueStatus::NearDuplicate => (
+            "duplicated",
+            "Near-duplicate of an existing document; skipped.",
+        ),
+        EnqueueStatus::Linked => ("accepted", "Linked as a version of a similar document."),
+        EnqueueStatus::Empty => ("rejected", "No text content."),
+        EnqueueStatus::Failed => ("rejected", "A pipeline hook failed on the content."),
+        EnqueueStatus::InProgress => (
+            "rejected",
+            "The document with the same name is still being processed; retry once it finishes.",
+        ),
+    };
+    (status.to_string(), message.to_string())
+}
+
+/// Checks caller-supplied metadata: it must be an object and its
+/// bibliographic fields (title, authors, journal, year, doi, tags) must have
+/// the right types.
+fn validate_metadata(metadata: Option<Value>) -> Result<Option<Value>, String> {
+    let Some(metadata) = metadata.filter(|value| !value.is_null()) else {
+        return Ok(None);
+    };
+    let fields = DocumentMetadata::from_value(&metadata).map_err(|err| format!("{err:#}"))?;
+    Ok(Some(fields.merge_into(Some(metadata))))
+}
+
+fn merge_objects(base: Option<Value>, overrides: Value) -> Value {
+    let mut merged = match base {
+        Some(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    if let Value::Object(fields) = overrides {
+        merged.extend(fields);
+    }
+    Value::Object(merged)
+}
+
+enum Admission {
+    Accepted {
+        file_name: String,
+        file_path: PathBuf,
+    },
+    Duplicated(String),
+    Rejected(String),
+}
+
+/// Validates an uploaded file and writes it to the input directory. Shared
+/// by single-file and archive uploads so both apply the same duplicate rules.
+async fn admit_file(
+    pipeline: &Pipeline,
+    original_filename: &str,
+    file_bytes: &[u8],
+) -> Result<Admission, (StatusCode, String)> {
+    let doc_manager = pipeline.document_manager();
+
+    let safe_filename = match doc_manager.sanitize_filename(original_filename) {
+        Ok(name) => name,
+        Err(err) => {
+            return Ok(Admission::Rejected(format!(
+                "invalid filename '{}': {err}",
+                original_filename
+            )));
+        }
+    };
+
+    if !doc_manager.is_supported_file(&safe_filename) {
+        return Ok(Admission::Rejected(format!(
+            "unsupported file type. supported types: {:?}",
+            crate::SUPPORTED_EXTENSIONS
+        )));
+    }
+
+    // a file name that is already stored is accepted as a new version unless
+    // its content is unchanged
+    let existing_doc = pipeline
+        .find_unchanged_document(&safe_filename, &String::from_utf8_lossy(file_bytes))
         .await
         .map_err(|err| {
             (
@@ -177,69 +717,221 @@ async fn upload_to_input_dir(
 
     if let Some(status) = existing_doc {
         let status_label = format!("{:?}", status.status);
-        return Ok(Json(InsertResponse {
-            status: "duplicated".to_string(),
-            message: format!(
-                "File '{}' already exists in document storage (Status: {}).",
-                safe_filename, status_label
-            ),
-            track_id: String::new(),
-        }));
+        return Ok(Admission::Duplicated(format!(
+            "File '{}' already exists in document storage (Status: {}).",
+            safe_filename, status_label
+        )));
+    }
+    let processing = pipeline
+        .find_existing_document(&safe_filename)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to query document status: {err}"),
+            )
+        })?
+        .is_some_and(|status| in_progress(&status));
+    if processing {
+        return Ok(Admission::Rejected(format!(
+            "File '{}' is still being processed; upload the new version once it finishes.",
+            safe_filename
+        )));
     }
 
-    let target_dir = doc_manager.input_dir();
-    let file_path = target_dir.join(&safe_filename);
+    let file_path = doc_manager.input_dir().join(&safe_filename);
 
     if file_path.exists() {
-        return Ok(Json(InsertResponse {
-            status: "duplicated".to_string(),
-            message: format!(
-                "File '{}' already exists in the input directory.",
-                safe_filename
-            ),
-            track_id: String::new(),
-        }));
+        return Ok(Admission::Duplicated(format!(
+            "File '{}' already exists in the input directory.",
+            safe_filename
+        )));
     }
 
-    fs::write(&file_path, &file_bytes).await.map_err(|err| {
+    fs::write(&file_path, file_bytes).await.map_err(|err| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("failed to persist uploaded file: {err}"),
         )
     })?;
 
-    let pipeline = state.pipeline.clone();
-    let enqueue_path = file_path.clone();
-    let track_id = pipeline
-        .enqueue_file(enqueue_path, None)
-        .await
-        .map_err(|err| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("failed to enqueue document: {err}"),
-            )
-        })?;
+    Ok(Admission::Accepted {
+        file_name: safe_filename,
+        file_path,
+    })
+}
 
-    let background_pipeline = pipeline.clone();
+/// Unpacks a zip or tar.gz upload and enqueues every supported file under a
+/// single track id.
+async fn upload_archive(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<Json<ArchiveUploadResponse>, (StatusCode, String)> {
+    let mut upload: Option<(String, Vec<u8>)> = None;
 
-    tokio::spawn(async move {
-        if let Err(err) = background_pipeline.enqueue_pending_docs(scheduler).await {
-            warn!(error = %err, "background pipeline processing failed");
+    while let Some(field) = multipart.next_field().await.map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("invalid multipart payload: {err}"),
+        )
+    })? {
+        if field.name() == Some("file") {
+            let file_name = field
+                .file_name()
+                .map(|name| name.to_string())
+                .ok_or_else(|| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        "uploaded file missing filename".to_string(),
+                    )
+                })?;
+            let data = field.bytes().await.map_err(|err| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("failed to read upload field: {err}"),
+                )
+            })?;
+            upload = Some((file_name, data.to_vec()));
+            break;
         }
-    });
+    }
+
+    let (archive_name, archive_bytes) = upload.ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            "missing file field in multipart payload".to_string(),
+        )
+    })?;
 
-    info!(filename = %safe_filename, track_id = %track_id, "file uploaded successfully");
+    let kind = ArchiveKind::from_file_name(&archive_name).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            "unsupported archive type. supported types: .zip, .tar.gz, .tgz".to_string(),
+        )
+    })?;
 
-    Ok(Json(InsertResponse {
-        status: "success".to_string(),
+    let entries = tokio::task::spawn_blocking(move || {
+        unpack_archive(kind, &archive_bytes, &ArchiveLimits::default())
+    })
+    .await
+    .map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("archive extraction panicked: {err}"),
+        )
+    })?
+    .map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("failed to unpack '{archive_name}': {err:#}"),
+        )
+    })?;
+
+    let track_id = generate_track_id("archive");
+    let files = enqueue_archive_entries(&state.pipeline, entries, &track_id).await?;
+
+    let count = |status: &str| files.iter().filter(|f| f.status == status).count();
+    let (accepted, duplicated, rejected) =
+        (count("accepted"), count("duplicated"), count("rejected"));
+
+    if accepted > 0 {
+        let pipeline = state.pipeline.clone();
+        let scheduler = state.scheduler.clone();
+        tokio::spawn(async move {
+            if let Err(err) = pipeline.enqueue_pending_docs(scheduler).await {
+                warn!(error = %err, "background pipeline processing failed");
+            }
+        });
+    }
+
+    info!(
+        archive = %archive_name,
+        track_id = %track_id,
+        accepted,
+        duplicated,
+        rejected,
+        "archive uploaded"
+    );
+
+    Ok(Json(ArchiveUploadResponse {
+        status: if accepted > 0 { "success" } else { "failure" }.to_string(),
         message: format!(
-            "File '{}' uploaded successfully. Processing will continue in background.",
-            safe_filename
+            "Archive '{archive_name}': {accepted} accepted, {duplicated} duplicated, {rejected} rejected."
         ),
         track_id,
+        accepted,
+        duplicated,
+        rejected,
+        files,
     }))
 }
 
+/// Admits and enqueues the files of an unpacked archive, reporting for each
+/// whether it was accepted, duplicated or rejected.
+async fn enqueue_archive_entries(
+    pipeline: &Pipeline,
+    entries: Vec<ArchiveEntry>,
+    track_id: &str,
+) -> Result<Vec<ArchiveFileReport>, (StatusCode, String)> {
+    let mut files = Vec::with_capacity(entries.len());
+    // document names are flat, so entries from different folders can collide
+    let mut seen_names: HashMap<String, String> = HashMap::new();
+
+    for entry in entries {
+        let mut report = ArchiveFileReport {
+            path: entry.path,
+            file_name: entry.file_name.clone(),
+            status: "rejected".to_string(),
+            message: String::new(),
+        };
+        let (file_name, bytes) = match (entry.file_name, entry.contents) {
+            (Some(file_name), Ok(bytes)) => (file_name, bytes),
+            (_, Err(reason)) => {
+                report.message = reason;
+                files.push(report);
+                continue;
+            }
+            (None, Ok(_)) => {
+                report.message = "entry has no file name".to_string();
+                files.push(report);
+                continue;
+            }
+        };
+
+        if let Some(first_path) = seen_names.get(&file_name) {
+            report.message = format!(
+                "'{}' would be stored as '{file_name}', the name already taken by '{first_path}'; rename one of them to ingest both.",
+                report.path
+            );
+            files.push(report);
+            continue;
+        }
+        seen_names.insert(file_name.clone(), report.path.clone());
+
+        match admit_file(pipeline, &file_name, &bytes).await? {
+            Admission::Accepted { file_path, .. } => {
+                match pipeline
+                    .enqueue_file(file_path.clone(), track_id, None)
+                    .await
+                {
+                    Ok(outcome) => {
+                        discard_if_in_progress(&outcome, &file_path).await;
+                        (report.status, report.message) = describe_enqueued(outcome.status)
+                    }
+                    Err(err) => report.message = format!("{err:#}"),
+                }
+            }
+            Admission::Duplicated(message) => {
+                report.status = "duplicated".to_string();
+                report.message = message;
+            }
+            Admission::Rejected(message) => report.message = message,
+        }
+        files.push(report);
+    }
+    Ok(files)
+}
+
 fn map_status(status: &crate::storage::DocStatus) -> String {
     use crate::storage::DocStatus;
     match status {
@@ -262,3 +954,86 @@ fn random_string(len: usize) -> String {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use super::*;
+    use crate::pipeline::{PipelineConfig, pipeline::tests::test_pipeline};
+
+    #[tokio::test]
+    async fn archive_report_flags_duplicate_content_and_invalid_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, PipelineConfig::default()).await;
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in [
+            (
+                "papers/rapamycin.txt",
+                b"Rapamycin inhibits MTOR in mice.".as_slice(),
+            ),
+            (
+                "papers/copy.txt",
+                b"Rapamycin inhibits MTOR in mice.".as_slice(),
+            ),
+            ("papers/scan.txt", [0xff, 0xfe, 0x00, 0x41].as_slice()),
+            ("papers/figure.png", b"PNG".as_slice()),
+        ] {
+            writer
+                .start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        let archive = writer.finish().unwrap().into_inner();
+        let entries =
+            unpack_archive(ArchiveKind::Zip, &archive, &ArchiveLimits::default()).unwrap();
+
+        let files = enqueue_archive_entries(&pipeline, entries, "archive-test")
+            .await
+            .unwrap();
+
+        let statuses: Vec<(&str, &str)> = files
+            .iter()
+            .map(|file| (file.path.as_str(), file.status.as_str()))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("papers/rapamycin.txt", "accepted"),
+                ("papers/copy.txt", "duplicated"),
+                ("papers/scan.txt", "rejected"),
+                ("papers/figure.png", "rejected"),
+            ]
+        );
+        assert!(files[2].message.contains("not valid UTF-8"));
+    }
+
+    #[tokio::test]
+    async fn archive_entries_sharing_a_file_name_are_rejected_not_duplicated() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let pipeline = test_pipeline(&dir, PipelineConfig::default()).await;
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in [
+            ("a/results.md", "Rapamycin inhibits MTOR in mice."),
+            ("b/results.md", "Metformin activates AMPK in rats."),
+        ] {
+            writer
+                .start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        let archive = writer.finish().unwrap().into_inner();
+        let entries =
+            unpack_archive(ArchiveKind::Zip, &archive, &ArchiveLimits::default()).unwrap();
+
+        let files = enqueue_archive_entries(&pipeline, entries, "archive-test")
+            .await
+            .unwrap();
+
+        assert_eq!(files[0].status, "accepted");
+        assert_eq!(files[1].status, "rejected");
+        assert!(files[1].message.contains("already taken by 'a/results.md'"));
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppState,
+    pipeline::{DecideError, EntityResolver, MergeRecord, MergeStatus, Ontology},
+};
+
+#[derive(Deserialize)]
+struct MergeListQuery {
+    #[serde(default)]
+    status: Option<MergeStatus>,
+}
+
+#[derive(Serialize)]
+struct MergeListResponse {
+    total: usize,
+    merges: Vec<MergeItem>,
+}
+
+#[derive(Serialize)]
+struct MergeItem {
+    id: String,
+    #[serde(flatten)]
+    record: MergeRecord,
+}
+
+pub fn entity_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/entities/types", get(entity_types))
+        .route("/entities/merges", get(list_merges))
+        .route("/entities/merges/{id}/approve", post(approve_merge))
+        .route("/entities/merges/{id}/reject", post(reject_merge))
+}
+
+/// The ontology entities are extracted with: type names, descriptions,
+/// colors and parent types.
+async fn entity_types(State(state): State<Arc<AppState>>) -> Json<Ontology> {
+    Json(state.pipeline.config.ontology.as_ref().clone())
+}
+
+/// Lists entity resolution candidates and decisions, e.g.
+/// `GET /entities/merges?status=pending` for the review queue.
+async fn list_merges(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<MergeListQuery>,
+) -> Result<Json<MergeListResponse>, (StatusCode, String)> {
+    let merges = resolver(&state)?
+        .merges(query.status)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to load merges: {err}"),
+            )
+        })?;
+    Ok(Json(MergeListResponse {
+        total: merges.len(),
+        merges: merges
+            .into_iter()
+            .map(|(id, record)| MergeItem { id, record })
+            .collect(),
+    }))
+}
+
+async fn approve_merge(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<MergeItem>, (StatusCode, String)> {
+    decide(&state, id, true).await
+}
+
+async fn reject_merge(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<MergeItem>, (StatusCode, String)> {
+    decide(&state, id, false).await
+}
+
+async fn decide(
+    state: &AppState,
+    id: String,
+    approve: bool,
+) -> Result<Json<MergeItem>, (StatusCode, String)> {
+    let record = resolver(state)?.decide(&id, approve).await.map_err(|err| {
+        let status = match err {
+            DecideError::NotFound(_) => StatusCode::NOT_FOUND,
+            DecideError::Conflict(_) => StatusCode::CONFLICT,
+            DecideError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, err.to_string())
+    })?;
+    state.pipeline.persist_all().await.map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to persist merge: {err}"),
+        )
+    })?;
+    Ok(Json(MergeItem { id, record }))
+}
+
+fn resolver(state: &AppState) -> Result<&EntityResolver, (StatusCode, String)> {
+    state.pipeline.entity_resolver.as_deref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            "entity resolution is disabled".to_string(),
+        )
+    })
+}
@@ -10,11 +10,13 @@ use super::types::{
 };
 use crate::{
     AppState,
+    ai::schemas::Modality,
     pipeline::{
-        types::{EntityNode, RelationEdge},
+        canonical::collapse_to_canonical,
+        types::{EdgeFilter, EntityNode, RelationEdge},
         utils::{get_all_entities, get_all_relationships},
     },
-    storage::{JsonKvStorage, JsonKvStorageConfig, KvStorage},
+    storage::{DocFilter, JsonKvStorage, JsonKvStorageConfig, KvStorage},
 };
 use anyhow::Result;
 use axum::{
@@ -54,8 +56,10 @@ pub fn graph_routes() -> Router<Arc<AppState>> {
 
 async fn get_graph(
     State(state): State<Arc<AppState>>,
+    Query(filter): Query<DocFilter>,
+    Query(edge_filter): Query<EdgeFilter>,
 ) -> Result<Json<GraphResponse>, (StatusCode, String)> {
-    let all_entities = get_all_entities(state.storages.full_entities.as_ref())
+    let mut all_entities = get_all_entities(state.storages.full_entities.as_ref())
         .await
         .map_err(|err| {
             (
@@ -63,7 +67,7 @@ async fn get_graph(
                 format!("error getting entities {}", err),
             )
         })?;
-    let all_relationships = get_all_relationships(state.storages.full_relations.as_ref())
+    let mut all_relationships = get_all_relationships(state.storages.full_relations.as_ref())
         .await
         .map_err(|err| {
             (
@@ -71,6 +75,16 @@ async fn get_graph(
                 format!("error getting relationships {}", err),
             )
         })?;
+    restrict_to_documents(&filter, &mut all_entities, &mut all_relationships);
+    all_relationships.retain(|_, relation| {
+        edge_filter.matches(relation)
+            && edge_filter.matches_endpoints(
+                all_entities.get(&relation.source_entity_id),
+                all_entities.get(&relation.target_entity_id),
+            )
+    });
+    let (all_entities, all_relationships) =
+        collapse_to_canonical(&all_entities, &all_relationships);
     let mut entities_vec = Vec::new();
     let mut relations_vec = Vec::new();
     for (entity_id, entity_node) in all_entities {
@@ -79,6 +93,7 @@ async fn get_graph(
             entity_name: entity_node.entity_name,
             entity_description: entity_node.entity_description,
             entity_type: entity_node.entity_type,
+            mention_count: entity_node.mention_count,
         });
     }
     for (relationship_id, relationship_edge) in all_relationships {
@@ -87,6 +102,9 @@ async fn get_graph(
             source_node_id: relationship_edge.source_entity_id,
             target_node_id: relationship_edge.target_entity_id,
             relation_description: relationship_edge.relationship_description,
+            confidence: relationship_edge.confidence,
+            modality: relationship_edge.modality,
+            predicate: relationship_edge.predicate,
         });
     }
     Ok(Json(GraphResponse {
@@ -108,8 +126,10 @@ struct GraphSearchQuery {
 async fn graph_search(
     State(state): State<Arc<AppState>>,
     Query(params): Query<GraphSearchQuery>,
+    Query(filter): Query<DocFilter>,
+    Query(edge_filter): Query<EdgeFilter>,
 ) -> Result<Json<GraphSearchResponse>, (StatusCode, String)> {
-    let all_entities = get_all_entities(state.storages.full_entities.as_ref())
+    let mut all_entities = get_all_entities(state.storages.full_entities.as_ref())
         .await
         .map_err(|err| {
             (
@@ -117,7 +137,7 @@ async fn graph_search(
                 format!("error getting entities {err}"),
             )
         })?;
-    let all_relationships = get_all_relationships(state.storages.full_relations.as_ref())
+    let mut all_relationships = get_all_relationships(state.storages.full_relations.as_ref())
         .await
         .map_err(|err| {
             let full_error = err
@@ -131,6 +151,18 @@ async fn graph_search(
             )
         })?;
 
+    restrict_to_documents(&filter, &mut all_entities, &mut all_relationships);
+    // dropped before the graph is built so paths never cross excluded edges
+    all_relationships.retain(|_, relation| {
+        edge_filter.matches(relation)
+            && edge_filter.matches_endpoints(
+                all_entities.get(&relation.source_entity_id),
+                all_entities.get(&relation.target_entity_id),
+            )
+    });
+    let (all_entities, all_relationships) =
+        collapse_to_canonical(&all_entities, &all_relationships);
+
     let (graph, node_ids) = build_graph(&all_entities, &all_relationships);
 
     let query = params.q.as_ref().and_then(|q| {
@@ -180,6 +212,20 @@ async fn graph_search(
     }
 }
 
+/// Keeps only entities and relations extracted from documents whose metadata
+/// matches `filter`, e.g. `?year_from=2020&tags=mouse-study`.
+fn restrict_to_documents(
+    filter: &DocFilter,
+    entities: &mut HashMap<String, EntityNode>,
+    relationships: &mut HashMap<String, RelationEdge>,
+) {
+    if filter.is_empty() {
+        return;
+    }
+    entities.retain(|_, entity| filter.matches(&entity.doc_metadata));
+    relationships.retain(|_, relation| filter.matches(&relation.doc_metadata));
+}
+
 fn build_graph(
     all_entities: &HashMap<String, EntityNode>,
     all_relationships: &HashMap<String, RelationEdge>,
@@ -285,9 +331,22 @@ fn build_path_as_str(
         if let Some((relation, _is_forward)) = find_edge(graph, a, b) {
             let node_a = &graph[a];
             let node_b = &graph[b];
+            let qualifier = match relation.modality {
+                Modality::Asserted => String::new(),
+                modality => format!(" ({})", modality.as_str()),
+            };
+            let predicate = relation
+                .predicate
+                .as_deref()
+                .map(|predicate| format!("[{predicate}] "))
+                .unwrap_or_default();
             path_strs.push(format!(
-                "{} ---  {}  ---> {}",
-                node_a.entity_name, relation.relationship_description, node_b.entity_name
+                "{} ---  {}{}{}  ---> {}",
+                node_a.entity_name,
+                predicate,
+                relation.relationship_description,
+                qualifier,
+                node_b.entity_name
             ));
         };
     }
@@ -351,6 +410,9 @@ fn build_path(
                 source_entity_id: relation.source_entity_id.clone(),
                 target_entity_id: relation.target_entity_id.clone(),
                 is_forward,
+                confidence: relation.confidence,
+                modality: relation.modality,
+                predicate: relation.predicate.clone(),
             });
         }
     }
@@ -369,6 +431,7 @@ fn build_entity(
         entity_name: entity.entity_name.clone(),
         entity_description: entity.entity_description.clone(),
         entity_type: entity.entity_type.clone(),
+        mention_count: entity.mention_count,
     }
 }
 
@@ -409,7 +472,13 @@ fn find_symptom_nodes(
             let entity = &graph[idx];
             is_symptom(entity)
                 && match query {
-                    Some(q) if !q.is_empty() => matches_query(&entity.entity_name, q),
+                    Some(q) if !q.is_empty() => {
+                        matches_query(&entity.entity_name, q)
+                            || entity
+                                .normalization
+                                .as_ref()
+                                .is_some_and(|entry| entry.mentions(q))
+                    }
                     _ => true,
                 }
         })
@@ -1,9 +1,13 @@
 pub mod documents;
 pub mod download;
+pub mod entities;
 pub mod graph;
+pub mod usage;
 
 pub mod types;
 
 pub use documents::document_routes;
 pub use download::download_routes;
+pub use entities::entity_routes;
 pub use graph::graph_routes;
+pub use usage::usage_routes;
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+use crate::ai::schemas::Modality;
+
 #[derive(Default, Clone, Debug, Deserialize, TS, Serialize)]
 #[ts(export)]
 pub struct EntityResponse {
@@ -8,6 +10,8 @@ pub struct EntityResponse {
     pub entity_name: String,
     pub entity_description: String,
     pub entity_type: String,
+    /// Per-document mentions merged into this canonical entity.
+    pub mention_count: u32,
 }
 
 #[derive(Default, Clone, Debug, Deserialize, TS, Serialize)]
@@ -17,6 +21,9 @@ pub struct RelationshipEdgeResponse {
     pub source_node_id: String,
     pub target_node_id: String,
     pub relation_description: String,
+    pub confidence: Option<f32>,
+    pub modality: Modality,
+    pub predicate: Option<String>,
 }
 
 #[derive(Serialize, TS)]
@@ -33,6 +40,8 @@ pub struct GraphSearchEntity {
     pub entity_name: String,
     pub entity_description: String,
     pub entity_type: String,
+    /// Per-document mentions merged into this canonical entity.
+    pub mention_count: u32,
 }
 
 #[derive(Default, Clone, Debug, Deserialize, TS, Serialize)]
@@ -43,6 +52,9 @@ pub struct GraphSearchEdge {
     pub source_entity_id: String,
     pub target_entity_id: String,
     pub is_forward: bool,
+    pub confidence: Option<f32>,
+    pub modality: Modality,
+    pub predicate: Option<String>,
 }
 
 #[derive(Default, Clone, Debug, Deserialize, TS, Serialize)]
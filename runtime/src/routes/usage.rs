@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+};
+use serde::Deserialize;
+
+use crate::{AppState, pipeline::UsageReport};
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct UsageQuery {
+    track_id: Option<String>,
+    doc_id: Option<String>,
+}
+
+pub fn usage_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/usage", get(get_usage))
+}
+
+/// Tokens and cost of LLM calls, in total and per upload batch, document
+/// and model, e.g. `GET /usage?track_id=upload-...` for one batch.
+async fn get_usage(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageReport>, (StatusCode, String)> {
+    let records = state.pipeline.usage.records().await.map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to load usage: {err}"),
+        )
+    })?;
+    let matching = records.iter().filter(|record| {
+        query
+            .track_id
+            .as_ref()
+            .is_none_or(|track_id| record.track_id.as_ref() == Some(track_id))
+            && query
+                .doc_id
+                .as_ref()
+                .is_none_or(|doc_id| &record.doc_id == doc_id)
+    });
+    Ok(Json(UsageReport::from_records(matching)))
+}
@@ -14,7 +14,9 @@ use serde_json::{Map, Value};
 use tokio::sync::RwLock;
 
 use super::io::{ensure_parent_dir, load_or_default, write_json_file};
-use super::{DocProcessingStatus, DocStatus, DocStatusStorage};
+use super::{
+    DocFilter, DocProcessingStatus, DocStatus, DocStatusStorage, DocumentMetadata, ERROR_ID_PREFIX,
+};
 
 #[derive(Clone, Debug)]
 pub struct JsonDocStatusConfig {
@@ -233,13 +235,19 @@ impl DocStatusStorage for JsonDocStatusStorage {
 
     async fn get_doc_by_file_path(&self, file_path: &str) -> Result<Option<DocProcessingStatus>> {
         let guard = self.data.read().await;
-        Ok(guard.iter().find_map(|(id, record)| {
-            record
-                .file_path
-                .as_deref()
-                .filter(|fp| *fp == file_path)
-                .map(|_| record.to_status(id))
-        }))
+        // failures to ingest a file are recorded under its path as well; the
+        // stored document wins over them
+        let mut found = None;
+        for (id, record) in guard.iter() {
+            if record.file_path.as_deref() != Some(file_path) {
+                continue;
+            }
+            if !id.starts_with(ERROR_ID_PREFIX) {
+                return Ok(Some(record.to_status(id)));
+            }
+            found.get_or_insert_with(|| record.to_status(id));
+        }
+        Ok(found)
     }
 
     async fn filter_keys(&self, keys: &HashSet<String>) -> Result<HashSet<String>> {
@@ -301,6 +309,7 @@ impl DocStatusStorage for JsonDocStatusStorage {
     async fn docs_paginated(
         &self,
         status_filter: Option<&DocStatus>,
+        metadata_filter: Option<&DocFilter>,
         page: usize,
         page_size: usize,
         sort_field: &str,
@@ -323,6 +332,11 @@ impl DocStatusStorage for JsonDocStatusStorage {
                         return None;
                     }
                 }
+                if let Some(filter) = metadata_filter.filter(|filter| !filter.is_empty())
+                    && !filter.matches(&DocumentMetadata::from_stored(Some(&record.metadata)))
+                {
+                    return None;
+                }
                 Some((id.clone(), record.clone()))
             })
             .collect();
@@ -0,0 +1,303 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ts_rs::TS;
+
+/// Bibliographic fields of a document. They live next to pipeline keys such
+/// as `versions` in `DocProcessingStatus.metadata` and are copied onto every
+/// entity and relation extracted from the document.
+#[derive(Default, Clone, Debug, PartialEq, Deserialize, TS, Serialize)]
+#[serde(default)]
+#[ts(export)]
+pub struct DocumentMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[ts(as = "Option<Vec<String>>", optional)]
+    pub authors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub journal: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub year: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub doi: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[ts(as = "Option<Vec<String>>", optional)]
+    pub tags: Vec<String>,
+}
+
+impl DocumentMetadata {
+    /// Reads the bibliographic fields of a metadata object, ignoring any
+    /// other keys. Fails when a known field has the wrong type.
+    pub fn from_value(value: &Value) -> Result<Self> {
+        if !value.is_object() {
+            return Err(anyhow!("metadata must be a JSON object"));
+        }
+        let metadata: Self =
+            serde_json::from_value(value.clone()).context("invalid document metadata")?;
+        metadata.normalized()
+    }
+
+    /// Same as [`DocumentMetadata::from_value`] but treats missing or invalid
+    /// metadata as empty; used when reading back stored documents.
+    pub fn from_stored(value: Option<&Value>) -> Self {
+        value
+            .and_then(|value| Self::from_value(value).ok())
+            .unwrap_or_default()
+    }
+
+    /// Trims every field, lowercases tags and strips resolver prefixes from
+    /// DOIs so filters compare like with like.
+    fn normalized(self) -> Result<Self> {
+        if let Some(year) = self.year
+            && !(1000..=9999).contains(&year)
+        {
+            return Err(anyhow!("year {year} is out of range"));
+        }
+
+        let doi = non_empty(self.doi).map(|doi| normalize_doi(&doi));
+
+        let mut tags: Vec<String> = self
+            .tags
+            .into_iter()
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        tags.sort();
+        tags.dedup();
+
+        Ok(Self {
+            title: non_empty(self.title),
+            authors: self
+                .authors
+                .into_iter()
+                .filter_map(|author| non_empty(Some(author)))
+                .collect(),
+            journal: non_empty(self.journal),
+            year: self.year,
+            doi,
+            tags,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Writes the fields into a metadata object, replacing the ones present
+    /// in `self` and keeping every other key.
+    pub fn merge_into(&self, metadata: Option<Value>) -> Value {
+        let mut target = match metadata {
+            Some(Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        if let Ok(Value::Object(fields)) = serde_json::to_value(self) {
+            target.extend(fields);
+        }
+        Value::Object(target)
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Restricts documents, or graph elements through their provenance, by
+/// bibliographic metadata. Empty fields match everything. Deserializes from
+/// query strings such as `?year_from=2020&tags=mouse-study,rapamycin`.
+#[derive(Default, Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct DocFilter {
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+    /// The document must carry every listed tag.
+    #[serde(deserialize_with = "comma_separated")]
+    pub tags: Vec<String>,
+    /// Case-insensitive exact match.
+    pub journal: Option<String>,
+    /// Case-insensitive substring of any author.
+    pub author: Option<String>,
+    pub doi: Option<String>,
+}
+
+impl DocFilter {
+    pub fn is_empty(&self) -> bool {
+        self.year_from.is_none()
+            && self.year_to.is_none()
+            && self.tags.is_empty()
+            && self.journal.is_none()
+            && self.author.is_none()
+            && self.doi.is_none()
+    }
+
+    pub fn matches(&self, metadata: &DocumentMetadata) -> bool {
+        if self.year_from.is_some() || self.year_to.is_some() {
+            let Some(year) = metadata.year else {
+                return false;
+            };
+            if self.year_from.is_some_and(|from| year < from)
+                || self.year_to.is_some_and(|to| year > to)
+            {
+                return false;
+            }
+        }
+        if !self
+            .tags
+            .iter()
+            .all(|tag| metadata.tags.contains(&tag.trim().to_lowercase()))
+        {
+            return false;
+        }
+        if let Some(journal) = &self.journal
+            && !metadata
+                .journal
+                .as_deref()
+                .is_some_and(|value| value.eq_ignore_ascii_case(journal.trim()))
+        {
+            return false;
+        }
+        if let Some(author) = &self.author {
+            let needle = author.trim().to_lowercase();
+            if !metadata
+                .authors
+                .iter()
+                .any(|value| value.to_lowercase().contains(&needle))
+            {
+                return false;
+            }
+        }
+        if let Some(doi) = &self.doi
+            && metadata.doi.as_deref() != Some(normalize_doi(doi).as_str())
+        {
+            return false;
+        }
+        true
+    }
+}
+
+/// Lowercases a DOI and strips the resolver URL or `doi:` prefix, so
+/// `https://doi.org/10.1038/Nature08221` and `10.1038/nature08221` compare
+/// equal.
+fn normalize_doi(doi: &str) -> String {
+    let lower = doi.trim().to_ascii_lowercase();
+    [
+        "https://doi.org/",
+        "http://doi.org/",
+        "https://dx.doi.org/",
+        "doi:",
+    ]
+    .iter()
+    .find_map(|prefix| lower.strip_prefix(prefix))
+    .map(str::trim)
+    .unwrap_or(&lower)
+    .to_string()
+}
+
+fn comma_separated<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw = Option::<String>::deserialize(deserializer)?;
+    Ok(raw
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn metadata_is_normalized_and_other_keys_ignored() {
+        let metadata = DocumentMetadata::from_value(&json!({
+            "title": "  Rapamycin in mice ",
+            "authors": ["Harrison", " "],
+            "year": 2009,
+            "doi": "https://doi.org/10.1038/Nature08221",
+            "tags": ["Mouse-Study", "mouse-study", ""],
+            "versions": [],
+        }))
+        .unwrap();
+
+        assert_eq!(metadata.title.as_deref(), Some("Rapamycin in mice"));
+        assert_eq!(metadata.authors, vec!["Harrison"]);
+        assert_eq!(metadata.doi.as_deref(), Some("10.1038/nature08221"));
+        assert_eq!(metadata.tags, vec!["mouse-study"]);
+
+        assert!(DocumentMetadata::from_value(&json!({ "year": "2009" })).is_err());
+        assert!(DocumentMetadata::from_value(&json!({ "year": 20090 })).is_err());
+    }
+
+    #[test]
+    fn filter_requires_every_condition() {
+        let metadata = DocumentMetadata {
+            year: Some(2021),
+            tags: vec!["mouse-study".to_string()],
+            journal: Some("Nature".to_string()),
+            authors: vec!["Jane Harrison".to_string()],
+            ..DocumentMetadata::default()
+        };
+
+        let after_2020 = DocFilter {
+            year_from: Some(2020),
+            tags: vec!["Mouse-Study".to_string()],
+            ..DocFilter::default()
+        };
+        assert!(after_2020.matches(&metadata));
+        assert!(
+            DocFilter {
+                author: Some("harrison".to_string()),
+                journal: Some("nature".to_string()),
+                ..DocFilter::default()
+            }
+            .matches(&metadata)
+        );
+        assert!(
+            !DocFilter {
+                year_to: Some(2020),
+                ..DocFilter::default()
+            }
+            .matches(&metadata)
+        );
+        assert!(!after_2020.matches(&DocumentMetadata::default()));
+    }
+
+    #[test]
+    fn doi_filter_accepts_any_spelling_of_the_stored_doi() {
+        let metadata = DocumentMetadata::from_value(&json!({
+            "doi": "https://doi.org/10.1038/Nature08221",
+        }))
+        .unwrap();
+
+        for doi in [
+            "10.1038/nature08221",
+            "https://doi.org/10.1038/NATURE08221",
+            "doi:10.1038/Nature08221",
+        ] {
+            let filter = DocFilter {
+                doi: Some(doi.to_string()),
+                ..DocFilter::default()
+            };
+            assert!(filter.matches(&metadata), "{doi}");
+        }
+        assert!(
+            !DocFilter {
+                doi: Some("10.1038/nature08222".to_string()),
+                ..DocFilter::default()
+            }
+            .matches(&metadata)
+        );
+    }
+}
@@ -8,11 +8,13 @@ pub mod io;
 pub mod json_doc_status;
 pub mod json_kv;
 pub mod manager;
+pub mod metadata;
 
 pub use io::*;
 pub use json_doc_status::{JsonDocStatusConfig, JsonDocStatusStorage};
 pub use json_kv::{JsonKvStorage, JsonKvStorageConfig};
 pub use manager::{StorageManager, StoragesStatus};
+pub use metadata::{DocFilter, DocumentMetadata};
 
 pub type StorageResult<T> = Result<T>;
 
@@ -35,6 +37,10 @@ pub trait KvStorage: Send + Sync {
     async fn sync_if_dirty(&self) -> StorageResult<()>;
 }
 
+/// Prefix of the ids of statuses that record a file failing to ingest
+/// rather than a stored document.
+pub const ERROR_ID_PREFIX: &str = "error-";
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub enum DocStatus {
     #[default]
@@ -61,6 +67,13 @@ pub struct DocProcessingStatus {
     pub error_msg: Option<String>,
 }
 
+impl DocProcessingStatus {
+    /// Bibliographic fields stored in `metadata`.
+    pub fn document_metadata(&self) -> DocumentMetadata {
+        DocumentMetadata::from_stored(self.metadata.as_ref())
+    }
+}
+
 #[async_trait]
 pub trait DocStatusStorage: Send + Sync {
     async fn initialize(&self) -> StorageResult<()>;
@@ -97,6 +110,7 @@ pub trait DocStatusStorage: Send + Sync {
     async fn docs_paginated(
         &self,
         status_filter: Option<&DocStatus>,
+        metadata_filter: Option<&DocFilter>,
         page: usize,
         page_size: usize,
         sort_field: &str,
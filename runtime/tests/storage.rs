@@ -1,8 +1,8 @@
 use std::collections::{HashMap, HashSet};
 
 use runtime::storage::{
-    DocProcessingStatus, DocStatus, DocStatusStorage, JsonDocStatusConfig, JsonDocStatusStorage,
-    JsonKvStorage, JsonKvStorageConfig, KvStorage,
+    DocFilter, DocProcessingStatus, DocStatus, DocStatusStorage, JsonDocStatusConfig,
+    JsonDocStatusStorage, JsonKvStorage, JsonKvStorageConfig, KvStorage,
 };
 use serde_json::json;
 use tempfile::TempDir;
@@ -178,11 +178,28 @@ async fn json_doc_status_roundtrip_and_pagination() -> anyhow::Result<()> {
     assert_eq!(by_track.len(), 2);
 
     let (page, total) = storage
-        .docs_paginated(Some(&DocStatus::PROCESSED), 1, 2, "updated_at", "desc")
+        .docs_paginated(
+            Some(&DocStatus::PROCESSED),
+            None,
+            1,
+            2,
+            "updated_at",
+            "desc",
+        )
         .await?;
     assert_eq!(total, 2);
     assert_eq!(page.len(), 2);
 
+    let science = DocFilter {
+        tags: vec!["science".into()],
+        ..DocFilter::default()
+    };
+    let (page, total) = storage
+        .docs_paginated(None, Some(&science), 1, 10, "updated_at", "desc")
+        .await?;
+    assert_eq!(total, 1);
+    assert_eq!(page[0].0, "doc-3");
+
     storage.delete(&["doc-2".to_string()]).await?;
     storage.sync_if_dirty().await?;
     assert!(storage.get_by_id("doc-2").await?.is_none());
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The dictionary entry an entity name was matched to.
+ */
+export type DictionaryEntry = { 
+/**
+ * Canonical identifier, e.g. `HGNC:3942`.
+ */
+id: string, source: string, 
+/**
+ * Preferred name, e.g. `MTOR`.
+ */
+name: string, synonyms: Array<string>, };
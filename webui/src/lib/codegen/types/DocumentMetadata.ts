@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Bibliographic fields of a document. They live next to pipeline keys such
+ * as `versions` in `DocProcessingStatus.metadata` and are copied onto every
+ * entity and relation extracted from the document.
+ */
+export type DocumentMetadata = { title?: string, authors?: Array<string>, journal?: string, year?: number, doi?: string, tags?: Array<string>, };
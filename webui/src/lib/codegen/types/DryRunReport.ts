@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FileEstimate } from "./FileEstimate";
+
+export type DryRunReport = { model: string, files: Array<FileEstimate>, chunks: number, tokens: bigint, llm_calls: bigint, estimated_input_tokens: bigint, estimated_output_tokens: bigint, 
+/**
+ * Cost of the files that could be priced.
+ */
+estimated_cost_usd: number, };
@@ -1,3 +1,37 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DictionaryEntry } from "./DictionaryEntry";
+import type { DocumentMetadata } from "./DocumentMetadata";
+import type { Evidence } from "./Evidence";
+import type { SourcedDescription } from "./SourcedDescription";
 
-export type EntityNode = { chunk_id: string, chunk_order_index: number, doc_id: string, entity_description: string, entity_name: string, entity_type: string, };
+export type EntityNode = { chunk_id: string, chunk_order_index: number, doc_id: string, entity_description: string, 
+/**
+ * Descriptions from every chunk the entity was extracted from;
+ * `entity_description` joins or summarizes them.
+ */
+descriptions: Array<SourcedDescription>, 
+/**
+ * Verified quotes supporting the entity, with their offsets.
+ */
+evidence: Array<Evidence>, entity_name: string, entity_type: string, 
+/**
+ * Metadata of the source document, for filtering the graph.
+ */
+doc_metadata: DocumentMetadata, 
+/**
+ * Entity this mention belongs to across documents.
+ */
+canonical_id: string | null, 
+/**
+ * Number of mentions merged into this node when the graph is built over
+ * canonical entities; 0 on stored mentions.
+ */
+mention_count: number, 
+/**
+ * Created for a relationship endpoint that matched no extracted entity.
+ */
+placeholder: boolean, 
+/**
+ * Dictionary entry the name was normalized to, with its synonyms.
+ */
+normalization?: DictionaryEntry, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type EntityTypeDefinition = { name: string, 
+/**
+ * Shown to the extraction model next to the type name.
+ */
+description: string, 
+/**
+ * Color the graph view draws entities of this type in, e.g. `#1f77b4`.
+ */
+color?: string, 
+/**
+ * The type this one specializes, e.g. `Organism` for `LifespanModel`.
+ */
+parent?: string, };
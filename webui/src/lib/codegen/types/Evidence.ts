@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Span } from "./Span";
+
+/**
+ * A quote supporting an extracted entity or relation, verified to occur in
+ * the chunk it was extracted from.
+ */
+export type Evidence = { chunk_id: string, 
+/**
+ * The quote as it appears in the chunk, which may differ from what the
+ * model returned in case and whitespace.
+ */
+quote: string, 
+/**
+ * Position of the quote in the chunk text.
+ */
+chunk_span: Span, 
+/**
+ * Position of the quote in the stored document, when the chunk's own
+ * position is known.
+ */
+span?: Span, };
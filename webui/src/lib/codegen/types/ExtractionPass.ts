@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What one extraction pass over a chunk added and what it cost. Stored on
+ * the chunk record under `extraction_passes`.
+ */
+export type ExtractionPass = { 
+/**
+ * 0 for the initial extraction, then 1 for the first gleaning pass.
+ */
+pass: number, entities_added: number, relationships_added: number, input_tokens: bigint, output_tokens: bigint, reasoning_tokens: bigint, };
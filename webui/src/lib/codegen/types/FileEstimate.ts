@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What ingesting one file would produce, without ingesting it.
+ */
+export type FileEstimate = { file_name: string, chunks: number, 
+/**
+ * Tokens across all chunks, overlap included.
+ */
+tokens: bigint, 
+/**
+ * Extraction calls if every gleaning pass runs; mapped CSV tables need none.
+ */
+llm_calls: bigint, estimated_input_tokens: bigint, estimated_output_tokens: bigint, 
+/**
+ * `None` when the model has no configured price.
+ */
+estimated_cost_usd?: number, 
+/**
+ * Why the file could not be estimated; it would be rejected on upload too.
+ */
+error?: string, };
@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Modality } from "./Modality";
 
-export type GraphSearchEdge = { relation_description: string, relationship_keywords: Array<string>, source_entity_id: string, target_entity_id: string, is_forward: boolean, };
+export type GraphSearchEdge = { relation_description: string, relationship_keywords: Array<string>, source_entity_id: string, target_entity_id: string, is_forward: boolean, confidence: number | null, modality: Modality, predicate: string | null, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Whether the text states a relationship, only suggests it or denies it.
+ */
+export type Modality = "asserted" | "hedged" | "negated";
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EntityTypeDefinition } from "./EntityTypeDefinition";
+import type { PredicateDefinition } from "./PredicateDefinition";
+
+/**
+ * The entity types extraction may assign, and optionally the predicates
+ * relations may be typed with, loaded from YAML.
+ */
+export type Ontology = { name: string, entity_types: Array<EntityTypeDefinition>, predicates: Array<PredicateDefinition>, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A relationship predicate such as `inhibits`. `domain` and `range` list
+ * the entity types its source and target may have, subtypes included; an
+ * empty list allows any type.
+ */
+export type PredicateDefinition = { name: string, description: string, domain: Array<string>, range: Array<string>, };
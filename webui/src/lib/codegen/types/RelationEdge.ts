@@ -1,3 +1,16 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DocumentMetadata } from "./DocumentMetadata";
+import type { Evidence } from "./Evidence";
+import type { Modality } from "./Modality";
+import type { SourcedDescription } from "./SourcedDescription";
 
-export type RelationEdge = { chunk_id: string, doc_id: string, relationship_description: string, relationship_keywords: Array<string>, source_entity_id: string, target_entity_id: string, };
+export type RelationEdge = { chunk_id: string, doc_id: string, relationship_description: string, descriptions: Array<SourcedDescription>, evidence: Array<Evidence>, relationship_keywords: Array<string>, source_entity_id: string, target_entity_id: string, doc_metadata: DocumentMetadata, 
+/**
+ * How strongly the source text supports the relation, from 0 to 1.
+ * Relations extracted before confidence was recorded have none.
+ */
+confidence: number | null, modality: Modality, hedge_cues: Array<string>, 
+/**
+ * Predicate from the ontology's vocabulary, e.g. `inhibits`.
+ */
+predicate: string | null, };
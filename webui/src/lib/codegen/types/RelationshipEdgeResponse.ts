@@ -1,3 +1,4 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Modality } from "./Modality";
 
-export type RelationshipEdgeResponse = { id: string, source_node_id: string, target_node_id: string, relation_description: string, };
+export type RelationshipEdgeResponse = { id: string, source_node_id: string, target_node_id: string, relation_description: string, confidence: number | null, modality: Modality, predicate: string | null, };
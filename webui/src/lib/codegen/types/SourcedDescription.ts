@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A description as extracted from one chunk. Entity and relation records
+ * keep all of them under `descriptions`.
+ */
+export type SourcedDescription = { chunk_id: string, description: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Byte range `start..end` in a document or chunk.
+ */
+export type Span = { start: number, end: number, };
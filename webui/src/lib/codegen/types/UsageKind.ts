@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What a model call was made for.
+ */
+export type UsageKind = "extraction" | "description_summary" | "document_summary" | "entity_resolution" | "semantic_chunking";
@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { UsageKind } from "./UsageKind";
+
+/**
+ * Tokens and cost of one LLM call, attributed to the document, upload
+ * batch and, for extraction, chunk it was made for.
+ */
+export type UsageRecord = { doc_id: string, chunk_id?: string, track_id?: string, 
+/**
+ * Records written before other calls were recorded are extractions.
+ */
+kind: UsageKind, model: string, 
+/**
+ * Extraction pass; 0 is the initial extraction, later ones gleaning.
+ */
+pass: number, input_tokens: bigint, output_tokens: bigint, reasoning_tokens: bigint, 
+/**
+ * `None` when the model has no configured price.
+ */
+cost_usd?: number, created_at: string, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type UsageTotals = { calls: bigint, input_tokens: bigint, output_tokens: bigint, reasoning_tokens: bigint, 
+/**
+ * Cost of the priced calls.
+ */
+cost_usd: number, 
+/**
+ * Calls to models without a configured price, missing from `cost_usd`.
+ */
+unpriced_calls: bigint, };